@@ -1,2 +1,291 @@
-#[derive(Debug, Clone, Copy)]
-pub enum Node {}
+use std::fmt;
+
+use crate::span::Span;
+
+/// A node in the abstract syntax tree consisting of some kind `K` along with
+/// the span of the input from which it was parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node<K> {
+    pub kind: K,
+    pub span: Span,
+}
+
+pub type Stat = Node<StatKind>;
+pub type TypeExpr = Node<TypeExprKind>;
+
+/// An expression node. Unlike other nodes, expressions also have a type which
+/// is [`Type::Unknown`] until determined during type checking.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub ty: Type,
+    pub span: Span,
+}
+
+/// A Kobe function definition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: Ident,
+    /// Text of the doc comments (`---`) immediately preceding the function,
+    /// one line per comment.
+    pub doc: Option<String>,
+    /// Whether the function is marked `pub` and so should be exported from
+    /// the generated module.
+    pub public: bool,
+    pub params: Vec<Param>,
+    /// Span of the parameter list, including the brackets.
+    pub params_span: Span,
+    pub return_type: Option<TypeExpr>,
+    /// The type of value returned by this function, resolved from
+    /// `return_type` during type checking.
+    pub returns: Type,
+    pub body: Vec<Stat>,
+    pub span: Span,
+}
+
+/// A single function parameter and its type (i.e., `ident ':' type`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    pub name: Ident,
+    pub param_type: TypeExpr,
+    /// Resolved from `param_type` during type checking.
+    pub ty: Type,
+}
+
+/// An identifier (such as a variable or type name) along with its span in the
+/// input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ident {
+    pub name: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatKind {
+    Expr(Expr),
+    Let {
+        name: Ident,
+        /// As for [`Function::doc`].
+        doc: Option<String>,
+        var_type: Option<TypeExpr>,
+        value: Option<Expr>,
+        /// Resolved from `var_type` or, if there is no type annotation,
+        /// inferred from `value` during type checking.
+        ty: Type,
+    },
+    Assign {
+        target: Ident,
+        value: Expr,
+    },
+    /// Assignment using one of `+=`, `-=`, `*=` or `/=`.
+    CompoundAssign {
+        target: Ident,
+        op: BinOp,
+        value: Expr,
+    },
+    Return(Expr),
+    If {
+        condition: Expr,
+        then_body: Vec<Stat>,
+        else_body: Vec<Stat>,
+    },
+    While {
+        condition: Expr,
+        body: Vec<Stat>,
+    },
+    For {
+        var: Ident,
+        iterable: Expr,
+        body: Vec<Stat>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    IntLiteral(i64),
+    FloatLiteral(f64),
+    CharLiteral(char),
+    StringLiteral(String),
+    /// An interpolated string (e.g., `"x = {x}"`), made up of string literals
+    /// and the interpolated expressions between them, in order. Each part is
+    /// converted to a string and the results concatenated.
+    Interpolation(Vec<Expr>),
+    BoolLiteral(bool),
+    List(Vec<Expr>),
+    Identifier(String),
+    Call {
+        function: String,
+        args: Vec<Expr>,
+    },
+    /// The half-open range of integers from `start` up to (but not including)
+    /// `end`. May only appear as what a `for` loop iterates over.
+    Range {
+        start: Box<Expr>,
+        end: Box<Expr>,
+    },
+    /// Indexing into a list or string (e.g., `xs[i]`).
+    Index {
+        target: Box<Expr>,
+        index: Box<Expr>,
+    },
+    Binary {
+        op: BinOp,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    Unary {
+        op: UnOp,
+        operand: Box<Expr>,
+    },
+}
+
+/// A type as written in the source (e.g., `int` or `[float]`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeExprKind {
+    Named(String),
+    List(Box<TypeExpr>),
+}
+
+impl fmt::Display for TypeExprKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TypeExprKind::Named(name) => write!(f, "{name}"),
+            TypeExprKind::List(inner) => write!(f, "[{}]", inner.kind),
+        }
+    }
+}
+
+/// A Kobe type as determined during type checking.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Int,
+    Float,
+    Char,
+    String,
+    Bool,
+    List(Box<Type>),
+    /// The 'type' of functions which do not return a value.
+    Void,
+    /// The type of an expression that has not been (or could not be)
+    /// determined. Considered compatible with every other type so that a single
+    /// mistake does not produce a cascade of type errors.
+    Unknown,
+}
+
+impl Type {
+    /// Whether a value of this type can be used where a value of the other type
+    /// is expected.
+    pub fn is_compatible_with(&self, other: &Type) -> bool {
+        match (self, other) {
+            (Type::Unknown, _) | (_, Type::Unknown) => true,
+            (Type::List(a), Type::List(b)) => a.is_compatible_with(b),
+            (a, b) => a == b,
+        }
+    }
+
+    /// Whether this type is unknown or contains an unknown type (e.g., the
+    /// element type of an empty list).
+    pub fn contains_unknown(&self) -> bool {
+        match self {
+            Type::Unknown => true,
+            Type::List(inner) => inner.contains_unknown(),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Float => write!(f, "float"),
+            Type::Char => write!(f, "char"),
+            Type::String => write!(f, "string"),
+            Type::Bool => write!(f, "bool"),
+            Type::List(inner) => write!(f, "[{inner}]"),
+            Type::Void => write!(f, "nothing"),
+            Type::Unknown => write!(f, "{{unknown}}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Equivalent,
+    NotEquivalent,
+    And,
+    Or,
+    LessThan,
+    GreaterThan,
+    LessThanOrEqual,
+    GreaterThanOrEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+impl BinOp {
+    pub const LOWEST_PRECEDENCE: u8 = 1;
+
+    /// The precedence of this operator, with higher values binding more
+    /// tightly. All binary operators are left-associative. From lowest to
+    /// highest:
+    ///
+    /// 1. `or`
+    /// 2. `and`
+    /// 3. `==`, `!=`
+    /// 4. `<`, `>`, `<=`, `>=`
+    /// 5. `+`, `-`
+    /// 6. `*`, `/`
+    ///
+    /// Unary operators (`-`, `!`) bind more tightly than any binary operator.
+    pub fn precedence(self) -> u8 {
+        match self {
+            BinOp::Or => 1,
+            BinOp::And => 2,
+            BinOp::Equivalent | BinOp::NotEquivalent => 3,
+            BinOp::LessThan
+            | BinOp::GreaterThan
+            | BinOp::LessThanOrEqual
+            | BinOp::GreaterThanOrEqual => 4,
+            BinOp::Add | BinOp::Subtract => 5,
+            BinOp::Multiply | BinOp::Divide => 6,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnOp {
+    Negate,
+    Not,
+}
+
+impl fmt::Display for BinOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            BinOp::Equivalent => "==",
+            BinOp::NotEquivalent => "!=",
+            BinOp::And => "and",
+            BinOp::Or => "or",
+            BinOp::LessThan => "<",
+            BinOp::GreaterThan => ">",
+            BinOp::LessThanOrEqual => "<=",
+            BinOp::GreaterThanOrEqual => ">=",
+            BinOp::Add => "+",
+            BinOp::Subtract => "-",
+            BinOp::Multiply => "*",
+            BinOp::Divide => "/",
+        };
+        write!(f, "{s}")
+    }
+}
+
+impl fmt::Display for UnOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UnOp::Negate => write!(f, "-"),
+            UnOp::Not => write!(f, "!"),
+        }
+    }
+}
@@ -0,0 +1,1577 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::Read,
+    mem,
+};
+
+use log::debug;
+
+use crate::{
+    ast::{BinOp, Expr, ExprKind, Function, Stat, StatKind, Type, TypeExpr, TypeExprKind, UnOp},
+    error::{Error, ErrorKind, Label, LintKind, Severity, TypeErrorKind, MAX_ERRORS},
+    lint::{Level, LintLevels},
+    parse::Parser,
+    span::Span,
+};
+
+/// The Kobe type checker. Takes the functions produced by a [`Parser`],
+/// ensuring that they are well-typed and annotating each expression with its
+/// type for use during code generation. Lints are also run, as they rely on
+/// the same knowledge of which variables are in scope.
+pub struct TypeChecker<R> {
+    parser: Parser<R>,
+    /// Signatures of every function in the program, so that functions can be
+    /// called before they are defined.
+    functions: HashMap<String, Signature>,
+    /// Variables in scope, with the innermost scope last.
+    scopes: Vec<HashMap<String, Variable>>,
+    /// Number of variables declared so far, used to give each a unique ID.
+    variable_count: usize,
+    /// IDs of the variables which may not have been assigned a value at the
+    /// current point in the function being checked.
+    unassigned: HashSet<usize>,
+    /// IDs of the variables which have already been reported as read before
+    /// being assigned, so that each is only reported once.
+    reported_unassigned: HashSet<usize>,
+    /// Return type of the function currently being checked.
+    return_type: Type,
+    /// Span of the return type annotation of the function currently being
+    /// checked (if any).
+    return_type_span: Option<Span>,
+    /// Span of the parameter list of the function currently being checked.
+    params_span: Span,
+    errors: Vec<Error>,
+    /// Whether errors beyond [`MAX_ERRORS`] were dropped.
+    errors_truncated: bool,
+    warnings: Vec<Error>,
+    lint_levels: LintLevels,
+}
+
+impl<R: Read> TypeChecker<R> {
+    pub fn new(parser: Parser<R>) -> Self {
+        TypeChecker {
+            parser,
+            functions: HashMap::new(),
+            scopes: Vec::new(),
+            variable_count: 0,
+            unassigned: HashSet::new(),
+            reported_unassigned: HashSet::new(),
+            return_type: Type::Void,
+            return_type_span: None,
+            params_span: Span::default(),
+            errors: Vec::new(),
+            errors_truncated: false,
+            warnings: Vec::new(),
+            lint_levels: LintLevels::default(),
+        }
+    }
+
+    /// Set the level at which each lint is reported (by default, all warn).
+    pub fn with_lint_levels(mut self, lint_levels: LintLevels) -> Self {
+        self.lint_levels = lint_levels;
+        self
+    }
+
+    /// The parser from which functions are read.
+    pub fn parser(&self) -> &Parser<R> {
+        &self.parser
+    }
+
+    pub fn parser_mut(&mut self) -> &mut Parser<R> {
+        &mut self.parser
+    }
+
+    /// Type check the functions making up the program, having parsed them
+    /// with [`Parser::parse_all`] (using [`TypeChecker::parser_mut`]). Any
+    /// warnings are returned along with the checked functions, or after the
+    /// errors should checking fail. Should only be called once.
+    pub fn check(
+        &mut self,
+        mut functions: Vec<Function>,
+    ) -> Result<(Vec<Function>, Vec<Error>), Vec<Error>> {
+        for function in &mut functions {
+            self.declare_function(function);
+        }
+
+        for function in &mut functions {
+            debug!("checking function '{}'", function.name.name);
+            self.check_function(function);
+        }
+
+        if self.errors.is_empty() {
+            Ok((functions, mem::take(&mut self.warnings)))
+        } else {
+            let mut errors = mem::take(&mut self.errors);
+            errors.append(&mut self.warnings);
+            Err(errors)
+        }
+    }
+
+    /// Whether more than [`MAX_ERRORS`] errors were found while parsing or
+    /// checking, so that some were not reported.
+    pub fn errors_truncated(&self) -> bool {
+        self.errors_truncated || self.parser.errors_truncated()
+    }
+
+    /// Resolve the parameter and return types of a function, recording its
+    /// signature for use when checking calls.
+    fn declare_function(&mut self, function: &mut Function) {
+        for param in &mut function.params {
+            param.ty = self.resolve_type(&param.param_type);
+        }
+
+        function.returns = match &function.return_type {
+            Some(return_type) => self.resolve_type(return_type),
+            None => Type::Void,
+        };
+
+        let name = &function.name;
+        if let Some(existing) = self.functions.get(&name.name) {
+            let error = type_error(
+                TypeErrorKind::DuplicateFunction(name.name.clone()),
+                &name.span,
+            )
+            .with_secondary_label(existing.name_span.clone(), "first defined here");
+            self.report(error);
+        } else {
+            let signature = Signature {
+                params: function
+                    .params
+                    .iter()
+                    .map(|p| (p.ty.clone(), p.name.span.to(&p.param_type.span)))
+                    .collect(),
+                returns: function.returns.clone(),
+                name_span: name.span.clone(),
+            };
+            self.functions.insert(name.name.clone(), signature);
+        }
+    }
+
+    fn check_function(&mut self, function: &mut Function) {
+        self.scopes = vec![HashMap::new()];
+
+        for param in &function.params {
+            if let Some(existing) = self.scopes[0].get(&param.name.name) {
+                let kind = TypeErrorKind::DuplicateParameter(param.name.name.clone());
+                let error = type_error(kind, &param.name.span)
+                    .with_secondary_label(existing.span.clone(), "first declared here");
+                self.report(error);
+            }
+            self.declare(&param.name.name, param.ty.clone(), &param.name.span, true);
+        }
+
+        self.return_type = function.returns.clone();
+        self.return_type_span = function.return_type.as_ref().map(|t| t.span.clone());
+        self.params_span = function.params_span.clone();
+
+        self.check_block(&mut function.body);
+        self.pop_scope();
+
+        if let (Some(return_type), false) = (&function.return_type, always_returns(&function.body))
+        {
+            let error = type_error(TypeErrorKind::MissingReturn, &return_type.span)
+                .with_label(format!("function returns {}", function.returns))
+                .with_help("add a 'return' statement to the end of the function");
+            self.report(error);
+        }
+    }
+
+    /// Check a sequence of statements in a new scope.
+    fn check_block(&mut self, stats: &mut [Stat]) {
+        self.scopes.push(HashMap::new());
+        for stat in stats.iter_mut() {
+            self.check_stat(stat);
+        }
+
+        let returning = stats.iter().position(always_returns_stat);
+        if let Some(i) = returning.filter(|&i| i + 1 < stats.len()) {
+            let span = stats[i + 1].span.to(&stats[stats.len() - 1].span);
+            let error = lint_error(LintKind::UnreachableCode, &span).with_secondary_label(
+                stats[i].span.clone(),
+                "any code following this statement is unreachable",
+            );
+            self.report_lint(error);
+        }
+
+        self.pop_scope();
+    }
+
+    fn check_stat(&mut self, stat: &mut Stat) {
+        match &mut stat.kind {
+            StatKind::Expr(expr) => {
+                self.check_expr(expr);
+            }
+
+            StatKind::Let {
+                name,
+                var_type,
+                value,
+                ty,
+                ..
+            } => {
+                // declared without a value, so must be assigned before use
+                let is_unassigned = var_type.is_some() && value.is_none();
+
+                *ty = match (var_type, value) {
+                    (Some(var_type), value) => {
+                        let declared_type = self.resolve_type(var_type);
+                        if let Some(value) = value {
+                            let reason = Label {
+                                span: var_type.span.clone(),
+                                message: "expected due to this type annotation".to_string(),
+                            };
+                            self.check_expr_is_because(value, &declared_type, Some(reason));
+                        }
+                        declared_type
+                    }
+
+                    (None, Some(value)) => {
+                        let error_count = self.errors.len();
+                        let inferred_type = self.check_value(value);
+
+                        // no need to report if the unknown type is due to an
+                        // error in the initial value
+                        if inferred_type.contains_unknown() && self.errors.len() == error_count {
+                            let kind = TypeErrorKind::CannotInferType(Some(name.name.clone()));
+                            let error = type_error(kind, &name.span)
+                                .with_secondary_label(value.span.clone(), "type is not known")
+                                .with_help(format!(
+                                    "add a type annotation (e.g., 'let {}: [int]')",
+                                    name.name
+                                ));
+                            self.report(error);
+                        }
+                        inferred_type
+                    }
+
+                    (None, None) => {
+                        let kind = TypeErrorKind::MissingTypeAnnotation(name.name.clone());
+                        let error = type_error(kind, &name.span).with_help(format!(
+                            "add a type annotation (e.g., 'let {0}: int') or an initial value \
+                             (e.g., 'let {0} = 0')",
+                            name.name
+                        ));
+                        self.report(error);
+                        Type::Unknown
+                    }
+                };
+                let id = self.declare(&name.name, ty.clone(), &name.span, false);
+                if is_unassigned {
+                    self.unassigned.insert(id);
+                }
+            }
+
+            StatKind::Assign { target, value } => {
+                // the value is evaluated before the variable is assigned to
+                let target_type = self
+                    .find_variable(&target.name)
+                    .map_or(Type::Unknown, |variable| variable.ty.clone());
+                let reason = self.declared_here(&target.name);
+                self.check_expr_is_because(value, &target_type, reason);
+                self.lookup(&target.name, &target.span, Access::Write);
+            }
+
+            StatKind::CompoundAssign { target, op, value } => {
+                let target_type = self.lookup(&target.name, &target.span, Access::Update);
+                let value_type = self.check_value(value);
+
+                let is_valid = binary_result_type(*op, &target_type, &value_type)
+                    .is_some_and(|result_type| result_type.is_compatible_with(&target_type));
+
+                if !is_valid {
+                    let error = type_error(
+                        TypeErrorKind::InvalidOperands {
+                            op: *op,
+                            left: target_type.clone(),
+                            right: value_type.clone(),
+                        },
+                        &stat.span,
+                    )
+                    .with_secondary_label(target.span.clone(), target_type.to_string())
+                    .with_secondary_label(value.span.clone(), value_type.to_string());
+                    self.report(error);
+                }
+            }
+
+            StatKind::Return(value) => {
+                if self.return_type == Type::Void {
+                    let ty = self.check_expr(value);
+                    let mut error = type_error(TypeErrorKind::UnexpectedReturnValue, &stat.span);
+                    if !ty.contains_unknown() && ty != Type::Void {
+                        error = error.with_suggestion(
+                            self.params_span.following(),
+                            format!(" -> {ty}"),
+                            format!("add a return type to the function signature: '-> {ty}'"),
+                        );
+                    }
+                    self.report(error);
+                } else {
+                    let return_type = self.return_type.clone();
+                    let reason = self.return_type_span.clone().map(|span| Label {
+                        span,
+                        message: "expected because of this return type".to_string(),
+                    });
+                    self.check_expr_is_because(value, &return_type, reason);
+                }
+            }
+
+            StatKind::If {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                self.check_expr_is(condition, &Type::Bool);
+
+                let before = self.unassigned.clone();
+                self.check_block(then_body);
+                let after_then = std::mem::replace(&mut self.unassigned, before);
+                self.check_block(else_body);
+
+                // a variable may be unassigned after the `if` if it may be
+                // unassigned at the end of either branch which reaches there
+                match (always_returns(then_body), always_returns(else_body)) {
+                    (true, _) => {}
+                    (false, true) => self.unassigned = after_then,
+                    (false, false) => self.unassigned.extend(after_then),
+                }
+            }
+
+            StatKind::While { condition, body } => {
+                self.check_expr_is(condition, &Type::Bool);
+
+                // the body may not be executed at all
+                let before = self.unassigned.clone();
+                self.check_block(body);
+                self.unassigned = before;
+            }
+
+            StatKind::For {
+                var,
+                iterable,
+                body,
+            } => {
+                let error_count = self.errors.len();
+                let element_type = match self.check_iterable(iterable) {
+                    Type::List(element_type) => *element_type,
+                    Type::String => Type::Char,
+                    Type::Unknown => Type::Unknown,
+                    ty => {
+                        self.error(TypeErrorKind::NotIterable(ty), &iterable.span);
+                        Type::Unknown
+                    }
+                };
+
+                // e.g., iterating over an empty list literal
+                if element_type.contains_unknown() && self.errors.len() == error_count {
+                    self.error(
+                        TypeErrorKind::CannotInferType(Some(var.name.clone())),
+                        &var.span,
+                    );
+                }
+
+                let before = self.unassigned.clone();
+                self.scopes.push(HashMap::new());
+                self.declare(&var.name, element_type, &var.span, false);
+                self.check_block(body);
+                self.pop_scope();
+                self.unassigned = before;
+            }
+        }
+    }
+
+    /// Check an expression, producing an error if its type is not compatible
+    /// with the expected type.
+    fn check_expr_is(&mut self, expr: &mut Expr, expected: &Type) {
+        self.check_expr_is_because(expr, expected, None);
+    }
+
+    /// As [`TypeChecker::check_expr_is`], but with a label to include in any
+    /// error pointing out why the type is expected (e.g., a variable's
+    /// declaration).
+    fn check_expr_is_because(&mut self, expr: &mut Expr, expected: &Type, reason: Option<Label>) {
+        let found = self.check_value(expr);
+
+        if !found.is_compatible_with(expected) {
+            let kind = TypeErrorKind::Mismatch {
+                expected: expected.clone(),
+                found,
+            };
+            let mut error = type_error(kind, &expr.span).with_label(format!("expected {expected}"));
+            error.secondary_labels.extend(reason);
+            self.report(error);
+        } else if found.contains_unknown() {
+            // e.g., an empty list takes on the expected list type
+            expr.ty = expected.clone();
+        }
+    }
+
+    /// Check what a `for` loop iterates over, treating a range as a list of
+    /// integers.
+    fn check_iterable(&mut self, iterable: &mut Expr) -> Type {
+        match &mut iterable.kind {
+            ExprKind::Range { start, end } => {
+                self.check_expr_is(start, &Type::Int);
+                self.check_expr_is(end, &Type::Int);
+                Type::List(Box::new(Type::Int))
+            }
+            _ => self.check_value(iterable),
+        }
+    }
+
+    /// Check an expression whose value is used, producing an error if it is a
+    /// call to a function which does not return a value.
+    fn check_value(&mut self, expr: &mut Expr) -> Type {
+        let ty = self.check_expr(expr);
+
+        match (&ty, &expr.kind) {
+            (Type::Void, ExprKind::Call { function, .. }) => {
+                let name_span = self.functions[function].name_span.clone();
+                let error = type_error(TypeErrorKind::NoValue(function.clone()), &expr.span)
+                    .with_secondary_label(name_span, "defined here without a return type");
+                self.report(error);
+                expr.ty = Type::Unknown;
+                Type::Unknown
+            }
+            _ => ty,
+        }
+    }
+
+    /// Determine the type of an expression (and all sub-expressions), storing
+    /// it on the expression itself as well as returning it.
+    fn check_expr(&mut self, expr: &mut Expr) -> Type {
+        let span = expr.span.clone();
+
+        let ty = match &mut expr.kind {
+            ExprKind::IntLiteral(_) => Type::Int,
+            ExprKind::FloatLiteral(_) => Type::Float,
+            ExprKind::CharLiteral(_) => Type::Char,
+            ExprKind::StringLiteral(_) => Type::String,
+
+            ExprKind::Interpolation(parts) => {
+                for part in parts {
+                    match self.check_value(part) {
+                        Type::Int
+                        | Type::Float
+                        | Type::Char
+                        | Type::Bool
+                        | Type::String
+                        | Type::Unknown => {}
+                        ty => {
+                            let error =
+                                type_error(TypeErrorKind::NotInterpolatable(ty), &part.span)
+                                    .with_note(
+                                        "only int, float, char, bool and string values can be \
+                                     interpolated",
+                                    );
+                            self.report(error);
+                        }
+                    }
+                }
+                Type::String
+            }
+            ExprKind::BoolLiteral(_) => Type::Bool,
+
+            ExprKind::List(items) => {
+                let mut element_type = Type::Unknown;
+
+                for item in items.iter_mut() {
+                    let item_type = self.check_value(item);
+
+                    if !item_type.is_compatible_with(&element_type) {
+                        self.error(
+                            TypeErrorKind::Mismatch {
+                                expected: element_type.clone(),
+                                found: item_type,
+                            },
+                            &item.span,
+                        );
+                    } else if element_type.contains_unknown() {
+                        element_type = item_type;
+                    }
+                }
+
+                // give elements with partially unknown types (e.g., empty
+                // lists) the type shared by the other elements
+                for item in items.iter_mut() {
+                    if item.ty.contains_unknown() {
+                        item.ty = element_type.clone();
+                    }
+                }
+
+                Type::List(Box::new(element_type))
+            }
+
+            ExprKind::Identifier(name) => self.lookup(name, &span, Access::Read),
+
+            ExprKind::Call { function, args } => match self.functions.get(function).cloned() {
+                Some(signature) => {
+                    if args.len() != signature.params.len() {
+                        let kind = TypeErrorKind::WrongArgumentCount {
+                            function: function.clone(),
+                            expected: signature.params.len(),
+                            found: args.len(),
+                        };
+                        let error = type_error(kind, &span)
+                            .with_secondary_label(signature.name_span.clone(), "defined here");
+                        self.report(error);
+                    }
+
+                    for (arg, (param_type, param_span)) in args.iter_mut().zip(&signature.params) {
+                        let reason = Label {
+                            span: param_span.clone(),
+                            message: "parameter declared here".to_string(),
+                        };
+                        self.check_expr_is_because(arg, param_type, Some(reason));
+                    }
+                    for arg in args.iter_mut().skip(signature.params.len()) {
+                        self.check_value(arg);
+                    }
+                    signature.returns
+                }
+                None => {
+                    self.error(TypeErrorKind::UndefinedFunction(function.clone()), &span);
+                    for arg in args.iter_mut() {
+                        self.check_value(arg);
+                    }
+                    Type::Unknown
+                }
+            },
+
+            ExprKind::Range { .. } => unreachable!("range outside of a for loop"),
+
+            ExprKind::Index { target, index } => {
+                let target_type = self.check_value(target);
+                self.check_expr_is(index, &Type::Int);
+
+                match target_type {
+                    Type::List(element_type) => *element_type,
+                    Type::String => Type::Char,
+                    Type::Unknown => Type::Unknown,
+                    ty => {
+                        self.error(TypeErrorKind::NotIndexable(ty), &span);
+                        Type::Unknown
+                    }
+                }
+            }
+
+            ExprKind::Binary { op, left, right } => {
+                let left_type = self.check_value(left);
+                let right_type = self.check_value(right);
+
+                binary_result_type(*op, &left_type, &right_type).unwrap_or_else(|| {
+                    let error = type_error(
+                        TypeErrorKind::InvalidOperands {
+                            op: *op,
+                            left: left_type.clone(),
+                            right: right_type.clone(),
+                        },
+                        &span,
+                    )
+                    .with_secondary_label(left.span.clone(), left_type.to_string())
+                    .with_secondary_label(right.span.clone(), right_type.to_string());
+                    self.report(error);
+                    Type::Unknown
+                })
+            }
+
+            ExprKind::Unary { op, operand } => {
+                let operand_type = self.check_value(operand);
+
+                match (*op, &operand_type) {
+                    (_, Type::Unknown) => Type::Unknown,
+                    (UnOp::Negate, Type::Int | Type::Float) => operand_type,
+                    (UnOp::Not, Type::Bool) => Type::Bool,
+                    _ => {
+                        self.error(
+                            TypeErrorKind::InvalidOperand {
+                                op: *op,
+                                operand: operand_type,
+                            },
+                            &span,
+                        );
+                        Type::Unknown
+                    }
+                }
+            }
+        };
+
+        // code cannot be generated for an unknown type, which is fine as long
+        // as some error has been reported (e.g., for an unknown parameter type
+        // this expression depends on) so that code generation never happens
+        if ty == Type::Unknown && self.errors.is_empty() {
+            let mut error = type_error(TypeErrorKind::CannotInferType(None), &span);
+            if let ExprKind::Index { target, .. } = &expr.kind {
+                error = error
+                    .with_secondary_label(target.span.clone(), "element type is not known")
+                    .with_help("give the list a type annotation (e.g., 'let xs: [int] = []')");
+            }
+            self.report(error);
+        }
+
+        expr.ty = ty.clone();
+        ty
+    }
+
+    /// Get the type of the named type annotation.
+    fn resolve_type(&mut self, type_expr: &TypeExpr) -> Type {
+        match &type_expr.kind {
+            TypeExprKind::Named(name) => match name.as_str() {
+                "int" => Type::Int,
+                "float" => Type::Float,
+                "char" => Type::Char,
+                "string" => Type::String,
+                "bool" => Type::Bool,
+                _ => {
+                    self.error(TypeErrorKind::UnknownType(name.clone()), &type_expr.span);
+                    Type::Unknown
+                }
+            },
+            TypeExprKind::List(inner) => Type::List(Box::new(self.resolve_type(inner))),
+        }
+    }
+
+    /// Declare a variable in the innermost scope, returning its ID.
+    fn declare(&mut self, name: &str, ty: Type, span: &Span, is_parameter: bool) -> usize {
+        // parameters can only shadow each other, which is already an error
+        if let Some(previous) = self.find_variable(name).filter(|_| !is_parameter) {
+            let error = lint_error(LintKind::Shadowing(name.to_string()), span)
+                .with_secondary_label(previous.span.clone(), "previously declared here");
+            self.report_lint(error);
+        }
+
+        let id = self.variable_count;
+        self.variable_count += 1;
+
+        let variable = Variable {
+            ty,
+            span: span.clone(),
+            id,
+            is_parameter,
+            read: false,
+            assigned: false,
+        };
+        let replaced = self
+            .scopes
+            .last_mut()
+            .and_then(|scope| scope.insert(name.to_string(), variable));
+        if let Some(replaced) = replaced.filter(|_| !is_parameter) {
+            self.check_used(name, replaced);
+        }
+
+        id
+    }
+
+    fn find_variable(&self, name: &str) -> Option<&Variable> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    /// Get the type of the variable with the given name, recording how it is
+    /// accessed and producing an error at the given position if it is not in
+    /// scope.
+    fn lookup(&mut self, name: &str, span: &Span, access: Access) -> Type {
+        let variable = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(name));
+        let Some(variable) = variable else {
+            self.error(TypeErrorKind::UndefinedVariable(name.to_string()), span);
+            return Type::Unknown;
+        };
+
+        match access {
+            Access::Read => variable.read = true,
+            Access::Write | Access::Update => variable.assigned = true,
+        }
+        let (ty, id, declared_at) = (variable.ty.clone(), variable.id, variable.span.clone());
+
+        if access == Access::Write {
+            self.unassigned.remove(&id);
+        } else if self.unassigned.contains(&id) && self.reported_unassigned.insert(id) {
+            let error = lint_error(LintKind::ReadBeforeAssignment(name.to_string()), span)
+                .with_secondary_label(
+                    declared_at,
+                    format!("'{name}' declared here without a value"),
+                );
+            self.report_lint(error);
+        }
+
+        ty
+    }
+
+    /// Leave the innermost scope, reporting any of its variables which were
+    /// never used.
+    fn pop_scope(&mut self) {
+        let Some(scope) = self.scopes.pop() else {
+            return;
+        };
+
+        let mut variables: Vec<_> = scope.into_iter().collect();
+        variables.sort_by_key(|(_, variable)| variable.id);
+        for (name, variable) in variables {
+            self.check_used(&name, variable);
+        }
+    }
+
+    /// Report a variable which has gone out of scope if it was never read,
+    /// unless its name starts with an underscore.
+    fn check_used(&mut self, name: &str, variable: Variable) {
+        if variable.read || name.starts_with('_') {
+            return;
+        }
+
+        let error = if variable.assigned {
+            lint_error(LintKind::UnusedAssignment(name.to_string()), &variable.span)
+        } else {
+            let kind = if variable.is_parameter {
+                LintKind::UnusedParameter(name.to_string())
+            } else {
+                LintKind::UnusedVariable(name.to_string())
+            };
+            lint_error(kind, &variable.span).with_suggestion(
+                variable.span.clone(),
+                format!("_{name}"),
+                format!("if this is intentional, prefix it with an underscore: '_{name}'"),
+            )
+        };
+        self.report_lint(error);
+    }
+
+    /// A label pointing out the declaration of the named variable (if it is
+    /// in scope) and its type.
+    fn declared_here(&self, name: &str) -> Option<Label> {
+        let variable = self.find_variable(name)?;
+        Some(Label {
+            span: variable.span.clone(),
+            message: format!("'{name}' declared here as {}", variable.ty),
+        })
+    }
+
+    fn error(&mut self, kind: TypeErrorKind, span: &Span) {
+        self.report(type_error(kind, span));
+    }
+
+    fn report(&mut self, error: Error) {
+        if self.errors.len() < MAX_ERRORS {
+            let error = self.parser.with_source(error);
+            self.errors.push(error);
+        } else {
+            self.errors_truncated = true;
+        }
+    }
+
+    /// Report a problem found by a lint as an error or a warning, depending on
+    /// the lint's level.
+    fn report_lint(&mut self, error: Error) {
+        let ErrorKind::Lint(kind) = &error.kind else {
+            unreachable!("not produced by a lint: {:?}", error.kind);
+        };
+        let lint = kind.lint();
+        let error = error.with_note(self.lint_levels.explain(lint));
+
+        match self.lint_levels.level(lint) {
+            Level::Allow => {}
+            Level::Warn => {
+                let warning = self
+                    .parser
+                    .with_source(error.with_severity(Severity::Warning));
+                self.warnings.push(warning);
+            }
+            Level::Deny => self.report(error),
+        }
+    }
+}
+
+fn type_error(kind: TypeErrorKind, span: &Span) -> Error {
+    Error::new(ErrorKind::Type(kind), span.clone())
+}
+
+fn lint_error(kind: LintKind, span: &Span) -> Error {
+    Error::new(ErrorKind::Lint(kind), span.clone())
+}
+
+/// A variable in scope, along with the span of its name where it was declared
+/// and how it has been used so far.
+struct Variable {
+    ty: Type,
+    span: Span,
+    id: usize,
+    is_parameter: bool,
+    read: bool,
+    /// Whether the variable has been assigned to since its declaration.
+    assigned: bool,
+}
+
+/// How a variable is accessed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Access {
+    Read,
+    /// Assignment of a new value (i.e., `=`).
+    Write,
+    /// Compound assignment (e.g., `+=`), which depends on the variable's
+    /// existing value but does not count as reading it.
+    Update,
+}
+
+/// The parameter and return types of a function, along with the span of each
+/// parameter and of the function's name.
+#[derive(Clone)]
+struct Signature {
+    params: Vec<(Type, Span)>,
+    returns: Type,
+    name_span: Span,
+}
+
+/// Get the type produced by applying the given binary operator to operands of
+/// the given types, or `None` if the operator cannot be applied to them.
+fn binary_result_type(op: BinOp, left: &Type, right: &Type) -> Option<Type> {
+    match op {
+        BinOp::Add | BinOp::Subtract | BinOp::Multiply | BinOp::Divide => match (left, right) {
+            (Type::Unknown, other) | (other, Type::Unknown) => Some(other.clone()),
+            (Type::Int, Type::Int) => Some(Type::Int),
+            (Type::Float, Type::Float) => Some(Type::Float),
+            _ => None,
+        },
+
+        BinOp::LessThan
+        | BinOp::GreaterThan
+        | BinOp::LessThanOrEqual
+        | BinOp::GreaterThanOrEqual => match (left, right) {
+            (Type::Unknown, _) | (_, Type::Unknown) => Some(Type::Bool),
+            (Type::Int, Type::Int) | (Type::Float, Type::Float) | (Type::Char, Type::Char) => {
+                Some(Type::Bool)
+            }
+            _ => None,
+        },
+
+        BinOp::Equivalent | BinOp::NotEquivalent => match (left, right) {
+            (Type::Unknown, _) | (_, Type::Unknown) => Some(Type::Bool),
+            (Type::List(_) | Type::Void, _) => None,
+            (left, right) if left == right => Some(Type::Bool),
+            _ => None,
+        },
+
+        BinOp::And | BinOp::Or => match (left, right) {
+            (Type::Unknown | Type::Bool, Type::Unknown | Type::Bool) => Some(Type::Bool),
+            _ => None,
+        },
+    }
+}
+
+/// Whether the given sequence of statements is guaranteed to execute a
+/// `return` statement.
+fn always_returns(stats: &[Stat]) -> bool {
+    stats.iter().any(always_returns_stat)
+}
+
+fn always_returns_stat(stat: &Stat) -> bool {
+    match &stat.kind {
+        StatKind::Return(_) => true,
+        StatKind::If {
+            then_body,
+            else_body,
+            ..
+        } => always_returns(then_body) && always_returns(else_body),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{lex::Lexer, lint::Lint};
+
+    /// Parse and type check the given input.
+    fn check_all(
+        input: &str,
+        levels: LintLevels,
+    ) -> Result<(Vec<Function>, Vec<Error>), Vec<Error>> {
+        let lexer = Lexer::new(Cursor::new(input), "test".to_string());
+        let mut checker = TypeChecker::new(Parser::new(lexer)).with_lint_levels(levels);
+        let functions = checker.parser_mut().parse_all()?;
+        checker.check(functions)
+    }
+
+    /// Type check the given input, ignoring any warnings.
+    fn check(input: &str) -> Result<Vec<Function>, Vec<Error>> {
+        check_with_levels(input, LintLevels::default())
+    }
+
+    fn check_with_levels(input: &str, levels: LintLevels) -> Result<Vec<Function>, Vec<Error>> {
+        match check_all(input, levels) {
+            Ok((functions, _)) => Ok(functions),
+            Err(errors) => Err(errors
+                .into_iter()
+                .filter(|e| e.severity == Severity::Error)
+                .collect()),
+        }
+    }
+
+    /// Type check the given input, returning the kind and position of each
+    /// type error encountered.
+    fn type_errors(input: &str) -> Vec<(TypeErrorKind, usize, usize)> {
+        check(input)
+            .err()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|e| match e.kind {
+                ErrorKind::Type(kind) => (kind, e.span.start.line, e.span.start.column),
+                kind => panic!("expected type error but got {kind:?}"),
+            })
+            .collect()
+    }
+
+    /// Type check the given statements as the body of a function with the
+    /// given parameters, returning only the kinds of any type errors.
+    fn body_errors(params: &str, body: &str) -> Vec<TypeErrorKind> {
+        body_errors_in(&format!("fn f({params})\n{body}\nend"))
+    }
+
+    /// The kind of each type error in the given input.
+    fn body_errors_in(input: &str) -> Vec<TypeErrorKind> {
+        type_errors(input)
+            .into_iter()
+            .map(|(kind, _, _)| kind)
+            .collect()
+    }
+
+    fn mismatch(expected: Type, found: Type) -> TypeErrorKind {
+        TypeErrorKind::Mismatch { expected, found }
+    }
+
+    fn list(ty: Type) -> Type {
+        Type::List(Box::new(ty))
+    }
+
+    #[test]
+    fn well_typed_program() {
+        let input = "fn f(n: int, xs: [float]) -> float
+let total: float = 0.0
+let i: int = 0
+for x in xs do
+  if x > 0.0 and !(i == n) then
+    total += x * 2.0
+  end
+  i = i + 1
+end
+while total >= 100.0 do
+  total /= 2.0
+end
+let ys: [[int]] = [[], [1, 2]]
+let done: bool = true
+let c: char = 'c'
+let s: string = \"s\"
+return -total
+end";
+
+        let functions = check(input).unwrap();
+        let function = &functions[0];
+
+        assert_eq!(function.params[0].ty, Type::Int);
+        assert_eq!(function.params[1].ty, list(Type::Float));
+        assert_eq!(function.returns, Type::Float);
+
+        match &function.body[4].kind {
+            StatKind::Let { ty, value, .. } => {
+                assert_eq!(*ty, list(list(Type::Int)));
+                assert_eq!(value.as_ref().unwrap().ty, list(list(Type::Int)));
+            }
+            kind => panic!("expected let statement but got {kind:?}"),
+        }
+
+        match &function.body[8].kind {
+            StatKind::Return(value) => assert_eq!(value.ty, Type::Float),
+            kind => panic!("expected return statement but got {kind:?}"),
+        }
+    }
+
+    #[test]
+    fn expressions_are_annotated() {
+        let functions = check("fn f(a: int)\n(a + 1) * 2 < 10\nend").unwrap();
+
+        match &functions[0].body[0].kind {
+            StatKind::Expr(Expr {
+                kind: ExprKind::Binary { left, .. },
+                ty,
+                ..
+            }) => {
+                assert_eq!(*ty, Type::Bool);
+                assert_eq!(left.ty, Type::Int);
+            }
+            kind => panic!("expected binary expression but got {kind:?}"),
+        }
+    }
+
+    #[test]
+    fn unknown_types() {
+        assert_eq!(
+            type_errors("fn f(a: integer) -> [str]\nreturn [a]\nend"),
+            vec![
+                (TypeErrorKind::UnknownType("integer".to_string()), 1, 9),
+                (TypeErrorKind::UnknownType("str".to_string()), 1, 22),
+            ]
+        );
+    }
+
+    #[test]
+    fn variables() {
+        assert_eq!(
+            body_errors("", "x = 1"),
+            vec![TypeErrorKind::UndefinedVariable("x".to_string())]
+        );
+        assert_eq!(
+            body_errors("", "if true then\nlet x: int = 1\nend\nx"),
+            vec![TypeErrorKind::UndefinedVariable("x".to_string())]
+        );
+        assert_eq!(
+            body_errors("a: int, a: float", ""),
+            vec![TypeErrorKind::DuplicateParameter("a".to_string())]
+        );
+        assert!(body_errors("", "let x: int = 1\nlet x: float = 1.0\nx = 2.0").is_empty());
+    }
+
+    #[test]
+    fn functions() {
+        let input = "fn f() -> int\nreturn g(1.5, h())\nend\n\
+                     fn g(x: float, y: bool) -> int\nreturn 1\nend\n\
+                     fn h() -> bool\nreturn f() == 1\nend";
+        assert!(check(input).is_ok());
+
+        assert_eq!(
+            type_errors("fn f()\nend\nfn g()\nend\nfn f(x: int)\nend"),
+            vec![(TypeErrorKind::DuplicateFunction("f".to_string()), 5, 4)]
+        );
+    }
+
+    #[test]
+    fn calls() {
+        // the final "end" is added by body_errors
+        let signatures = "\nend\nfn one(x: int) -> int\nreturn x\nend\nfn none()";
+        let errors = |body: &str| body_errors("", &format!("{body}{signatures}"));
+
+        assert_eq!(
+            errors("missing(1 + true)"),
+            vec![
+                TypeErrorKind::UndefinedFunction("missing".to_string()),
+                TypeErrorKind::InvalidOperands {
+                    op: BinOp::Add,
+                    left: Type::Int,
+                    right: Type::Bool
+                },
+            ]
+        );
+        assert_eq!(errors("one(1.5)"), vec![mismatch(Type::Int, Type::Float)]);
+        assert_eq!(
+            errors("one(1, 2)\nnone(1)"),
+            vec![
+                TypeErrorKind::WrongArgumentCount {
+                    function: "one".to_string(),
+                    expected: 1,
+                    found: 2
+                },
+                TypeErrorKind::WrongArgumentCount {
+                    function: "none".to_string(),
+                    expected: 0,
+                    found: 1
+                },
+            ]
+        );
+        assert_eq!(
+            errors("let x = none()\nlet y: bool = one(none())"),
+            vec![
+                TypeErrorKind::NoValue("none".to_string()),
+                TypeErrorKind::NoValue("none".to_string()),
+                mismatch(Type::Bool, Type::Int),
+            ]
+        );
+        assert!(errors("none()\none(one(1))").is_empty());
+    }
+
+    #[test]
+    fn assignments() {
+        assert_eq!(
+            body_errors("", "let x: int = 1.5"),
+            vec![mismatch(Type::Int, Type::Float)]
+        );
+        assert_eq!(
+            body_errors("x: char", "x = \"x\""),
+            vec![mismatch(Type::Char, Type::String)]
+        );
+        assert_eq!(
+            body_errors("xs: [int]", "xs = [1.0]"),
+            vec![mismatch(list(Type::Int), list(Type::Float))]
+        );
+        assert_eq!(
+            body_errors("x: int", "x += 1.0"),
+            vec![TypeErrorKind::InvalidOperands {
+                op: BinOp::Add,
+                left: Type::Int,
+                right: Type::Float
+            }]
+        );
+        assert_eq!(
+            body_errors("s: string", "s -= \"a\""),
+            vec![TypeErrorKind::InvalidOperands {
+                op: BinOp::Subtract,
+                left: Type::String,
+                right: Type::String
+            }]
+        );
+    }
+
+    #[test]
+    fn operators() {
+        assert_eq!(
+            body_errors("", "1 + 2.0"),
+            vec![TypeErrorKind::InvalidOperands {
+                op: BinOp::Add,
+                left: Type::Int,
+                right: Type::Float
+            }]
+        );
+        assert_eq!(
+            body_errors("", "1 and true"),
+            vec![TypeErrorKind::InvalidOperands {
+                op: BinOp::And,
+                left: Type::Int,
+                right: Type::Bool
+            }]
+        );
+        assert_eq!(
+            body_errors("", "[1] == [1]"),
+            vec![TypeErrorKind::InvalidOperands {
+                op: BinOp::Equivalent,
+                left: list(Type::Int),
+                right: list(Type::Int)
+            }]
+        );
+        assert_eq!(
+            body_errors("", "!1\n-true"),
+            vec![
+                TypeErrorKind::InvalidOperand {
+                    op: UnOp::Not,
+                    operand: Type::Int
+                },
+                TypeErrorKind::InvalidOperand {
+                    op: UnOp::Negate,
+                    operand: Type::Bool
+                }
+            ]
+        );
+        assert!(body_errors("", "'a' < 'b' == (1.0 >= 2.0) or \"a\" != \"b\"").is_empty());
+    }
+
+    #[test]
+    fn list_literals() {
+        assert_eq!(
+            body_errors("", "[1, 'a', 3]"),
+            vec![mismatch(Type::Int, Type::Char)]
+        );
+        assert_eq!(
+            body_errors("", "let xs: [[char]] = [[], ['a'], [1]]"),
+            vec![mismatch(list(Type::Char), list(Type::Int))]
+        );
+    }
+
+    #[test]
+    fn for_loops() {
+        let body = "let total = 0\n\
+                    for x in xs do\ntotal += x\nend\n\
+                    for c in s do\nlet d: char = c\nend\n\
+                    for i in 0..total do\ntotal -= i\nend";
+        assert!(body_errors("xs: [int], s: string", body).is_empty());
+
+        assert_eq!(
+            body_errors(
+                "",
+                "for i in 0.5..true do\nend\nfor x in 1 do\nend\nfor y in [] do\nend"
+            ),
+            vec![
+                mismatch(Type::Int, Type::Float),
+                mismatch(Type::Int, Type::Bool),
+                TypeErrorKind::NotIterable(Type::Int),
+                TypeErrorKind::CannotInferType(Some("y".to_string())),
+            ]
+        );
+        assert_eq!(
+            body_errors("", "for i in 0..10 do\nend\ni = 1"),
+            vec![TypeErrorKind::UndefinedVariable("i".to_string())]
+        );
+    }
+
+    #[test]
+    fn indexing() {
+        assert!(body_errors(
+            "xs: [[int]], s: string",
+            "let x: int = xs[0][1]\nlet c: char = s[x]"
+        )
+        .is_empty());
+        assert_eq!(
+            body_errors("xs: [int]", "xs[1.5]\nxs[0][0]\nlet b: bool = xs[0]"),
+            vec![
+                mismatch(Type::Int, Type::Float),
+                TypeErrorKind::NotIndexable(Type::Int),
+                mismatch(Type::Bool, Type::Int),
+            ]
+        );
+    }
+
+    #[test]
+    fn indexing_unknown_element_type() {
+        assert_eq!(
+            type_errors("fn f()\n[][0]\nend"),
+            vec![(TypeErrorKind::CannotInferType(None), 2, 1)]
+        );
+        assert_eq!(
+            body_errors("", "[[]][0][0]"),
+            vec![TypeErrorKind::CannotInferType(None)]
+        );
+        assert_eq!(
+            body_errors("x: int", "x += [][0]"),
+            vec![TypeErrorKind::CannotInferType(None)]
+        );
+
+        for (returns, value) in [
+            ("int", "[][0] + 1"),
+            ("bool", "[][0] == 1"),
+            ("bool", "[][0] and true"),
+            ("string", "\"{[][0]}\""),
+        ] {
+            let input = format!("fn f() -> {returns}\nreturn {value}\nend");
+            assert_eq!(
+                body_errors_in(&input),
+                vec![TypeErrorKind::CannotInferType(None)],
+                "{value}"
+            );
+        }
+
+        // the elements of a list of empty lists are still lists
+        assert!(body_errors("", "[[]][0]").is_empty());
+
+        let error = check("fn f()\n[][0]\nend").unwrap_err().remove(0);
+        assert_eq!(
+            error.secondary_labels[0].message,
+            "element type is not known"
+        );
+    }
+
+    #[test]
+    fn interpolation() {
+        assert!(body_errors(
+            "i: int, x: float, c: char, b: bool, s: string",
+            "let t: string = \"{i} {x} {c} {b} {s} {\"{i}\"}\""
+        )
+        .is_empty());
+        assert_eq!(
+            body_errors("xs: [int]", "let s = \"{xs} {xs[0]} {g()}\""),
+            vec![
+                TypeErrorKind::NotInterpolatable(Type::List(Box::new(Type::Int))),
+                TypeErrorKind::UndefinedFunction("g".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn inferred_variable_types() {
+        let input = "fn f(a: float)\nlet x = a * 2.0\nlet ys = [[1], []]\nlet z = x > 1.0\nend";
+        let functions = check(input).unwrap();
+
+        let types: Vec<_> = functions[0]
+            .body
+            .iter()
+            .map(|stat| match &stat.kind {
+                StatKind::Let { ty, .. } => ty.clone(),
+                kind => panic!("expected let statement but got {kind:?}"),
+            })
+            .collect();
+        assert_eq!(types, vec![Type::Float, list(list(Type::Int)), Type::Bool]);
+
+        assert_eq!(
+            body_errors("", "let x = 1\nx = 2.0"),
+            vec![mismatch(Type::Int, Type::Float)]
+        );
+    }
+
+    #[test]
+    fn uninferrable_variable_types() {
+        assert_eq!(
+            type_errors("fn f()\nlet x\nlet ys = []\nlet zs = [[]]\nend"),
+            vec![
+                (TypeErrorKind::MissingTypeAnnotation("x".to_string()), 2, 5),
+                (TypeErrorKind::CannotInferType(Some("ys".to_string())), 3, 5),
+                (TypeErrorKind::CannotInferType(Some("zs".to_string())), 4, 5),
+            ]
+        );
+
+        // the cause of the unknown type is reported instead
+        assert_eq!(
+            body_errors("", "let x = y"),
+            vec![TypeErrorKind::UndefinedVariable("y".to_string())]
+        );
+    }
+
+    #[test]
+    fn conditions() {
+        assert_eq!(
+            body_errors("", "if 1 then\nend\nwhile 'x' do\nend"),
+            vec![
+                mismatch(Type::Bool, Type::Int),
+                mismatch(Type::Bool, Type::Char)
+            ]
+        );
+        assert_eq!(
+            body_errors("x: int", "for c in x do\nend"),
+            vec![TypeErrorKind::NotIterable(Type::Int)]
+        );
+    }
+
+    #[test]
+    fn returns() {
+        assert_eq!(
+            type_errors("fn f()\nreturn 1\nend"),
+            vec![(TypeErrorKind::UnexpectedReturnValue, 2, 1)]
+        );
+        assert_eq!(
+            type_errors("fn f() -> int\nreturn 1.0\nend"),
+            vec![(mismatch(Type::Int, Type::Float), 2, 8)]
+        );
+        assert_eq!(
+            type_errors("fn f(x: bool) -> int\nif x then\nreturn 1\nend\nend"),
+            vec![(TypeErrorKind::MissingReturn, 1, 18)]
+        );
+        assert!(
+            check("fn f(x: bool) -> int\nif x then\nreturn 1\nelse\nreturn 2\nend\nend").is_ok()
+        );
+    }
+
+    #[test]
+    fn unknown_types_do_not_cascade() {
+        assert_eq!(
+            body_errors("", "let x: int = y + 1\nlet z: bool = y and x > 0"),
+            vec![
+                TypeErrorKind::UndefinedVariable("y".to_string()),
+                TypeErrorKind::UndefinedVariable("y".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn syntax_errors_prevent_type_checking() {
+        let errors = check("fn f()\nx = )\nend\nfn f()\ny\nend").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(errors[0].kind, ErrorKind::Syntax(_)));
+    }
+
+    #[test]
+    fn type_error_messages() {
+        let errors = check("fn f(x: int)\nx = 1.5 * 2.0\nend").unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "error[K0307]: expected int, found float\n\
+             \x20--> test:2:5\n\
+             \x20 |\n\
+             1 | fn f(x: int)\n\
+             \x20 |      - 'x' declared here as int\n\
+             2 | x = 1.5 * 2.0\n\
+             \x20 |     ^^^^^^^^^ expected int\n"
+        );
+
+        let errors = check("fn f()\nf(1, 2)\nend").unwrap_err();
+        assert_eq!(
+            errors[0].kind.to_string(),
+            "Type error: function 'f' takes 0 arguments but 2 were given."
+        );
+    }
+
+    fn labels(input: &str) -> Vec<(String, usize, usize)> {
+        let errors = check(input).unwrap_err();
+        errors[0]
+            .secondary_labels
+            .iter()
+            .map(|label| {
+                (
+                    label.message.clone(),
+                    label.span.start.line,
+                    label.span.start.column,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn secondary_labels() {
+        assert_eq!(
+            labels("fn f()\nlet x = 1\nx = true\nend"),
+            vec![("'x' declared here as int".to_string(), 2, 5)]
+        );
+        assert_eq!(
+            labels("fn f(a: int)\nend\nfn g()\nf(1.0)\nend"),
+            vec![("parameter declared here".to_string(), 1, 6)]
+        );
+        assert_eq!(
+            labels("fn f() -> bool\nreturn 1\nend"),
+            vec![("expected because of this return type".to_string(), 1, 11)]
+        );
+        assert_eq!(
+            labels("fn f()\nend\nfn f()\nend"),
+            vec![("first defined here".to_string(), 1, 4)]
+        );
+        assert_eq!(
+            labels("fn f()\nlet x = 1 + true\nend"),
+            vec![("int".to_string(), 2, 9), ("bool".to_string(), 2, 13)]
+        );
+    }
+
+    /// Check the given input, returning the kind and position of each problem
+    /// found by a lint (whether reported as an error or a warning).
+    fn lints_with_levels(input: &str, levels: LintLevels) -> Vec<(LintKind, usize, usize)> {
+        let (Ok((_, errors)) | Err(errors)) = check_all(input, levels);
+        errors
+            .into_iter()
+            .filter_map(|e| match e.kind {
+                ErrorKind::Lint(kind) => Some((kind, e.span.start.line, e.span.start.column)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn lints(input: &str) -> Vec<(LintKind, usize, usize)> {
+        lints_with_levels(input, LintLevels::default())
+    }
+
+    #[test]
+    fn unused_variables() {
+        assert_eq!(
+            lints("fn f(a: int, _b: int)\nlet x = 1\nlet _y = 2\nend"),
+            vec![
+                (LintKind::UnusedVariable("x".to_string()), 2, 5),
+                (LintKind::UnusedParameter("a".to_string()), 1, 6)
+            ]
+        );
+        assert_eq!(
+            lints("fn f()\nlet x = 1\nx = 2\nlet y = 1\ny += 1\nend"),
+            vec![
+                (LintKind::UnusedAssignment("x".to_string()), 2, 5),
+                (LintKind::UnusedAssignment("y".to_string()), 4, 5)
+            ]
+        );
+        assert_eq!(
+            lints("fn f()\nfor i in 0..3 do\nend\nend"),
+            vec![(LintKind::UnusedVariable("i".to_string()), 2, 5)]
+        );
+        assert!(lints("fn f(x: int) -> int\nlet y = x\ny = y + 1\nreturn y\nend").is_empty());
+    }
+
+    #[test]
+    fn unreachable_code() {
+        assert_eq!(
+            lints("fn f() -> int\nreturn 1\nf()\nf()\nend"),
+            vec![(LintKind::UnreachableCode, 3, 1)]
+        );
+        assert_eq!(
+            lints("fn f(c: bool) -> int\nif c then\nreturn 1\nelse\nreturn 2\nend\nreturn 3\nend"),
+            vec![(LintKind::UnreachableCode, 7, 1)]
+        );
+        assert!(lints("fn f(c: bool) -> int\nif c then\nreturn 1\nend\nreturn 2\nend").is_empty());
+    }
+
+    #[test]
+    fn shadowing() {
+        assert_eq!(
+            lints("fn f(x: int) -> int\nlet x = x + 1\nreturn x\nend"),
+            vec![(LintKind::Shadowing("x".to_string()), 2, 5)]
+        );
+        assert_eq!(
+            lints(
+                "fn f() -> int\nlet x = 1\nwhile x > 0 do\nlet x = 2\nx -= 1\nend\nreturn x\nend"
+            )[0],
+            (LintKind::Shadowing("x".to_string()), 4, 5)
+        );
+    }
+
+    #[test]
+    fn read_before_assignment() {
+        let read_before_assignment = |line, column| {
+            vec![(
+                LintKind::ReadBeforeAssignment("x".to_string()),
+                line,
+                column,
+            )]
+        };
+
+        assert_eq!(
+            lints("fn f(c: bool) -> int\nlet x: int\nif c then\nx = 1\nend\nreturn x\nend"),
+            read_before_assignment(6, 8)
+        );
+        assert_eq!(
+            lints("fn f(c: bool) -> int\nlet x: int\nwhile c do\nx = 1\nend\nreturn x\nend"),
+            read_before_assignment(6, 8)
+        );
+        assert_eq!(
+            lints("fn f() -> int\nlet x: int\nx = x + 1\nreturn x + x\nend"),
+            read_before_assignment(3, 5)
+        );
+        assert_eq!(
+            lints("fn f() -> int\nlet x: int\nx += 1\nreturn x\nend"),
+            read_before_assignment(3, 1)
+        );
+
+        assert!(lints(
+            "fn f(c: bool) -> int\nlet x: int\nif c then\nx = 1\nelse\nx = 2\nend\nreturn x\nend"
+        )
+        .is_empty());
+        assert!(lints(
+            "fn f(c: bool) -> int\nlet x: int\nif c then\nreturn 0\nelse\nx = 1\nend\nreturn x\nend"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn lint_levels() {
+        let input = "fn f(a: int)\nlet x = 1\nend";
+
+        let mut levels = LintLevels::default();
+        levels.set(Lint::UnusedVariables, Level::Allow);
+        assert!(lints_with_levels(input, levels).is_empty());
+
+        let mut levels = LintLevels::default();
+        levels.set(Lint::Shadowing, Level::Allow);
+        levels.deny_warnings();
+        let errors = check_with_levels(input, levels).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().all(|e| e.severity == Severity::Error));
+
+        let (_, warnings) = check_all(input, LintLevels::default()).unwrap();
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings.iter().all(|e| e.severity == Severity::Warning));
+        assert_eq!(
+            warnings[0].notes,
+            ["'-W unused-variables' is on by default"]
+        );
+    }
+
+    #[test]
+    fn suggestions() {
+        let errors = check("fn f()\nreturn 1\nend").unwrap_err();
+        let suggestion = &errors[0].suggestions[0];
+        assert_eq!(suggestion.replacement, " -> int");
+        assert_eq!(suggestion.span.byte_range, 6..6);
+    }
+
+    #[test]
+    fn error_limit() {
+        let truncated = |count| {
+            let input = "fn f()\n".to_string() + &"x = 1\n".repeat(count) + "end";
+            let lexer = Lexer::new(Cursor::new(input), "test".to_string());
+            let mut checker = TypeChecker::new(Parser::new(lexer));
+            let functions = checker.parser_mut().parse_all().unwrap();
+            let errors = checker.check(functions).unwrap_err();
+            assert_eq!(errors.len(), count.min(MAX_ERRORS));
+            checker.errors_truncated()
+        };
+        assert!(!truncated(MAX_ERRORS));
+        assert!(truncated(MAX_ERRORS + 1));
+    }
+}
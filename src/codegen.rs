@@ -1,20 +1,1088 @@
-use std::io::Read;
+use std::collections::HashMap;
 
-use crate::{error::Result, parse::Parser};
+use wasm_encoder::{
+    BlockType, CodeSection, ConstExpr, DataSection, ExportKind, ExportSection,
+    Function as WasmFunction, FunctionSection, GlobalSection, GlobalType, IndirectNameMap,
+    Instruction, MemorySection, MemoryType, Module, NameMap, NameSection, TypeSection, ValType,
+};
 
-pub struct CodeGenerator<R> {
-    nodes: Parser<R>,
+use crate::{
+    ast::{BinOp, Expr, ExprKind, Function, Ident, Stat, StatKind, Type, UnOp},
+    runtime::{
+        self, element_size, load, store, value_type, RuntimeFunction, DATA_START, ERROR_CODE,
+        ERROR_CODE_EXPORT, HEADER_SIZE, MEMORY_EXPORT,
+    },
+};
+
+/// The source lines from which the code of each Kobe function in a module was
+/// generated, indexed by function. For each statement, the index of the first
+/// instruction generated for it is given along with its line, unless the
+/// previous statement was on the same line.
+pub type LineTable = Vec<Vec<(usize, usize)>>;
+
+pub struct CodeGenerator {
+    functions: Vec<Function>,
+}
+
+impl CodeGenerator {
+    /// Create a code generator for the given functions, which must have already been type checked.
+    pub fn new(functions: Vec<Function>) -> Self {
+        CodeGenerator { functions }
+    }
+
+    /// Generate a WASM module containing a function for each Kobe function,
+    /// along with the [runtime](crate::runtime) those functions rely on.
+    ///
+    /// The `main` function (if any) and every function marked `pub` are
+    /// exported under their own names. The module's memory and the global
+    /// holding the reason for a trap are exported as `kobe.memory` and
+    /// `kobe.error_code` respectively, which no Kobe function can be named.
+    ///
+    /// Along with the module, the source lines from which each function's code
+    /// was generated are given.
+    pub fn generate(self) -> (Vec<u8>, LineTable) {
+        let mut types = TypeSection::new();
+        let mut functions = FunctionSection::new();
+        let mut exports = ExportSection::new();
+        let mut code = CodeSection::new();
+        let mut function_names = NameMap::new();
+        let mut local_names = IndirectNameMap::new();
+        let mut line_table = LineTable::new();
+
+        let mut context = Context {
+            functions: self
+                .functions
+                .iter()
+                .enumerate()
+                .map(|(index, function)| (function.name.name.as_str(), index as u32))
+                .collect(),
+            runtime_start: self.functions.len() as u32,
+            strings: StringTable::default(),
+        };
+
+        for (index, function) in self.functions.iter().enumerate() {
+            let index = index as u32;
+            let name = &function.name.name;
+
+            let params = function.params.iter().map(|p| value_type(&p.ty));
+            let results = result_types(&function.returns);
+            types.function(params, results);
+            functions.function(index);
+
+            if function.public || name == "main" {
+                exports.export(name, ExportKind::Func, index);
+            }
+            function_names.append(index, name);
+
+            let generated = FunctionGenerator::new(function, &mut context).generate(function);
+            code.function(&generated.function);
+            local_names.append(index, &generated.local_names);
+            line_table.push(generated.lines);
+        }
+
+        for runtime_function in RuntimeFunction::ALL {
+            let index = context.runtime_index(runtime_function);
+            types.function(runtime_function.params(), runtime_function.results());
+            functions.function(index);
+            function_names.append(index, runtime_function.name());
+            code.function(&runtime_function.body(context.runtime_start));
+        }
+
+        let mut memories = MemorySection::new();
+        memories.memory(MemoryType {
+            minimum: 1,
+            maximum: None,
+            memory64: false,
+            shared: false,
+        });
+        exports.export(MEMORY_EXPORT, ExportKind::Memory, 0);
+
+        let data = context.strings.data;
+        let heap_start = (DATA_START + data.len() as u32).next_multiple_of(8);
+
+        let mut globals = GlobalSection::new();
+        let global_type = GlobalType {
+            val_type: ValType::I32,
+            mutable: true,
+        };
+        globals.global(global_type, &ConstExpr::i32_const(heap_start as i32));
+        globals.global(global_type, &ConstExpr::i32_const(0));
+        exports.export(ERROR_CODE_EXPORT, ExportKind::Global, ERROR_CODE);
+
+        let mut data_section = DataSection::new();
+        data_section.active(0, &ConstExpr::i32_const(DATA_START as i32), data);
+
+        let mut names = NameSection::new();
+        names.functions(&function_names);
+        names.locals(&local_names);
+
+        let mut module = Module::new();
+        module
+            .section(&types)
+            .section(&functions)
+            .section(&memories)
+            .section(&globals)
+            .section(&exports)
+            .section(&code)
+            .section(&data_section)
+            .section(&names);
+        (module.finish(), line_table)
+    }
+}
+
+/// State shared between the generators for each function in a module.
+struct Context<'a> {
+    /// Maps function names to their indices in the module.
+    functions: HashMap<&'a str, u32>,
+    /// Index of the first runtime function, which follow the Kobe functions.
+    runtime_start: u32,
+    strings: StringTable,
+}
+
+impl Context<'_> {
+    fn runtime_index(&self, function: RuntimeFunction) -> u32 {
+        function.index(self.runtime_start)
+    }
+}
+
+/// The string constants to be placed in the module's data section, laid out as
+/// described in [`runtime`].
+#[derive(Default)]
+struct StringTable {
+    data: Vec<u8>,
+    addresses: HashMap<String, u32>,
+}
+
+impl StringTable {
+    /// Get the address of the given string constant, adding it to the table if
+    /// it is not already present.
+    fn address_of(&mut self, string: &str) -> u32 {
+        if let Some(&address) = self.addresses.get(string) {
+            return address;
+        }
+
+        let address = DATA_START + self.data.len() as u32;
+        let length = string.chars().count() as u32;
+        self.data.extend(length.to_le_bytes());
+        for c in string.chars() {
+            self.data.extend((c as u32).to_le_bytes());
+        }
+
+        self.addresses.insert(string.to_string(), address);
+        address
+    }
+}
+
+/// Generates the body of a single WASM function.
+struct FunctionGenerator<'a, 'b> {
+    context: &'b mut Context<'a>,
+    /// The number of locals (including parameters) declared so far.
+    local_count: u32,
+    /// The types of the locals declared by `let` or `for` statements, which
+    /// WASM requires up front, following the function's parameters.
+    locals: Vec<ValType>,
+    /// Maps variable names to local indices, with one scope per block.
+    scopes: Vec<HashMap<String, u32>>,
+    /// The names of the parameters and of the locals declared for variables.
+    local_names: NameMap,
+    instructions: Vec<Instruction<'static>>,
+    lines: Vec<(usize, usize)>,
+}
+
+/// The code generated for a single function, along with the information
+/// needed to describe it in terms of the source.
+struct GeneratedFunction {
+    function: WasmFunction,
+    local_names: NameMap,
+    /// As for the [`LineTable`].
+    lines: Vec<(usize, usize)>,
+}
+
+impl<'a, 'b> FunctionGenerator<'a, 'b> {
+    fn new(function: &Function, context: &'b mut Context<'a>) -> Self {
+        let params = function
+            .params
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (p.name.name.clone(), i as u32))
+            .collect();
+        let mut local_names = NameMap::new();
+        for (i, param) in function.params.iter().enumerate() {
+            local_names.append(i as u32, &param.name.name);
+        }
+
+        FunctionGenerator {
+            context,
+            local_count: function.params.len() as u32,
+            locals: Vec::new(),
+            scopes: vec![params],
+            local_names,
+            instructions: Vec::new(),
+            lines: Vec::new(),
+        }
+    }
+
+    fn generate(mut self, function: &Function) -> GeneratedFunction {
+        self.generate_block(&function.body);
+
+        // The type checker ensures that every path through a function with a
+        // return type ends with a `return`, but WASM's validator still expects
+        // a value on the stack at the end of the function body.
+        if function.returns != Type::Void {
+            self.emit(Instruction::Unreachable);
+        }
+        self.emit(Instruction::End);
+
+        let mut wasm_function = WasmFunction::new_with_locals_types(self.locals);
+        for instruction in &self.instructions {
+            wasm_function.instruction(instruction);
+        }
+        GeneratedFunction {
+            function: wasm_function,
+            local_names: self.local_names,
+            lines: self.lines,
+        }
+    }
+
+    fn generate_block(&mut self, stats: &[Stat]) {
+        self.scopes.push(HashMap::new());
+        for stat in stats {
+            self.generate_stat(stat);
+        }
+        self.scopes.pop();
+    }
+
+    fn generate_stat(&mut self, stat: &Stat) {
+        let line = stat.span.start.line;
+        if self.lines.last().is_none_or(|&(_, last)| last != line) {
+            self.lines.push((self.instructions.len(), line));
+        }
+
+        match &stat.kind {
+            StatKind::Expr(expr) => {
+                self.generate_expr(expr);
+                if expr.ty != Type::Void {
+                    self.emit(Instruction::Drop);
+                }
+            }
+
+            StatKind::Let {
+                name, value, ty, ..
+            } => {
+                if let Some(value) = value {
+                    self.generate_expr(value);
+                } else {
+                    // Locals are zeroed when the function is entered, but a
+                    // `let` inside a loop must also be reset on each iteration.
+                    self.emit(zero(ty));
+                }
+                let local = self.declare(&name.name, ty);
+                self.emit(Instruction::LocalSet(local));
+            }
+
+            StatKind::Assign { target, value } => {
+                self.generate_expr(value);
+                let local = self.lookup(&target.name);
+                self.emit(Instruction::LocalSet(local));
+            }
+
+            StatKind::CompoundAssign { target, op, value } => {
+                let local = self.lookup(&target.name);
+                self.emit(Instruction::LocalGet(local));
+                self.generate_expr(value);
+                self.emit(binary_instruction(*op, &value.ty));
+                self.emit(Instruction::LocalSet(local));
+            }
+
+            StatKind::Return(value) => {
+                self.generate_expr(value);
+                self.emit(Instruction::Return);
+            }
+
+            StatKind::If {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                self.generate_expr(condition);
+                self.emit(Instruction::If(BlockType::Empty));
+                self.generate_block(then_body);
+                if !else_body.is_empty() {
+                    self.emit(Instruction::Else);
+                    self.generate_block(else_body);
+                }
+                self.emit(Instruction::End);
+            }
+
+            StatKind::While { condition, body } => {
+                // block
+                //   loop
+                //     br_if 1 (!condition)
+                //     body
+                //     br 0
+                //   end
+                // end
+                self.emit(Instruction::Block(BlockType::Empty));
+                self.emit(Instruction::Loop(BlockType::Empty));
+                self.generate_expr(condition);
+                self.emit(Instruction::I32Eqz);
+                self.emit(Instruction::BrIf(1));
+                self.generate_block(body);
+                self.emit(Instruction::Br(0));
+                self.emit(Instruction::End);
+                self.emit(Instruction::End);
+            }
+
+            StatKind::For {
+                var,
+                iterable,
+                body,
+            } => match &iterable.kind {
+                ExprKind::Range { start, end } => self.generate_range_loop(var, start, end, body),
+                _ => self.generate_list_loop(var, iterable, body),
+            },
+        }
+    }
+
+    /// Generate a loop over each integer in a range. The loop variable is set
+    /// from a separate counter at the start of each iteration so that
+    /// assigning to it does not affect the number of iterations.
+    fn generate_range_loop(&mut self, var: &Ident, start: &Expr, end: &Expr, body: &[Stat]) {
+        let counter = self.temporary(ValType::I64);
+        let end_value = self.temporary(ValType::I64);
+
+        self.generate_expr(start);
+        self.emit(Instruction::LocalSet(counter));
+        self.generate_expr(end);
+        self.emit(Instruction::LocalSet(end_value));
+
+        self.emit(Instruction::Block(BlockType::Empty));
+        self.emit(Instruction::Loop(BlockType::Empty));
+        self.emit(Instruction::LocalGet(counter));
+        self.emit(Instruction::LocalGet(end_value));
+        self.emit(Instruction::I64GeS);
+        self.emit(Instruction::BrIf(1));
+
+        self.scopes.push(HashMap::new());
+        let local = self.declare(&var.name, &Type::Int);
+        self.emit(Instruction::LocalGet(counter));
+        self.emit(Instruction::LocalSet(local));
+        self.generate_block(body);
+        self.scopes.pop();
+
+        self.emit(Instruction::LocalGet(counter));
+        self.emit(Instruction::I64Const(1));
+        self.emit(Instruction::I64Add);
+        self.emit(Instruction::LocalSet(counter));
+        self.emit(Instruction::Br(0));
+        self.emit(Instruction::End);
+        self.emit(Instruction::End);
+    }
+
+    /// Generate a loop over each element of a list or each char of a string.
+    fn generate_list_loop(&mut self, var: &Ident, iterable: &Expr, body: &[Stat]) {
+        let element_type = match &iterable.ty {
+            Type::List(element_type) => element_type,
+            Type::String => &Type::Char,
+            ty => panic!("cannot iterate over '{ty}' after type checking"),
+        };
+        let list = self.temporary(ValType::I32);
+        let offset = self.temporary(ValType::I32);
+        let end_offset = self.temporary(ValType::I32);
+        let size = element_size(element_type);
+
+        // `offset` counts up from the first element in bytes
+        self.generate_expr(iterable);
+        self.emit(Instruction::LocalTee(list));
+        self.emit(runtime::load_length());
+        self.emit(Instruction::I32Const(size as i32));
+        self.emit(Instruction::I32Mul);
+        self.emit(Instruction::LocalSet(end_offset));
+        self.emit(Instruction::I32Const(0));
+        self.emit(Instruction::LocalSet(offset));
+
+        self.emit(Instruction::Block(BlockType::Empty));
+        self.emit(Instruction::Loop(BlockType::Empty));
+        self.emit(Instruction::LocalGet(offset));
+        self.emit(Instruction::LocalGet(end_offset));
+        self.emit(Instruction::I32GeU);
+        self.emit(Instruction::BrIf(1));
+
+        self.scopes.push(HashMap::new());
+        let local = self.declare(&var.name, element_type);
+        self.emit(Instruction::LocalGet(list));
+        self.emit(Instruction::LocalGet(offset));
+        self.emit(Instruction::I32Add);
+        self.emit(load(element_type, HEADER_SIZE));
+        self.emit(Instruction::LocalSet(local));
+        self.generate_block(body);
+        self.scopes.pop();
+
+        self.emit(Instruction::LocalGet(offset));
+        self.emit(Instruction::I32Const(size as i32));
+        self.emit(Instruction::I32Add);
+        self.emit(Instruction::LocalSet(offset));
+        self.emit(Instruction::Br(0));
+        self.emit(Instruction::End);
+        self.emit(Instruction::End);
+    }
+
+    fn generate_expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::IntLiteral(value) => self.emit(Instruction::I64Const(*value)),
+            ExprKind::FloatLiteral(value) => self.emit(Instruction::F64Const(*value)),
+            ExprKind::CharLiteral(value) => self.emit(Instruction::I32Const(*value as i32)),
+            ExprKind::BoolLiteral(value) => self.emit(Instruction::I32Const(*value as i32)),
+
+            ExprKind::StringLiteral(value) => {
+                let address = self.context.strings.address_of(value);
+                self.emit(Instruction::I32Const(address as i32));
+            }
+
+            ExprKind::Interpolation(parts) => {
+                for (i, part) in parts.iter().enumerate() {
+                    self.generate_expr(part);
+                    match part.ty {
+                        Type::Int => self.call_runtime(RuntimeFunction::IntToString),
+                        Type::Float => self.call_runtime(RuntimeFunction::FloatToString),
+                        Type::Char => self.call_runtime(RuntimeFunction::CharToString),
+                        Type::Bool => self.call_runtime(RuntimeFunction::BoolToString),
+                        _ => {}
+                    }
+                    if i > 0 {
+                        self.call_runtime(RuntimeFunction::Concat);
+                    }
+                }
+            }
+
+            // the empty list is always found at address 0
+            ExprKind::List(items) if items.is_empty() => self.emit(Instruction::I32Const(0)),
+
+            ExprKind::List(items) => {
+                let Type::List(element_type) = &expr.ty else {
+                    panic!("list has type '{}' after type checking", expr.ty);
+                };
+                let size = element_size(element_type);
+
+                let list = self.temporary(ValType::I32);
+                self.emit(Instruction::I32Const(
+                    (HEADER_SIZE + items.len() as u32 * size) as i32,
+                ));
+                self.call_runtime(RuntimeFunction::Alloc);
+                self.emit(Instruction::LocalTee(list));
+                self.emit(Instruction::I32Const(items.len() as i32));
+                self.emit(runtime::store_length());
+
+                for (i, item) in items.iter().enumerate() {
+                    self.emit(Instruction::LocalGet(list));
+                    self.generate_expr(item);
+                    self.emit(store(element_type, HEADER_SIZE + i as u32 * size));
+                }
+                self.emit(Instruction::LocalGet(list));
+            }
+
+            ExprKind::Identifier(name) => {
+                let local = self.lookup(name);
+                self.emit(Instruction::LocalGet(local));
+            }
+
+            ExprKind::Call { function, args } => {
+                for arg in args {
+                    self.generate_expr(arg);
+                }
+                self.emit(Instruction::Call(self.context.functions[function.as_str()]));
+            }
+
+            // `and` and `or` only evaluate their right operand if the left
+            // operand does not already determine the result.
+            ExprKind::Binary {
+                op: BinOp::And,
+                left,
+                right,
+            } => {
+                self.generate_expr(left);
+                self.emit(Instruction::If(BlockType::Result(ValType::I32)));
+                self.generate_expr(right);
+                self.emit(Instruction::Else);
+                self.emit(Instruction::I32Const(0));
+                self.emit(Instruction::End);
+            }
+
+            ExprKind::Binary {
+                op: BinOp::Or,
+                left,
+                right,
+            } => {
+                self.generate_expr(left);
+                self.emit(Instruction::If(BlockType::Result(ValType::I32)));
+                self.emit(Instruction::I32Const(1));
+                self.emit(Instruction::Else);
+                self.generate_expr(right);
+                self.emit(Instruction::End);
+            }
+
+            ExprKind::Range { .. } => unreachable!("range outside of a for loop"),
+
+            ExprKind::Index { target, index } => {
+                self.generate_expr(target);
+                self.generate_expr(index);
+                self.emit(Instruction::I32Const(element_size(&expr.ty) as i32));
+                self.call_runtime(RuntimeFunction::ElementAddress);
+                self.emit(load(&expr.ty, 0));
+            }
+
+            ExprKind::Binary {
+                op: op @ (BinOp::Equivalent | BinOp::NotEquivalent),
+                left,
+                right,
+            } if left.ty == Type::String => {
+                self.generate_expr(left);
+                self.generate_expr(right);
+                self.call_runtime(RuntimeFunction::StringsEqual);
+                if *op == BinOp::NotEquivalent {
+                    self.emit(Instruction::I32Eqz);
+                }
+            }
+
+            ExprKind::Binary { op, left, right } => {
+                self.generate_expr(left);
+                self.generate_expr(right);
+                self.emit(binary_instruction(*op, &left.ty));
+            }
+
+            ExprKind::Unary {
+                op: UnOp::Negate,
+                operand,
+            } => {
+                if operand.ty == Type::Int {
+                    // There is no integer negation instruction, so subtract
+                    // from zero instead.
+                    self.emit(Instruction::I64Const(0));
+                    self.generate_expr(operand);
+                    self.emit(Instruction::I64Sub);
+                } else {
+                    self.generate_expr(operand);
+                    self.emit(Instruction::F64Neg);
+                }
+            }
+
+            ExprKind::Unary {
+                op: UnOp::Not,
+                operand,
+            } => {
+                self.generate_expr(operand);
+                self.emit(Instruction::I32Eqz);
+            }
+        }
+    }
+
+    /// Declare a new local variable in the current scope, returning its index.
+    fn declare(&mut self, name: &str, ty: &Type) -> u32 {
+        let local = self.temporary(value_type(ty));
+        self.local_names.append(local, name);
+        self.scopes
+            .last_mut()
+            .expect("there is always at least one scope")
+            .insert(name.to_string(), local);
+        local
+    }
+
+    /// Declare a new local which cannot be referred to by name, returning its
+    /// index.
+    fn temporary(&mut self, value_type: ValType) -> u32 {
+        let local = self.local_count;
+        self.local_count += 1;
+        self.locals.push(value_type);
+        local
+    }
+
+    fn lookup(&self, name: &str) -> u32 {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied())
+            .unwrap_or_else(|| panic!("undefined variable '{name}' after type checking"))
+    }
+
+    fn call_runtime(&mut self, function: RuntimeFunction) {
+        let index = self.context.runtime_index(function);
+        self.emit(Instruction::Call(index));
+    }
+
+    fn emit(&mut self, instruction: Instruction<'static>) {
+        self.instructions.push(instruction);
+    }
+}
+
+fn result_types(ty: &Type) -> Vec<ValType> {
+    match ty {
+        Type::Void => vec![],
+        ty => vec![value_type(ty)],
+    }
+}
+
+/// The instruction that produces the zero value of the given type.
+fn zero(ty: &Type) -> Instruction<'static> {
+    match value_type(ty) {
+        ValType::I64 => Instruction::I64Const(0),
+        ValType::F64 => Instruction::F64Const(0.0),
+        _ => Instruction::I32Const(0),
+    }
 }
 
-impl<R: Read> CodeGenerator<R> {
-    pub fn new(nodes: Parser<R>) -> Self {
-        CodeGenerator { nodes }
+/// Get the instruction implementing the given (non-short-circuiting) binary
+/// operator for operands of the given type.
+fn binary_instruction(op: BinOp, operand_type: &Type) -> Instruction<'static> {
+    use Instruction as I;
+
+    match (value_type(operand_type), op) {
+        (ValType::I64, BinOp::Add) => I::I64Add,
+        (ValType::I64, BinOp::Subtract) => I::I64Sub,
+        (ValType::I64, BinOp::Multiply) => I::I64Mul,
+        (ValType::I64, BinOp::Divide) => I::I64DivS,
+        (ValType::I64, BinOp::Equivalent) => I::I64Eq,
+        (ValType::I64, BinOp::NotEquivalent) => I::I64Ne,
+        (ValType::I64, BinOp::LessThan) => I::I64LtS,
+        (ValType::I64, BinOp::GreaterThan) => I::I64GtS,
+        (ValType::I64, BinOp::LessThanOrEqual) => I::I64LeS,
+        (ValType::I64, BinOp::GreaterThanOrEqual) => I::I64GeS,
+
+        (ValType::F64, BinOp::Add) => I::F64Add,
+        (ValType::F64, BinOp::Subtract) => I::F64Sub,
+        (ValType::F64, BinOp::Multiply) => I::F64Mul,
+        (ValType::F64, BinOp::Divide) => I::F64Div,
+        (ValType::F64, BinOp::Equivalent) => I::F64Eq,
+        (ValType::F64, BinOp::NotEquivalent) => I::F64Ne,
+        (ValType::F64, BinOp::LessThan) => I::F64Lt,
+        (ValType::F64, BinOp::GreaterThan) => I::F64Gt,
+        (ValType::F64, BinOp::LessThanOrEqual) => I::F64Le,
+        (ValType::F64, BinOp::GreaterThanOrEqual) => I::F64Ge,
+
+        // Chars (as Unicode scalar values) and bools.
+        (ValType::I32, BinOp::Equivalent) => I::I32Eq,
+        (ValType::I32, BinOp::NotEquivalent) => I::I32Ne,
+        (ValType::I32, BinOp::LessThan) => I::I32LtU,
+        (ValType::I32, BinOp::GreaterThan) => I::I32GtU,
+        (ValType::I32, BinOp::LessThanOrEqual) => I::I32LeU,
+        (ValType::I32, BinOp::GreaterThanOrEqual) => I::I32GeU,
+
+        _ => panic!("invalid operator '{op}' for '{operand_type}' after type checking"),
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use wasmi::{
+        core::{Trap, F64},
+        Engine, Instance, Linker, Module, Store, Value, WasmParams, WasmResults,
+    };
 
-    pub fn generate_wasm(self) -> Result<Vec<u8>> {
-        for _node in self.nodes {
-            // ...
+    use super::*;
+    use crate::{check::TypeChecker, lex::Lexer, parse::Parser, runtime::RuntimeError};
+
+    /// Compile the given input, which must be free of errors, and check that
+    /// the resulting module is valid.
+    fn compile(input: &str) -> Vec<u8> {
+        let lexer = Lexer::new(Cursor::new(input), "test".to_string());
+        let mut checker = TypeChecker::new(Parser::new(lexer));
+        let functions = checker
+            .parser_mut()
+            .parse_all()
+            .expect("input should parse");
+        let (functions, _) = checker.check(functions).expect("input should compile");
+        let wasm = CodeGenerator::new(functions).generate().0;
+        wasmparser::validate(&wasm).expect("module should be valid");
+        wasm
+    }
+
+    fn instantiate(input: &str) -> (Store<()>, Instance) {
+        let wasm = compile(input);
+        let engine = Engine::default();
+        let module = Module::new(&engine, &wasm[..]).unwrap();
+        let mut store = Store::new(&engine, ());
+        let instance = Linker::<()>::new(&engine)
+            .instantiate(&mut store, &module)
+            .unwrap()
+            .start(&mut store)
+            .unwrap();
+        (store, instance)
+    }
+
+    /// Compile the given input and call the function exported as `name`.
+    fn call<P: WasmParams, R: WasmResults>(input: &str, name: &str, args: P) -> Result<R, Trap> {
+        let (mut store, instance) = instantiate(input);
+        instance
+            .get_typed_func::<P, R>(&store, name)
+            .unwrap()
+            .call(&mut store, args)
+    }
+
+    /// Compile the given statements as the body of a function returning a
+    /// string, then call it and read the result from memory.
+    fn run_string(body: &str) -> String {
+        let (mut store, instance) = instantiate(&format!("fn main() -> string\n{body}\nend"));
+        let address = instance
+            .get_typed_func::<(), i32>(&store, "main")
+            .unwrap()
+            .call(&mut store, ())
+            .unwrap() as usize;
+
+        let memory = instance
+            .get_memory(&store, MEMORY_EXPORT)
+            .unwrap()
+            .data(&store);
+        let word =
+            |address: usize| u32::from_le_bytes(memory[address..address + 4].try_into().unwrap());
+        let length = word(address) as usize;
+        (0..length)
+            .map(|i| char::from_u32(word(address + 4 + i * 4)).unwrap())
+            .collect()
+    }
+
+    /// Compile the given statements as the body of a function, expecting it to
+    /// trap when called, and get the error code it set.
+    fn run_to_trap(body: &str) -> i32 {
+        let (mut store, instance) = instantiate(&format!("fn main()\n{body}\nend"));
+        let result = instance
+            .get_typed_func::<(), ()>(&store, "main")
+            .unwrap()
+            .call(&mut store, ());
+        assert!(result.is_err(), "expected a trap");
+
+        match instance
+            .get_global(&store, ERROR_CODE_EXPORT)
+            .unwrap()
+            .get(&store)
+        {
+            Value::I32(code) => code,
+            value => panic!("unexpected error code {value:?}"),
         }
-        unimplemented!()
+    }
+
+    /// Compile the given statements as the body of a function with the given
+    /// parameters and return type, then call it.
+    fn run<P: WasmParams, R: WasmResults>(params: &str, returns: &str, body: &str, args: P) -> R {
+        call(
+            &format!("fn main({params}) -> {returns}\n{body}\nend"),
+            "main",
+            args,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn empty_module() {
+        compile("");
+    }
+
+    #[test]
+    fn exports() {
+        let input = "fn main()\nend\npub fn id(x: int) -> int\nreturn x\nend\nfn half() -> float\nreturn 0.5\nend";
+        call::<(), ()>(input, "main", ()).unwrap();
+        assert_eq!(call::<i64, i64>(input, "id", 7).unwrap(), 7);
+
+        let wasm = compile(input);
+        let module = Module::new(&Engine::default(), &wasm[..]).unwrap();
+        let mut exports: Vec<_> = module.exports().map(|e| e.name().to_string()).collect();
+        exports.sort();
+        assert_eq!(
+            exports,
+            vec!["id", "kobe.error_code", "kobe.memory", "main"]
+        );
+    }
+
+    #[test]
+    fn exports_named_like_runtime() {
+        let input = "pub fn memory() -> int\nreturn 1\nend\n\
+                     pub fn error_code() -> int\nreturn 2\nend";
+        assert_eq!(call::<(), i64>(input, "memory", ()).unwrap(), 1);
+        assert_eq!(call::<(), i64>(input, "error_code", ()).unwrap(), 2);
+    }
+
+    #[test]
+    fn calls() {
+        let input = "fn main(n: int) -> int\nreturn add(n, double(n))\nend\n\
+                     fn double(x: int) -> int\nreturn add(x, x)\nend\n\
+                     fn add(a: int, b: int) -> int\nreturn a + b\nend";
+        assert_eq!(call::<i64, i64>(input, "main", 5).unwrap(), 15);
+
+        let input = "fn main() -> int\nlet x = 1\nignore(x)\nignore(x + 1)\nreturn x\nend\n\
+                     fn ignore(x: int)\nx = 0\nend";
+        assert_eq!(call::<(), i64>(input, "main", ()).unwrap(), 1);
+    }
+
+    #[test]
+    fn recursion() {
+        let input =
+            "fn main(n: int) -> int\nif n <= 1 then\nreturn 1\nend\nreturn n * main(n - 1)\nend";
+        assert_eq!(call::<i64, i64>(input, "main", 10).unwrap(), 3628800);
+
+        let input = "pub fn is_even(n: int) -> bool\nreturn n == 0 or is_odd(n - 1)\nend\n\
+                     pub fn is_odd(n: int) -> bool\nreturn n != 0 and is_even(n - 1)\nend";
+        assert_eq!(call::<i64, i32>(input, "is_even", 10).unwrap(), 1);
+        assert_eq!(call::<i64, i32>(input, "is_odd", 10).unwrap(), 0);
+    }
+
+    #[test]
+    fn arithmetic() {
+        let (a, b) = ("a: int, b: int", (9i64, 2i64));
+        assert_eq!(run::<_, i64>(a, "int", "return a + b * 2", b), 13);
+        assert_eq!(run::<_, i64>(a, "int", "return (a - b) / 2", b), 3);
+        assert_eq!(run::<_, i64>(a, "int", "return -a", b), -9);
+
+        let (a, b) = ("a: float, b: float", (F64::from(1.0), F64::from(4.0)));
+        assert_eq!(run::<_, F64>(a, "float", "return a / b", b), 0.25);
+        assert_eq!(run::<_, F64>(a, "float", "return -a * 2.0 + b", b), 2.0);
+    }
+
+    #[test]
+    fn division_by_zero_traps() {
+        let input = "fn main(a: int, b: int) -> int\nreturn a / b\nend";
+        assert!(call::<(i64, i64), i64>(input, "main", (1, 0)).is_err());
+    }
+
+    #[test]
+    fn comparisons() {
+        let compare = |op: &str, a: i64, b: i64| -> i32 {
+            run(
+                "a: int, b: int",
+                "bool",
+                &format!("return a {op} b"),
+                (a, b),
+            )
+        };
+        assert_eq!(compare("<", 1, 2), 1);
+        assert_eq!(compare("<", 2, 2), 0);
+        assert_eq!(compare("<=", 2, 2), 1);
+        assert_eq!(compare(">", -1, 2), 0);
+        assert_eq!(compare(">=", 3, 2), 1);
+        assert_eq!(compare("==", 2, 2), 1);
+        assert_eq!(compare("!=", 2, 2), 0);
+
+        let args = (F64::from(0.5), F64::from(1.0));
+        assert_eq!(
+            run::<_, i32>("a: float, b: float", "bool", "return a < b", args),
+            1
+        );
+        assert_eq!(run::<_, i32>("", "bool", "return 'a' < 'b'", ()), 1);
+        assert_eq!(run::<_, i32>("", "bool", "return true == !false", ()), 1);
+    }
+
+    #[test]
+    fn locals() {
+        let body = "let x = a * 2\nlet y: int\ny = x + 1\ny += 10\nreturn y";
+        assert_eq!(run::<i64, i64>("a: int", "int", body, 5), 21);
+
+        let body = "let total = 1.0\ntotal *= a\ntotal -= 0.5\nreturn total";
+        assert_eq!(run::<F64, F64>("a: float", "float", body, 3.0.into()), 2.5);
+    }
+
+    #[test]
+    fn shadowing() {
+        let body = "let x = 1\nif a then\nlet x = 2\nx += 1\nend\nreturn x";
+        assert_eq!(run::<i32, i64>("a: bool", "int", body, 1), 1);
+
+        let body = "let x = 1\nlet x = x + 1\nreturn x";
+        assert_eq!(run::<(), i64>("", "int", body, ()), 2);
+    }
+
+    #[test]
+    fn if_else() {
+        let body = "if a < 0 then\nreturn -1\nelse\nif a == 0 then\nreturn 0\nend\nend\nreturn 1";
+        assert_eq!(run::<i64, i64>("a: int", "int", body, -5), -1);
+        assert_eq!(run::<i64, i64>("a: int", "int", body, 0), 0);
+        assert_eq!(run::<i64, i64>("a: int", "int", body, 5), 1);
+
+        let body = "if a then\nreturn 1.0\nelse\nreturn 2.0\nend";
+        assert_eq!(run::<i32, F64>("a: bool", "float", body, 0), 2.0);
+    }
+
+    #[test]
+    fn while_loops() {
+        let body =
+            "let total = 0\nlet i = 1\nwhile i <= n do\ntotal += i\ni += 1\nend\nreturn total";
+        assert_eq!(run::<i64, i64>("n: int", "int", body, 10), 55);
+        assert_eq!(run::<i64, i64>("n: int", "int", body, 0), 0);
+
+        let body = "while true do\nif n > 100 then\nreturn n\nend\nn *= 2\nend\nreturn 0";
+        assert_eq!(run::<i64, i64>("n: int", "int", body, 3), 192);
+    }
+
+    #[test]
+    fn declarations_in_loops_are_reset() {
+        let body = "let total = 0\nwhile n > 0 do\nlet x: int\nx += n\ntotal += x\nn -= 1\nend\nreturn total";
+        assert_eq!(run::<i64, i64>("n: int", "int", body, 3), 6);
+    }
+
+    #[test]
+    fn short_circuiting() {
+        // Division by zero traps, so the right operands must not be evaluated.
+        let (params, body) = ("a: int, b: int", "return b != 0 and a / b > 1");
+        assert_eq!(run::<_, i32>(params, "bool", body, (4i64, 0i64)), 0);
+        assert_eq!(run::<_, i32>(params, "bool", body, (4i64, 2i64)), 1);
+
+        let body = "return b == 0 or a / b > 1";
+        assert_eq!(run::<_, i32>(params, "bool", body, (4i64, 0i64)), 1);
+        assert_eq!(run::<_, i32>(params, "bool", body, (2i64, 2i64)), 0);
+    }
+
+    #[test]
+    fn interpolation() {
+        assert_eq!(run_string("let x = 2\nreturn \"x = {x}\""), "x = 2");
+        assert_eq!(
+            run_string("return \"{1 + 2}{'é'}{true} or {false}!\""),
+            "3étrue or false!"
+        );
+        assert_eq!(
+            run_string("let s = \"b\"\nreturn \"a{s}{\"\"}c{\"{s}\"}\""),
+            "abcb"
+        );
+        assert_eq!(run_string("return \"{0}\""), "0");
+        assert_eq!(
+            run_string("return \"{-9223372036854775808} {9223372036854775807}\""),
+            "-9223372036854775808 9223372036854775807"
+        );
+        assert_eq!(run_string("return \"{-1507}\""), "-1507");
+
+        let float = |x: &str| run_string(&format!("return \"{{{x}}}\""));
+        assert_eq!(float("1.0"), "1.0");
+        assert_eq!(float("-2.5"), "-2.5");
+        assert_eq!(float("0.0"), "0.0");
+        assert_eq!(float("1.0 / 3.0"), "0.333333");
+        assert_eq!(float("0.1 + 0.2"), "0.3");
+        assert_eq!(float("123.0000004"), "123.0");
+        assert_eq!(float("0.0001"), "0.0001");
+        assert_eq!(float("999999999999999.5"), "999999999999999.5");
+        assert_eq!(float("0.9999999"), "1.0");
+        assert_eq!(float("1.5e20"), "1.5e20");
+        assert_eq!(float("-2.5e-7"), "-2.5e-7");
+        assert_eq!(float("1.0 / 0.0"), "inf");
+        assert_eq!(float("-1.0 / 0.0"), "-inf");
+        assert_eq!(float("0.0 / 0.0"), "NaN");
+    }
+
+    #[test]
+    fn raw_strings() {
+        assert_eq!(run_string("return r\"a\\n{b}\nc\""), "a\\n{b}\nc");
+        assert_eq!(run_string("return r##\"say \"#hi\"#\"##"), "say \"#hi\"#");
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(run_string("return \"\""), "");
+        assert_eq!(run_string("return \"hello, world\""), "hello, world");
+        assert_eq!(run_string("return \"héllo, wörld 😀\""), "héllo, wörld 😀");
+        assert_eq!(run_string("let s: string\nreturn s"), "");
+        assert_eq!(
+            run_string("let a = \"abc\"\nlet b = \"xyz\"\nreturn a"),
+            "abc"
+        );
+
+        assert_eq!(
+            run::<(), i32>("", "char", "return \"h😀llo\"[1]", ()),
+            '😀' as i32
+        );
+        assert_eq!(
+            run::<(), i32>("", "bool", "return \"abc\" == \"abc\"", ()),
+            1
+        );
+        assert_eq!(
+            run::<(), i32>("", "bool", "return \"abc\" == \"abd\"", ()),
+            0
+        );
+        assert_eq!(
+            run::<(), i32>("", "bool", "return \"abc\" != \"ab\"", ()),
+            1
+        );
+        assert_eq!(run::<(), i32>("", "bool", "return \"\" == \"\"", ()), 1);
+    }
+
+    #[test]
+    fn lists() {
+        let body = "let xs = [1, 2, 3]\nreturn xs[0] + xs[1] * xs[2]";
+        assert_eq!(run::<(), i64>("", "int", body, ()), 7);
+
+        let body = "let xs = [[0.5], [], [1.5, 2.5]]\nreturn xs[2][1] - xs[0][0]";
+        assert_eq!(run::<(), F64>("", "float", body, ()), 2.0);
+
+        let body = "let xs = [\"ab\", \"cd\"]\nreturn xs[i][1]";
+        assert_eq!(run::<i64, i32>("i: int", "char", body, 1), 'd' as i32);
+
+        let body = "let xs = [true, false, a]\nreturn xs[2]";
+        assert_eq!(run::<i32, i32>("a: bool", "bool", body, 1), 1);
+    }
+
+    #[test]
+    fn lists_are_allocated_separately() {
+        let body = "let xs = [a, a + 1]\nlet ys = [a * 10]\nlet zs = [[a], xs, ys]\n\
+                    return zs[0][0] + zs[1][1] + zs[2][0]";
+        assert_eq!(run::<i64, i64>("a: int", "int", body, 2), 25);
+
+        // lists allocated in a loop should not overwrite each other
+        let body = "let total = 0\nlet i = 0\nlet first = [0]\n\
+                    while i < 5 do\nlet xs = [i, i]\nif i == 0 then\nfirst = xs\nend\ntotal += xs[1]\ni += 1\nend\n\
+                    return total * 10 + first[0]";
+        assert_eq!(run::<(), i64>("", "int", body, ()), 100);
+    }
+
+    #[test]
+    fn memory_grows() {
+        // each list takes just over a page of memory
+        let body = "let i = 0\nwhile i < 10 do\n\
+                    let xs = [FILL]\ni += 1\nend\nreturn i"
+            .replace("FILL", &vec!["0"; 8200].join(", "));
+        assert_eq!(run::<(), i64>("", "int", &body, ()), 10);
+    }
+
+    #[test]
+    fn for_loops() {
+        let body =
+            "let total = 0\nfor x in [1, 2, 3, 4] do\ntotal = total * 10 + x\nend\nreturn total";
+        assert_eq!(run::<(), i64>("", "int", body, ()), 1234);
+
+        let body = "let total = 0.0\nfor x in xs do\ntotal += x\nend\nreturn total";
+        let input = format!("fn main() -> float\nreturn sum([0.5, 1.0, 2.5])\nend\nfn sum(xs: [float]) -> float\n{body}\nend");
+        assert_eq!(call::<(), F64>(&input, "main", ()).unwrap(), 4.0);
+
+        let body = "let count = 0\nfor c in \"a b c d\" do\nif c == ' ' then\ncount += 1\nend\nend\nreturn count";
+        assert_eq!(run::<(), i64>("", "int", body, ()), 3);
+
+        let body = "let count = 0\nfor xs in [[1], [], [2, 3]] do\nfor x in xs do\ncount += x\nend\nend\nreturn count";
+        assert_eq!(run::<(), i64>("", "int", body, ()), 6);
+
+        let body = "let xs: [int] = []\nfor x in xs do\nreturn 1\nend\nreturn 0";
+        assert_eq!(run::<(), i64>("", "int", body, ()), 0);
+    }
+
+    #[test]
+    fn range_loops() {
+        let body = "let total = 0\nfor i in a..b do\ntotal = total * 10 + i\nend\nreturn total";
+        let digits = |a: i64, b: i64| -> i64 { run("a: int, b: int", "int", body, (a, b)) };
+        assert_eq!(digits(1, 5), 1234);
+        assert_eq!(digits(3, 3), 0);
+        assert_eq!(digits(5, 1), 0);
+        assert_eq!(digits(-2, 1), -210);
+
+        // the bounds are only evaluated once and assigning to the loop variable
+        // does not affect iteration
+        let body =
+            "let count = 0\nfor i in 0..n do\nn += 1\ni += 10\ncount += 1\nend\nreturn count";
+        assert_eq!(run::<i64, i64>("n: int", "int", body, 4), 4);
+
+        let body =
+            "let count = 0\nfor i in 0..n do\nfor j in i..n do\ncount += 1\nend\nend\nreturn count";
+        assert_eq!(run::<i64, i64>("n: int", "int", body, 4), 10);
+    }
+
+    #[test]
+    fn index_out_of_bounds_traps() {
+        let code = RuntimeError::IndexOutOfBounds as i32;
+        assert_eq!(run_to_trap("let xs = [1, 2, 3]\nxs[3]"), code);
+        assert_eq!(run_to_trap("let xs = [1, 2, 3]\nxs[-1]"), code);
+        assert_eq!(run_to_trap("let xs: [int] = []\nxs[0]"), code);
+        assert_eq!(run_to_trap("\"abc\"[10]"), code);
+        assert_eq!(run_to_trap("[\"\"][0][0]"), code);
     }
 }
@@ -0,0 +1,211 @@
+//! Generation of documentation from the doc comments (`---`) and signatures of
+//! the functions in Kobe source files.
+
+use std::{fs, io, path::Path};
+
+use clap::ValueEnum;
+
+use crate::ast::Function;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DocFormat {
+    /// A Markdown file per module
+    Markdown,
+    /// A static HTML page per module along with an index page
+    Html,
+}
+
+/// The parsed functions of a single source file, named after that file.
+pub struct Module {
+    pub name: String,
+    pub functions: Vec<Function>,
+}
+
+/// Write documentation for the given modules to the directory `out_dir`,
+/// creating it if necessary. Only `pub` functions are documented unless
+/// `include_private` is set.
+pub fn write_docs(
+    out_dir: &Path,
+    format: DocFormat,
+    modules: &[Module],
+    include_private: bool,
+) -> io::Result<()> {
+    fs::create_dir_all(out_dir)?;
+
+    for module in modules {
+        let functions = documented_functions(module, include_private);
+        let (extension, contents) = match format {
+            DocFormat::Markdown => ("md", render_markdown(&module.name, &functions)),
+            DocFormat::Html => ("html", render_html(&module.name, &functions)),
+        };
+        fs::write(
+            out_dir.join(format!("{}.{extension}", module.name)),
+            contents,
+        )?;
+    }
+
+    if format == DocFormat::Html {
+        fs::write(out_dir.join("index.html"), render_html_index(modules))?;
+    }
+
+    Ok(())
+}
+
+fn documented_functions(module: &Module, include_private: bool) -> Vec<&Function> {
+    module
+        .functions
+        .iter()
+        .filter(|f| f.public || include_private)
+        .collect()
+}
+
+/// The signature of a function as it would be written in the source (e.g.,
+/// `pub fn f(a: int, b: [float]) -> bool`).
+pub fn signature(function: &Function) -> String {
+    let params = function
+        .params
+        .iter()
+        .map(|p| format!("{}: {}", p.name.name, p.param_type.kind))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let mut signature = format!(
+        "{}fn {}({params})",
+        if function.public { "pub " } else { "" },
+        function.name.name
+    );
+    if let Some(return_type) = &function.return_type {
+        signature += &format!(" -> {}", return_type.kind);
+    }
+    signature
+}
+
+fn render_markdown(module_name: &str, functions: &[&Function]) -> String {
+    let mut out = format!("# {module_name}\n");
+
+    for function in functions {
+        out += &format!("\n## `{}`\n\n", function.name.name);
+        out += &format!("```\n{}\n```\n", signature(function));
+        if let Some(doc) = &function.doc {
+            out += &format!("\n{doc}\n");
+        }
+    }
+
+    out
+}
+
+fn render_html(module_name: &str, functions: &[&Function]) -> String {
+    let mut body = format!("<h1>{}</h1>\n", escape_html(module_name));
+
+    for function in functions {
+        let name = escape_html(&function.name.name);
+        body += &format!("<section id=\"{name}\">\n<h2><code>{name}</code></h2>\n");
+        body += &format!(
+            "<pre><code>{}</code></pre>\n",
+            escape_html(&signature(function))
+        );
+
+        // blank lines in doc comments separate paragraphs
+        for paragraph in function.doc.iter().flat_map(|doc| doc.split("\n\n")) {
+            if !paragraph.trim().is_empty() {
+                body += &format!("<p>{}</p>\n", escape_html(paragraph.trim()));
+            }
+        }
+
+        body += "</section>\n";
+    }
+
+    html_page(module_name, &body)
+}
+
+fn render_html_index(modules: &[Module]) -> String {
+    let mut body = "<h1>Modules</h1>\n<ul>\n".to_string();
+    for module in modules {
+        let name = escape_html(&module.name);
+        body += &format!("<li><a href=\"{name}.html\">{name}</a></li>\n");
+    }
+    body += "</ul>\n";
+
+    html_page("Modules", &body)
+}
+
+fn html_page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n{body}</body>\n</html>\n",
+        escape_html(title)
+    )
+}
+
+fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{lex::Lexer, parse::Parser};
+
+    fn parse(input: &str) -> Vec<Function> {
+        let lexer = Lexer::new(Cursor::new(input), "test".to_string());
+        Parser::new(lexer).map(|f| f.unwrap()).collect()
+    }
+
+    #[test]
+    fn signatures() {
+        let functions = parse(
+            "fn f()\nend\npub fn g(a: int, bs: [[float]]) -> [bool]\nend\nfn h(s: string)\nend",
+        );
+        let signatures: Vec<_> = functions.iter().map(signature).collect();
+        assert_eq!(
+            signatures,
+            [
+                "fn f()",
+                "pub fn g(a: int, bs: [[float]]) -> [bool]",
+                "fn h(s: string)"
+            ]
+        );
+    }
+
+    #[test]
+    fn markdown() {
+        let functions =
+            parse("--- Adds one.\n---\n--- Really.\npub fn f(x: int) -> int\nend\nfn g()\nend");
+        let module = Module {
+            name: "m".to_string(),
+            functions,
+        };
+
+        assert_eq!(
+            render_markdown(&module.name, &documented_functions(&module, false)),
+            "# m\n\n## `f`\n\n```\npub fn f(x: int) -> int\n```\n\nAdds one.\n\nReally.\n"
+        );
+        assert_eq!(
+            render_markdown(&module.name, &documented_functions(&module, true)),
+            "# m\n\n## `f`\n\n```\npub fn f(x: int) -> int\n```\n\nAdds one.\n\nReally.\n\n## `g`\n\n```\nfn g()\n```\n"
+        );
+    }
+
+    #[test]
+    fn html() {
+        let functions = parse("--- Is x < y?\n---\n--- \"Maybe\".\npub fn f(x: int) -> int\nend");
+        let html = render_html("m", &functions.iter().collect::<Vec<_>>());
+
+        assert!(html.contains("<title>m</title>"));
+        assert!(html.contains("<section id=\"f\">\n<h2><code>f</code></h2>\n"));
+        assert!(html.contains("<pre><code>pub fn f(x: int) -&gt; int</code></pre>\n"));
+        assert!(html.contains("<p>Is x &lt; y?</p>\n<p>&quot;Maybe&quot;.</p>\n"));
+    }
+}
@@ -0,0 +1,616 @@
+//! Dumps of the tokens and syntax tree of a program, for debugging the
+//! compiler and for external tools. Each can be written either as text for
+//! people to read or as JSON (with the schema documented in the README).
+//!
+//! Syntax trees are written as indented S-expressions, with each statement on
+//! a line of its own, e.g.,
+//!
+//! ```text
+//! (fn main ():int
+//!   (let total 0)
+//!   (for x (list 1 2 3)
+//!     (+= total x))
+//!   (return total))
+//! ```
+//!
+//! Once the tree has been type checked, each variable and expression is
+//! followed by its type (e.g., `(+ total:int x:int):int`).
+
+use std::fmt::Write;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    ast::{Expr, ExprKind, Function, Stat, StatKind, Type, TypeExpr},
+    json::Position,
+    token::{Literal, Token},
+};
+
+/// The tokens read by the lexer, one per line with the span of each followed
+/// by its type, lexeme and value (if a literal).
+pub fn tokens(tokens: &[Token]) -> String {
+    let mut out = String::new();
+    for token in tokens {
+        write!(
+            out,
+            "{} {:?} {:?}",
+            token.span, token.tok_type, token.lexeme
+        )
+        .unwrap();
+        if let Some(value) = &token.value {
+            write!(out, " {value:?}").unwrap();
+        }
+        out += "\n";
+    }
+    out
+}
+
+/// A syntax tree, either before or after type checking, as S-expressions.
+pub fn ast(functions: &[Function]) -> String {
+    let mut out = String::new();
+    for function in functions {
+        let params: Vec<_> = function
+            .params
+            .iter()
+            .map(|param| {
+                let annotation = annotation(Some(&param.param_type), &param.ty);
+                format!("{}{annotation}", param.name.name)
+            })
+            .collect();
+        let header = format!(
+            "{}fn {} ({}){}",
+            if function.public { "pub " } else { "" },
+            function.name.name,
+            params.join(" "),
+            annotation(function.return_type.as_ref(), &function.returns),
+        );
+        write_block(&mut out, &header, &function.body, 0);
+        out += "\n";
+    }
+    out
+}
+
+/// Write a node whose children are the given statements, each on a line of its
+/// own, e.g., `(while cond` followed by the body.
+fn write_block(out: &mut String, header: &str, body: &[Stat], depth: usize) {
+    write!(out, "{}({header}", indent(depth)).unwrap();
+    for stat in body {
+        *out += "\n";
+        write_stat(out, stat, depth + 1);
+    }
+    *out += ")";
+}
+
+fn write_stat(out: &mut String, stat: &Stat, depth: usize) {
+    let line = match &stat.kind {
+        StatKind::Expr(expr) => sexpr(expr),
+        StatKind::Let {
+            name,
+            var_type,
+            value,
+            ty,
+            ..
+        } => {
+            let annotation = annotation(var_type.as_ref(), ty);
+            match value {
+                Some(value) => format!("(let {}{annotation} {})", name.name, sexpr(value)),
+                None => format!("(let {}{annotation})", name.name),
+            }
+        }
+        StatKind::Assign { target, value } => format!("(= {} {})", target.name, sexpr(value)),
+        StatKind::CompoundAssign { target, op, value } => {
+            format!("({op}= {} {})", target.name, sexpr(value))
+        }
+        StatKind::Return(value) => format!("(return {})", sexpr(value)),
+        StatKind::If {
+            condition,
+            then_body,
+            else_body,
+        } => {
+            writeln!(out, "{}(if {}", indent(depth), sexpr(condition)).unwrap();
+            write_block(out, "then", then_body, depth + 1);
+            if !else_body.is_empty() {
+                *out += "\n";
+                write_block(out, "else", else_body, depth + 1);
+            }
+            *out += ")";
+            return;
+        }
+        StatKind::While { condition, body } => {
+            let header = format!("while {}", sexpr(condition));
+            return write_block(out, &header, body, depth);
+        }
+        StatKind::For {
+            var,
+            iterable,
+            body,
+        } => {
+            let header = format!("for {} {}", var.name, sexpr(iterable));
+            return write_block(out, &header, body, depth);
+        }
+    };
+    write!(out, "{}{line}", indent(depth)).unwrap();
+}
+
+/// An expression as a single S-expression, followed by its type if known.
+fn sexpr(expr: &Expr) -> String {
+    let list = |head: &str, exprs: &[&Expr]| {
+        let mut s = format!("({head}");
+        for expr in exprs {
+            s += " ";
+            s += &sexpr(expr);
+        }
+        s + ")"
+    };
+
+    let s = match &expr.kind {
+        ExprKind::IntLiteral(value) => value.to_string(),
+        ExprKind::FloatLiteral(value) => format!("{value:?}"),
+        ExprKind::CharLiteral(value) => format!("{value:?}"),
+        ExprKind::StringLiteral(value) => format!("{value:?}"),
+        ExprKind::BoolLiteral(value) => value.to_string(),
+        ExprKind::Identifier(name) => name.clone(),
+        ExprKind::Interpolation(parts) => list("interpolate", &parts.iter().collect::<Vec<_>>()),
+        ExprKind::List(elements) => list("list", &elements.iter().collect::<Vec<_>>()),
+        ExprKind::Call { function, args } => list(
+            &format!("call {function}"),
+            &args.iter().collect::<Vec<_>>(),
+        ),
+        ExprKind::Range { start, end } => list("range", &[start, end]),
+        ExprKind::Index { target, index } => list("index", &[target, index]),
+        ExprKind::Binary { op, left, right } => list(&op.to_string(), &[left, right]),
+        ExprKind::Unary { op, operand } => list(&op.to_string(), &[operand]),
+    };
+    s + &annotation(None, &expr.ty)
+}
+
+/// The type of a variable or expression as a suffix (e.g., `:int`): the type
+/// determined during type checking if there is one, otherwise the type written
+/// in the source (if any). Empty for things with no type.
+fn annotation(written: Option<&TypeExpr>, ty: &Type) -> String {
+    match (ty, written) {
+        (Type::Unknown | Type::Void, Some(written)) => format!(":{}", written.kind),
+        (Type::Unknown | Type::Void, None) => String::new(),
+        (ty, _) => format!(":{ty}"),
+    }
+}
+
+fn indent(depth: usize) -> String {
+    "  ".repeat(depth)
+}
+
+#[derive(Serialize)]
+struct TokensJson<'a> {
+    file: &'a str,
+    tokens: Vec<TokenJson<'a>>,
+}
+
+#[derive(Serialize)]
+struct TokenJson<'a> {
+    #[serde(rename = "type")]
+    tok_type: String,
+    lexeme: &'a str,
+    /// The value of a literal as a number or string. `null` for other tokens.
+    value: Value,
+    span: Position,
+}
+
+#[derive(Serialize)]
+struct AstJson<'a> {
+    file: &'a str,
+    functions: Vec<FunctionJson<'a>>,
+}
+
+#[derive(Serialize)]
+struct FunctionJson<'a> {
+    name: &'a str,
+    doc: Option<&'a str>,
+    public: bool,
+    params: Vec<ParamJson<'a>>,
+    declared_return_type: Option<String>,
+    return_type: Option<String>,
+    body: Vec<StatJson<'a>>,
+    span: Position,
+}
+
+#[derive(Serialize)]
+struct ParamJson<'a> {
+    name: &'a str,
+    declared_type: String,
+    #[serde(rename = "type")]
+    ty: Option<String>,
+    span: Position,
+}
+
+#[derive(Serialize)]
+struct StatJson<'a> {
+    #[serde(flatten)]
+    kind: StatKindJson<'a>,
+    span: Position,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum StatKindJson<'a> {
+    Expr {
+        expr: ExprJson<'a>,
+    },
+    Let {
+        name: &'a str,
+        doc: Option<&'a str>,
+        declared_type: Option<String>,
+        value: Option<ExprJson<'a>>,
+        #[serde(rename = "type")]
+        ty: Option<String>,
+    },
+    Assign {
+        target: &'a str,
+        value: ExprJson<'a>,
+    },
+    CompoundAssign {
+        target: &'a str,
+        op: String,
+        value: ExprJson<'a>,
+    },
+    Return {
+        value: ExprJson<'a>,
+    },
+    If {
+        condition: ExprJson<'a>,
+        then_body: Vec<StatJson<'a>>,
+        else_body: Vec<StatJson<'a>>,
+    },
+    While {
+        condition: ExprJson<'a>,
+        body: Vec<StatJson<'a>>,
+    },
+    For {
+        variable: &'a str,
+        iterable: ExprJson<'a>,
+        body: Vec<StatJson<'a>>,
+    },
+}
+
+#[derive(Serialize)]
+struct ExprJson<'a> {
+    #[serde(flatten)]
+    kind: ExprKindJson<'a>,
+    #[serde(rename = "type")]
+    ty: Option<String>,
+    span: Position,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum ExprKindJson<'a> {
+    Int {
+        value: i64,
+    },
+    Float {
+        value: f64,
+    },
+    Char {
+        value: char,
+    },
+    String {
+        value: &'a str,
+    },
+    Bool {
+        value: bool,
+    },
+    Interpolation {
+        parts: Vec<ExprJson<'a>>,
+    },
+    List {
+        elements: Vec<ExprJson<'a>>,
+    },
+    Identifier {
+        name: &'a str,
+    },
+    Call {
+        function: &'a str,
+        args: Vec<ExprJson<'a>>,
+    },
+    Range {
+        start: Box<ExprJson<'a>>,
+        end: Box<ExprJson<'a>>,
+    },
+    Index {
+        target: Box<ExprJson<'a>>,
+        index: Box<ExprJson<'a>>,
+    },
+    Binary {
+        op: String,
+        left: Box<ExprJson<'a>>,
+        right: Box<ExprJson<'a>>,
+    },
+    Unary {
+        op: String,
+        operand: Box<ExprJson<'a>>,
+    },
+}
+
+/// The tokens read from the input with the given name as a JSON document.
+pub fn tokens_json(tokens: &[Token], input_source_name: &str) -> String {
+    let tokens = tokens
+        .iter()
+        .map(|token| TokenJson {
+            tok_type: format!("{:?}", token.tok_type),
+            lexeme: &token.lexeme,
+            value: match &token.value {
+                Some(Literal::Int(value)) => Value::from(*value),
+                Some(Literal::Float(value)) => Value::from(*value),
+                Some(Literal::Char(value)) => Value::from(value.to_string()),
+                Some(Literal::String(value)) => Value::from(value.as_str()),
+                None => Value::Null,
+            },
+            span: (&token.span).into(),
+        })
+        .collect();
+    to_json(&TokensJson {
+        file: input_source_name,
+        tokens,
+    })
+}
+
+/// A syntax tree parsed from the input with the given name as a JSON document.
+/// Types are `null` until the tree has been type checked.
+pub fn ast_json(functions: &[Function], input_source_name: &str) -> String {
+    to_json(&AstJson {
+        file: input_source_name,
+        functions: functions.iter().map(function_json).collect(),
+    })
+}
+
+fn to_json(value: &impl Serialize) -> String {
+    serde_json::to_string_pretty(value).expect("dumps can be serialised") + "\n"
+}
+
+fn function_json(function: &Function) -> FunctionJson<'_> {
+    FunctionJson {
+        name: &function.name.name,
+        doc: function.doc.as_deref(),
+        public: function.public,
+        params: function
+            .params
+            .iter()
+            .map(|param| ParamJson {
+                name: &param.name.name,
+                declared_type: param.param_type.kind.to_string(),
+                ty: type_json(&param.ty),
+                span: (&param.name.span.to(&param.param_type.span)).into(),
+            })
+            .collect(),
+        declared_return_type: function
+            .return_type
+            .as_ref()
+            .map(|return_type| return_type.kind.to_string()),
+        return_type: type_json(&function.returns),
+        body: body_json(&function.body),
+        span: (&function.span).into(),
+    }
+}
+
+fn body_json(body: &[Stat]) -> Vec<StatJson<'_>> {
+    body.iter().map(stat_json).collect()
+}
+
+fn stat_json(stat: &Stat) -> StatJson<'_> {
+    let kind = match &stat.kind {
+        StatKind::Expr(expr) => StatKindJson::Expr {
+            expr: expr_json(expr),
+        },
+        StatKind::Let {
+            name,
+            doc,
+            var_type,
+            value,
+            ty,
+        } => StatKindJson::Let {
+            name: &name.name,
+            doc: doc.as_deref(),
+            declared_type: var_type.as_ref().map(|var_type| var_type.kind.to_string()),
+            value: value.as_ref().map(expr_json),
+            ty: type_json(ty),
+        },
+        StatKind::Assign { target, value } => StatKindJson::Assign {
+            target: &target.name,
+            value: expr_json(value),
+        },
+        StatKind::CompoundAssign { target, op, value } => StatKindJson::CompoundAssign {
+            target: &target.name,
+            op: format!("{op}="),
+            value: expr_json(value),
+        },
+        StatKind::Return(value) => StatKindJson::Return {
+            value: expr_json(value),
+        },
+        StatKind::If {
+            condition,
+            then_body,
+            else_body,
+        } => StatKindJson::If {
+            condition: expr_json(condition),
+            then_body: body_json(then_body),
+            else_body: body_json(else_body),
+        },
+        StatKind::While { condition, body } => StatKindJson::While {
+            condition: expr_json(condition),
+            body: body_json(body),
+        },
+        StatKind::For {
+            var,
+            iterable,
+            body,
+        } => StatKindJson::For {
+            variable: &var.name,
+            iterable: expr_json(iterable),
+            body: body_json(body),
+        },
+    };
+    StatJson {
+        kind,
+        span: (&stat.span).into(),
+    }
+}
+
+fn expr_json(expr: &Expr) -> ExprJson<'_> {
+    let boxed = |expr| Box::new(expr_json(expr));
+    let kind = match &expr.kind {
+        ExprKind::IntLiteral(value) => ExprKindJson::Int { value: *value },
+        ExprKind::FloatLiteral(value) => ExprKindJson::Float { value: *value },
+        ExprKind::CharLiteral(value) => ExprKindJson::Char { value: *value },
+        ExprKind::StringLiteral(value) => ExprKindJson::String { value },
+        ExprKind::BoolLiteral(value) => ExprKindJson::Bool { value: *value },
+        ExprKind::Interpolation(parts) => ExprKindJson::Interpolation {
+            parts: parts.iter().map(expr_json).collect(),
+        },
+        ExprKind::List(elements) => ExprKindJson::List {
+            elements: elements.iter().map(expr_json).collect(),
+        },
+        ExprKind::Identifier(name) => ExprKindJson::Identifier { name },
+        ExprKind::Call { function, args } => ExprKindJson::Call {
+            function,
+            args: args.iter().map(expr_json).collect(),
+        },
+        ExprKind::Range { start, end } => ExprKindJson::Range {
+            start: boxed(start),
+            end: boxed(end),
+        },
+        ExprKind::Index { target, index } => ExprKindJson::Index {
+            target: boxed(target),
+            index: boxed(index),
+        },
+        ExprKind::Binary { op, left, right } => ExprKindJson::Binary {
+            op: op.to_string(),
+            left: boxed(left),
+            right: boxed(right),
+        },
+        ExprKind::Unary { op, operand } => ExprKindJson::Unary {
+            op: op.to_string(),
+            operand: boxed(operand),
+        },
+    };
+    ExprJson {
+        kind,
+        ty: type_json(&expr.ty),
+        span: (&expr.span).into(),
+    }
+}
+
+/// A type as a string, or `None` if it has not been determined.
+fn type_json(ty: &Type) -> Option<String> {
+    (*ty != Type::Unknown).then(|| ty.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{check::TypeChecker, lex::Lexer, parse::Parser};
+
+    const PROGRAM: &str = "\
+pub fn main(n: int) -> int
+  let total = 0
+  for x in 0..n do
+    if x > 2 and !false then
+      total += x
+    else
+      log(\"skip {x}\")
+    end
+  end
+  return total
+end
+
+fn log(_message: string)
+end";
+
+    fn parse(input: &str) -> Vec<Function> {
+        let lexer = Lexer::new(Cursor::new(input), "main.kobe".to_string());
+        Parser::new(lexer).parse_all().unwrap()
+    }
+
+    fn check(input: &str) -> Vec<Function> {
+        let lexer = Lexer::new(Cursor::new(input), "main.kobe".to_string());
+        let mut checker = TypeChecker::new(Parser::new(lexer));
+        let functions = checker.parser_mut().parse_all().unwrap();
+        checker.check(functions).unwrap().0
+    }
+
+    #[test]
+    fn token_lines() {
+        let lexer = Lexer::new(Cursor::new("x = 1\n"), "test".to_string());
+        let lexed: Vec<_> = lexer.map(Result::unwrap).collect();
+        assert_eq!(
+            tokens(&lexed),
+            "1:1-1:1 Identifier \"x\"\n\
+             1:3-1:3 Assign \"=\"\n\
+             1:5-1:5 IntLiteral \"1\" Int(1)\n\
+             1:6-1:6 EndStatement \"\\n\"\n"
+        );
+    }
+
+    #[test]
+    fn sexprs() {
+        assert_eq!(
+            ast(&parse(PROGRAM)),
+            "\
+(pub fn main (n:int):int
+  (let total 0)
+  (for x (range 0 n)
+    (if (and (> x 2) (! false))
+      (then
+        (+= total x))
+      (else
+        (call log (interpolate \"skip \" x)))))
+  (return total))
+(fn log (_message:string))
+"
+        );
+    }
+
+    #[test]
+    fn typed_sexprs() {
+        let printed = ast(&check(PROGRAM));
+        assert!(printed.contains("(let total:int 0:int)"));
+        assert!(printed.contains("(+= total x:int)"));
+        assert!(printed.contains("(and (> x:int 2:int):bool (! false:bool):bool):bool"));
+    }
+
+    #[test]
+    fn token_json() {
+        let lexer = Lexer::new(Cursor::new("'a' 1.5"), "test".to_string());
+        let lexed: Vec<_> = lexer.map(Result::unwrap).collect();
+        let json: Value = serde_json::from_str(&tokens_json(&lexed, "test")).unwrap();
+        assert_eq!(json["file"], "test");
+        assert_eq!(json["tokens"][0]["type"], "CharLiteral");
+        assert_eq!(json["tokens"][0]["lexeme"], "'a'");
+        assert_eq!(json["tokens"][0]["value"], "a");
+        assert_eq!(json["tokens"][1]["value"], 1.5);
+        assert_eq!(json["tokens"][1]["span"]["column_start"], 5);
+    }
+
+    #[test]
+    fn ast_json_schema() {
+        let input = "fn f(xs: [int]) -> int\nreturn -xs[0]\nend";
+        let json: Value = serde_json::from_str(&ast_json(&parse(input), "main.kobe")).unwrap();
+        let function = &json["functions"][0];
+        assert_eq!(function["name"], "f");
+        assert_eq!(function["params"][0]["declared_type"], "[int]");
+        assert_eq!(function["params"][0]["type"], Value::Null);
+        assert_eq!(function["declared_return_type"], "int");
+        assert_eq!(function["return_type"], Value::Null);
+
+        let stat = &function["body"][0];
+        assert_eq!(stat["kind"], "return");
+        assert_eq!(stat["span"]["line_start"], 2);
+        assert_eq!(stat["value"]["kind"], "unary");
+        assert_eq!(stat["value"]["op"], "-");
+        assert_eq!(stat["value"]["operand"]["kind"], "index");
+
+        let json: Value = serde_json::from_str(&ast_json(&check(input), "main.kobe")).unwrap();
+        assert_eq!(json["functions"][0]["body"][0]["value"]["type"], "int");
+    }
+}
@@ -0,0 +1,103 @@
+//! Output of the final and intermediate stages of compilation, selected on the
+//! command line with `--emit`.
+
+use std::{path::PathBuf, str::FromStr};
+
+use clap::ValueEnum;
+
+/// A kind of output. Variants are in the order in which they are produced
+/// during compilation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum Emit {
+    /// The tokens read by the lexer, one per line
+    Tokens,
+    /// The syntax tree produced by the parser
+    Ast,
+    /// The syntax tree annotated with types by the type checker
+    TypedAst,
+    /// A binary WebAssembly module
+    Wasm,
+    /// The WebAssembly module in the text format, annotated with source lines
+    Wat,
+}
+
+impl Emit {
+    /// Whether the output is binary rather than text.
+    pub fn is_binary(self) -> bool {
+        self == Emit::Wasm
+    }
+}
+
+/// A kind of output to produce along with where to write it, given on the
+/// command line as `KIND[=PATH]`. Without a path, the output is written to the
+/// output file or stdout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmitRequest {
+    pub kind: Emit,
+    pub path: Option<PathBuf>,
+}
+
+impl FromStr for EmitRequest {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, path) = match s.split_once('=') {
+            Some((kind, "")) => return Err(format!("no path given for '{kind}'")),
+            Some((kind, path)) => (kind, Some(PathBuf::from(path))),
+            None => (s, None),
+        };
+        if kind == "ir" {
+            return Err(
+                "there is no IR to emit, as WebAssembly is generated directly \
+                        from the typed syntax tree (use typed-ast to see it)"
+                    .to_string(),
+            );
+        }
+        let kind = Emit::from_str(kind, false).map_err(|_| {
+            let kinds: Vec<_> = Emit::value_variants()
+                .iter()
+                .filter_map(|kind| kind.to_possible_value())
+                .map(|value| value.get_name().to_string())
+                .collect();
+            format!(
+                "unknown kind '{kind}' (expected one of {})",
+                kinds.join(", ")
+            )
+        })?;
+        Ok(EmitRequest { kind, path })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests() {
+        assert_eq!(
+            "typed-ast".parse(),
+            Ok(EmitRequest {
+                kind: Emit::TypedAst,
+                path: None
+            })
+        );
+        assert_eq!(
+            "wasm=out/main.wasm".parse(),
+            Ok(EmitRequest {
+                kind: Emit::Wasm,
+                path: Some(PathBuf::from("out/main.wasm"))
+            })
+        );
+        assert!("wasm=".parse::<EmitRequest>().is_err());
+        assert!("ir"
+            .parse::<EmitRequest>()
+            .is_err_and(|e| e.starts_with("there is no IR")));
+        assert_eq!(
+            "asm".parse::<EmitRequest>(),
+            Err(
+                "unknown kind 'asm' (expected one of tokens, ast, typed-ast, wasm, wat)"
+                    .to_string()
+            )
+        );
+    }
+}
@@ -1,38 +1,273 @@
-use std::fmt;
+use std::{collections::BTreeMap, fmt, io};
 
+use crate::{
+    ast::{BinOp, Type, UnOp},
+    lex::ColumnUnit,
+    lint::Lint,
+    render,
+    span::Span,
+    token::TokenType,
+};
+
+/// The maximum number of errors that will be reported before compilation is
+/// abandoned.
+pub const MAX_ERRORS: usize = 20;
+
+/// A count followed by a noun, pluralised if needed (e.g., "2 errors").
+pub fn plural(count: usize, noun: &str) -> String {
+    let s = if count == 1 { "" } else { "s" };
+    format!("{count} {noun}{s}")
+}
+
+/// A compile error (or warning). Along with its kind and the span of input at
+/// which it occurred, an error may carry labels pointing out other relevant
+/// parts of the input, notes giving further explanation and suggested fixes,
+/// all of which are shown when it is rendered (see [`crate::render`]).
 #[derive(Debug, PartialEq)]
 pub struct Error {
     pub kind: ErrorKind,
-    pub line_number: usize,
-    pub char_number: usize,
-    pub line: String,
+    pub severity: Severity,
+    pub span: Span,
+    /// Message shown alongside the underlined primary span (if any).
+    pub label: Option<String>,
+    /// Other spans of input relevant to the error, such as where a variable
+    /// was declared.
+    pub secondary_labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
+    pub suggestions: Vec<Suggestion>,
+    /// The full text of each line of input covered by the primary and
+    /// secondary spans, by line number.
+    pub source_lines: BTreeMap<usize, String>,
     pub input_source_name: String,
+    /// The unit in which the columns of the spans are counted, needed to find
+    /// the characters they refer to in `source_lines`.
+    pub column_unit: ColumnUnit,
+}
+
+/// Whether an error prevents compilation. Only lints produce warnings, and
+/// then only if they are not denied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A message attached to a span of the input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+/// A fix for an error which can be applied mechanically, by replacing the
+/// bytes of the input in `span` with `replacement`. Insertions have an empty
+/// byte range.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    pub span: Span,
+    pub replacement: String,
+    /// Description of the fix, shown as help when the error is rendered.
+    pub message: String,
+}
+
+impl Error {
+    /// Create an error with no labels, notes or source lines. The latter are
+    /// filled in (along with the column unit) by
+    /// [`crate::lex::Lexer::with_source`].
+    pub fn new(kind: ErrorKind, span: Span) -> Self {
+        Error {
+            kind,
+            severity: Severity::Error,
+            span,
+            label: None,
+            secondary_labels: Vec::new(),
+            notes: Vec::new(),
+            help: Vec::new(),
+            suggestions: Vec::new(),
+            source_lines: BTreeMap::new(),
+            input_source_name: String::new(),
+            column_unit: ColumnUnit::default(),
+        }
+    }
+
+    /// Create an error for a failure to read the named input at all, which
+    /// therefore does not relate to any part of it.
+    pub fn io(error: io::Error, input_source_name: String) -> Self {
+        let mut error = Error::new(error.into(), Span::default());
+        error.input_source_name = input_source_name;
+        error
+    }
+
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    pub fn with_label(mut self, message: impl Into<String>) -> Self {
+        self.label = Some(message.into());
+        self
+    }
+
+    pub fn with_secondary_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.secondary_labels.push(Label {
+            span,
+            message: message.into(),
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help.push(help.into());
+        self
+    }
+
+    pub fn with_suggestion(
+        mut self,
+        span: Span,
+        replacement: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        self.suggestions.push(Suggestion {
+            span,
+            replacement: replacement.into(),
+            message: message.into(),
+        });
+        self
+    }
+
+    /// The primary span followed by the span of each secondary label.
+    pub fn spans(&self) -> impl Iterator<Item = &Span> {
+        std::iter::once(&self.span).chain(self.secondary_labels.iter().map(|label| &label.span))
+    }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(f, "{}", self.kind)?;
-        writeln!(
-            f,
-            "> {}:{}:{}",
-            self.input_source_name, self.line_number, self.char_number
-        )?;
-        writeln!(f, "| {}", self.line)?;
-        writeln!(f, "|{}^", " ".repeat(self.char_number))
+        write!(f, "{}", render::render(self, false))
     }
 }
 
 #[derive(Debug, PartialEq)]
 pub enum ErrorKind {
+    /// The input could not be read. The kind and message of the underlying
+    /// [`io::Error`] are kept (rather than the error itself) so that errors can
+    /// be compared.
+    Io {
+        kind: io::ErrorKind,
+        message: String,
+    },
     Lexical(LexicalErrorKind),
-    Syntax,
+    Syntax(SyntaxErrorKind),
+    Type(TypeErrorKind),
+    Lint(LintKind),
+}
+
+impl ErrorKind {
+    /// A unique code identifying this kind of error (e.g., `K0307`), the
+    /// second and third digits of which give the category of error: `00`
+    /// I/O, `01` lexical, `02` syntax, `03` type and `04` lint.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorKind::Io { .. } => "K0001",
+            ErrorKind::Lexical(kind) => match kind {
+                LexicalErrorKind::UnexpectedCharacter => "K0101",
+                LexicalErrorKind::InvalidFloatLiteral => "K0102",
+                LexicalErrorKind::InvalidCharLiteral => "K0103",
+                LexicalErrorKind::InvalidStringLiteral => "K0104",
+                LexicalErrorKind::InvalidEscapeCode => "K0105",
+                LexicalErrorKind::InvalidHexEscape => "K0106",
+                LexicalErrorKind::InvalidUnicodeEscape => "K0107",
+                LexicalErrorKind::InvalidUtf8 => "K0108",
+                LexicalErrorKind::UnterminatedComment => "K0109",
+                LexicalErrorKind::InvalidDigit { .. } => "K0110",
+                LexicalErrorKind::MissingDigits => "K0111",
+                LexicalErrorKind::InvalidNumberSuffix(_) => "K0112",
+                LexicalErrorKind::NumberLiteralOutOfRange(_) => "K0113",
+            },
+            ErrorKind::Syntax(kind) => match kind {
+                SyntaxErrorKind::UnexpectedToken { .. } => "K0201",
+                SyntaxErrorKind::UnexpectedEof { .. } => "K0202",
+                SyntaxErrorKind::ExpectedExpression { .. } => "K0203",
+                SyntaxErrorKind::MissingEnd { .. } => "K0204",
+                SyntaxErrorKind::InvalidAssignmentTarget => "K0205",
+            },
+            ErrorKind::Type(kind) => match kind {
+                TypeErrorKind::UnknownType(_) => "K0301",
+                TypeErrorKind::UndefinedVariable(_) => "K0302",
+                TypeErrorKind::UndefinedFunction(_) => "K0303",
+                TypeErrorKind::DuplicateFunction(_) => "K0304",
+                TypeErrorKind::WrongArgumentCount { .. } => "K0305",
+                TypeErrorKind::NoValue(_) => "K0306",
+                TypeErrorKind::Mismatch { .. } => "K0307",
+                TypeErrorKind::InvalidOperands { .. } => "K0308",
+                TypeErrorKind::InvalidOperand { .. } => "K0309",
+                TypeErrorKind::NotIterable(_) => "K0310",
+                TypeErrorKind::NotIndexable(_) => "K0311",
+                TypeErrorKind::NotInterpolatable(_) => "K0312",
+                TypeErrorKind::DuplicateParameter(_) => "K0313",
+                TypeErrorKind::MissingTypeAnnotation(_) => "K0314",
+                TypeErrorKind::CannotInferType(_) => "K0315",
+                TypeErrorKind::UnexpectedReturnValue => "K0316",
+                TypeErrorKind::MissingReturn => "K0317",
+            },
+            ErrorKind::Lint(kind) => match kind {
+                LintKind::UnusedVariable(_) => "K0401",
+                LintKind::UnusedParameter(_) => "K0402",
+                LintKind::UnusedAssignment(_) => "K0403",
+                LintKind::UnreachableCode => "K0404",
+                LintKind::Shadowing(_) => "K0405",
+                LintKind::ReadBeforeAssignment(_) => "K0406",
+            },
+        }
+    }
+
+    /// A description of the error, without the category given by
+    /// [`ErrorKind`]'s `Display`.
+    pub fn message(&self) -> String {
+        match self {
+            ErrorKind::Io { message, .. } => format!("could not read input: {message}"),
+            ErrorKind::Lexical(kind) => kind.to_string(),
+            ErrorKind::Syntax(kind) => kind.to_string(),
+            ErrorKind::Type(kind) => kind.to_string(),
+            ErrorKind::Lint(kind) => kind.to_string(),
+        }
+    }
+}
+
+impl From<io::Error> for ErrorKind {
+    fn from(error: io::Error) -> Self {
+        ErrorKind::Io {
+            kind: error.kind(),
+            message: error.to_string(),
+        }
+    }
 }
 
 impl fmt::Display for ErrorKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
+            ErrorKind::Io { .. } => write!(f, "I/O error: {}.", self.message()),
             ErrorKind::Lexical(k) => write!(f, "Lexical error: {k}."),
-            ErrorKind::Syntax => write!(f, "Syntax error: ..."),
+            ErrorKind::Syntax(k) => write!(f, "Syntax error: {k}."),
+            ErrorKind::Type(k) => write!(f, "Type error: {k}."),
+            // a lint may be reported as either a warning or an error, which
+            // only the severity of the error says
+            ErrorKind::Lint(k) => write!(f, "Lint: {k}."),
         }
     }
 }
@@ -44,6 +279,26 @@ pub enum LexicalErrorKind {
     InvalidCharLiteral,
     InvalidStringLiteral,
     InvalidEscapeCode,
+    /// A `\x` escape not followed by two hex digits giving an ASCII
+    /// character.
+    InvalidHexEscape,
+    /// A `\u` escape not of the form `\u{XXXX}` (with 1 to 6 hex digits) or
+    /// which does not give a valid character.
+    InvalidUnicodeEscape,
+    InvalidUtf8,
+    /// A block comment (`--[[`) which is not closed before the end of input.
+    UnterminatedComment,
+    /// A digit which is not valid in the radix of a number literal (e.g.,
+    /// the `2` in `0b102`).
+    InvalidDigit {
+        digit: char,
+        radix: u32,
+    },
+    /// A number literal with a radix prefix but no digits (e.g., `0x`).
+    MissingDigits,
+    InvalidNumberSuffix(String),
+    /// A number literal too large for the given type (e.g., `i64`).
+    NumberLiteralOutOfRange(&'static str),
 }
 
 impl fmt::Display for LexicalErrorKind {
@@ -54,8 +309,262 @@ impl fmt::Display for LexicalErrorKind {
             LexicalErrorKind::InvalidCharLiteral => write!(f, "invalid character literal"),
             LexicalErrorKind::InvalidStringLiteral => write!(f, "invalid string literal"),
             LexicalErrorKind::InvalidEscapeCode => write!(f, "invalid escape code"),
+            LexicalErrorKind::InvalidHexEscape => write!(
+                f,
+                "invalid escape code, '\\x' must be followed by two hex digits no greater than 7F"
+            ),
+            LexicalErrorKind::InvalidUnicodeEscape => write!(
+                f,
+                "invalid escape code, expected '\\u{{XXXX}}' with 1 to 6 hex digits giving a valid character"
+            ),
+            LexicalErrorKind::InvalidUtf8 => write!(f, "input is not valid UTF-8"),
+            LexicalErrorKind::UnterminatedComment => write!(f, "unterminated block comment"),
+            LexicalErrorKind::InvalidDigit { digit, radix } => {
+                let radix_name = match radix {
+                    2 => "binary",
+                    8 => "octal",
+                    16 => "hexadecimal",
+                    _ => "decimal",
+                };
+                write!(f, "invalid digit '{digit}' in {radix_name} literal")
+            }
+            LexicalErrorKind::MissingDigits => write!(f, "number literal has no digits"),
+            LexicalErrorKind::InvalidNumberSuffix(suffix) => {
+                write!(f, "invalid suffix '{suffix}' for number literal")
+            }
+            LexicalErrorKind::NumberLiteralOutOfRange(ty) => {
+                write!(f, "number literal does not fit in {ty}")
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SyntaxErrorKind {
+    UnexpectedToken {
+        expected: Vec<TokenType>,
+        found: TokenType,
+    },
+    UnexpectedEof {
+        expected: Vec<TokenType>,
+    },
+    ExpectedExpression {
+        found: TokenType,
+    },
+    /// The input ended before the block opened by the given token (on the
+    /// given line) was closed with `end`.
+    MissingEnd {
+        opened_by: TokenType,
+        opened_at: usize,
+    },
+    InvalidAssignmentTarget,
+}
+
+impl fmt::Display for SyntaxErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SyntaxErrorKind::UnexpectedToken { expected, found } => {
+                write!(f, "expected {}, found {found}", ExpectedList(expected))
+            }
+            SyntaxErrorKind::UnexpectedEof { expected } if expected.is_empty() => {
+                write!(f, "unexpected end of input")
+            }
+            SyntaxErrorKind::UnexpectedEof { expected } => {
+                write!(f, "expected {}, found end of input", ExpectedList(expected))
+            }
+            SyntaxErrorKind::ExpectedExpression { found } => {
+                write!(f, "expected expression, found {found}")
+            }
+            SyntaxErrorKind::MissingEnd {
+                opened_by,
+                opened_at,
+            } => write!(f, "missing 'end' to close {opened_by} on line {opened_at}"),
+            SyntaxErrorKind::InvalidAssignmentTarget => {
+                write!(f, "can only assign to a variable")
+            }
         }
     }
 }
 
+#[derive(Debug, PartialEq)]
+pub enum TypeErrorKind {
+    UnknownType(String),
+    UndefinedVariable(String),
+    UndefinedFunction(String),
+    DuplicateFunction(String),
+    WrongArgumentCount {
+        function: String,
+        expected: usize,
+        found: usize,
+    },
+    /// A call to a function without a return type used where a value is
+    /// needed.
+    NoValue(String),
+    Mismatch {
+        expected: Type,
+        found: Type,
+    },
+    InvalidOperands {
+        op: BinOp,
+        left: Type,
+        right: Type,
+    },
+    InvalidOperand {
+        op: UnOp,
+        operand: Type,
+    },
+    NotIterable(Type),
+    NotIndexable(Type),
+    /// A value which cannot be converted to a string used in an interpolated
+    /// string.
+    NotInterpolatable(Type),
+    DuplicateParameter(String),
+    /// A variable declared with neither a type annotation nor an initial
+    /// value.
+    MissingTypeAnnotation(String),
+    /// The type of a variable's initial value could not be fully determined
+    /// (e.g., an empty list) and there is no type annotation, or (without a
+    /// variable name) the type of an expression could not be determined at
+    /// all (e.g., indexing into an empty list).
+    CannotInferType(Option<String>),
+    /// A `return` in a function without a return type.
+    UnexpectedReturnValue,
+    /// A function with a return type may reach the end of its body without
+    /// returning a value.
+    MissingReturn,
+}
+
+impl fmt::Display for TypeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TypeErrorKind::UnknownType(name) => write!(f, "unknown type '{name}'"),
+            TypeErrorKind::UndefinedVariable(name) => {
+                write!(f, "use of undeclared variable '{name}'")
+            }
+            TypeErrorKind::UndefinedFunction(name) => {
+                write!(f, "call to undefined function '{name}'")
+            }
+            TypeErrorKind::DuplicateFunction(name) => {
+                write!(f, "function '{name}' is defined more than once")
+            }
+            TypeErrorKind::WrongArgumentCount {
+                function,
+                expected,
+                found,
+            } => {
+                let plural = if *expected == 1 { "" } else { "s" };
+                let verb = if *found == 1 { "was" } else { "were" };
+                write!(
+                    f,
+                    "function '{function}' takes {expected} argument{plural} but {found} {verb} given"
+                )
+            }
+            TypeErrorKind::NoValue(name) => {
+                write!(f, "function '{name}' does not return a value")
+            }
+            TypeErrorKind::Mismatch { expected, found } => {
+                write!(f, "expected {expected}, found {found}")
+            }
+            TypeErrorKind::InvalidOperands { op, left, right } => {
+                write!(f, "cannot apply '{op}' to {left} and {right}")
+            }
+            TypeErrorKind::InvalidOperand { op, operand } => {
+                write!(f, "cannot apply unary '{op}' to {operand}")
+            }
+            TypeErrorKind::NotIterable(ty) => write!(f, "cannot iterate over {ty}"),
+            TypeErrorKind::NotIndexable(ty) => write!(f, "cannot index into {ty}"),
+            TypeErrorKind::NotInterpolatable(ty) => {
+                write!(f, "cannot interpolate {ty} into a string")
+            }
+            TypeErrorKind::DuplicateParameter(name) => {
+                write!(f, "parameter '{name}' is declared more than once")
+            }
+            TypeErrorKind::MissingTypeAnnotation(name) => write!(
+                f,
+                "variable '{name}' needs either a type annotation or an initial value"
+            ),
+            TypeErrorKind::CannotInferType(Some(name)) => {
+                write!(f, "cannot infer the type of variable '{name}'")
+            }
+            TypeErrorKind::CannotInferType(None) => {
+                write!(f, "cannot infer the type of this expression")
+            }
+            TypeErrorKind::UnexpectedReturnValue => {
+                write!(
+                    f,
+                    "cannot return a value from a function without a return type"
+                )
+            }
+            TypeErrorKind::MissingReturn => {
+                write!(f, "function may reach its end without returning a value")
+            }
+        }
+    }
+}
+
+/// Displays a list of expected token types like "`a`, `b` or `c`".
+struct ExpectedList<'a>(&'a [TokenType]);
+
+impl fmt::Display for ExpectedList<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, tok_type) in self.0.iter().enumerate() {
+            if i > 0 {
+                let separator = if i == self.0.len() - 1 { " or " } else { ", " };
+                write!(f, "{separator}")?;
+            }
+            write!(f, "{tok_type}")?;
+        }
+        Ok(())
+    }
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
+
+/// Code which is valid but probably not what was intended, as found by one of
+/// the [`Lint`]s.
+#[derive(Debug, PartialEq)]
+pub enum LintKind {
+    UnusedVariable(String),
+    UnusedParameter(String),
+    /// A variable which is assigned to (other than by its declaration) but
+    /// never read.
+    UnusedAssignment(String),
+    UnreachableCode,
+    Shadowing(String),
+    /// A variable declared without an initial value which is read before
+    /// being assigned to on at least one path through the function.
+    ReadBeforeAssignment(String),
+}
+
+impl LintKind {
+    /// The lint which finds this kind of problem, and so controls whether and
+    /// how it is reported.
+    pub fn lint(&self) -> Lint {
+        match self {
+            LintKind::UnusedVariable(_) | LintKind::UnusedParameter(_) => Lint::UnusedVariables,
+            LintKind::UnusedAssignment(_) => Lint::UnusedAssignments,
+            LintKind::UnreachableCode => Lint::UnreachableCode,
+            LintKind::Shadowing(_) => Lint::Shadowing,
+            LintKind::ReadBeforeAssignment(_) => Lint::ReadBeforeAssignment,
+        }
+    }
+}
+
+impl fmt::Display for LintKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LintKind::UnusedVariable(name) => write!(f, "unused variable '{name}'"),
+            LintKind::UnusedParameter(name) => write!(f, "unused parameter '{name}'"),
+            LintKind::UnusedAssignment(name) => {
+                write!(f, "variable '{name}' is assigned to, but never read")
+            }
+            LintKind::UnreachableCode => write!(f, "unreachable code"),
+            LintKind::Shadowing(name) => {
+                write!(f, "declaration of '{name}' shadows an earlier declaration")
+            }
+            LintKind::ReadBeforeAssignment(name) => {
+                write!(f, "variable '{name}' may be read before it is assigned")
+            }
+        }
+    }
+}
@@ -0,0 +1,191 @@
+//! Errors as JSON, for consumption by editors and other tools. Each error is
+//! written as a single line holding one object of the following form (the
+//! schema is documented in full in the README):
+//!
+//! ```json
+//! {
+//!   "code": "K0307",
+//!   "severity": "error",
+//!   "message": "expected int, found float",
+//!   "file": "main.kobe",
+//!   "spans": [
+//!     {
+//!       "line_start": 3,
+//!       "column_start": 5,
+//!       "line_end": 3,
+//!       "column_end": 13,
+//!       "byte_start": 26,
+//!       "byte_end": 35,
+//!       "is_primary": true,
+//!       "label": "expected int"
+//!     }
+//!   ],
+//!   "notes": [],
+//!   "help": [],
+//!   "suggestions": []
+//! }
+//! ```
+
+use serde::Serialize;
+
+use crate::{error::Error, span::Span};
+
+#[derive(Serialize)]
+pub struct Diagnostic<'a> {
+    code: &'static str,
+    severity: String,
+    message: String,
+    file: &'a str,
+    /// The primary span followed by any secondary spans. Empty if the error
+    /// does not relate to any part of the input.
+    spans: Vec<LabelledSpan<'a>>,
+    notes: &'a [String],
+    help: &'a [String],
+    suggestions: Vec<Suggestion<'a>>,
+}
+
+/// The position of a span, with both line and column pairs inclusive (so
+/// giving the first and last characters) and the byte offsets exclusive at the
+/// end.
+#[derive(Serialize)]
+pub struct Position {
+    line_start: usize,
+    column_start: usize,
+    line_end: usize,
+    column_end: usize,
+    byte_start: usize,
+    byte_end: usize,
+}
+
+#[derive(Serialize)]
+struct LabelledSpan<'a> {
+    #[serde(flatten)]
+    position: Position,
+    is_primary: bool,
+    label: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct Suggestion<'a> {
+    message: &'a str,
+    /// Text to replace the bytes of `span` with.
+    replacement: &'a str,
+    span: Position,
+}
+
+impl From<&Span> for Position {
+    fn from(span: &Span) -> Self {
+        Position {
+            line_start: span.start.line,
+            column_start: span.start.column,
+            line_end: span.end.line,
+            column_end: span.end.column,
+            byte_start: span.byte_range.start,
+            byte_end: span.byte_range.end,
+        }
+    }
+}
+
+/// The JSON representation of an error.
+pub fn diagnostic(error: &Error) -> Diagnostic<'_> {
+    // errors which do not relate to any part of the input have no spans
+    let primary = error.span.has_position().then(|| LabelledSpan {
+        position: (&error.span).into(),
+        is_primary: true,
+        label: error.label.as_deref(),
+    });
+    let secondary = error.secondary_labels.iter().map(|label| LabelledSpan {
+        position: (&label.span).into(),
+        is_primary: false,
+        label: Some(&label.message),
+    });
+
+    Diagnostic {
+        code: error.kind.code(),
+        severity: error.severity.to_string(),
+        message: error.kind.message(),
+        file: &error.input_source_name,
+        spans: primary.into_iter().chain(secondary).collect(),
+        notes: &error.notes,
+        help: &error.help,
+        suggestions: error
+            .suggestions
+            .iter()
+            .map(|suggestion| Suggestion {
+                message: &suggestion.message,
+                replacement: &suggestion.replacement,
+                span: (&suggestion.span).into(),
+            })
+            .collect(),
+    }
+}
+
+/// Render an error as a single line of JSON (without a trailing newline).
+pub fn render(error: &Error) -> String {
+    serde_json::to_string(&diagnostic(error)).expect("diagnostics can be serialised")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use insta::assert_json_snapshot;
+
+    use super::*;
+    use crate::{check::TypeChecker, lex::Lexer, parse::Parser};
+
+    fn first_error(input: &str) -> Error {
+        let lexer = Lexer::new(Cursor::new(input), "test.kobe".to_string());
+        let mut checker = TypeChecker::new(Parser::new(lexer));
+        let result = checker
+            .parser_mut()
+            .parse_all()
+            .and_then(|functions| checker.check(functions));
+        result.unwrap_err().remove(0)
+    }
+
+    #[test]
+    fn lexical_error() {
+        assert_json_snapshot!(diagnostic(&first_error(
+            "fn f()\nlet x = 5000000000i32\nend"
+        )));
+    }
+
+    #[test]
+    fn syntax_error() {
+        assert_json_snapshot!(diagnostic(&first_error("fn f()\nwhile true do\n")));
+    }
+
+    #[test]
+    fn type_error() {
+        assert_json_snapshot!(diagnostic(&first_error("fn f(x: int)\nx = 1.5\nend")));
+        assert_json_snapshot!(diagnostic(&first_error("fn f()\nreturn 'c'\nend")));
+    }
+
+    #[test]
+    fn warning() {
+        let lexer = Lexer::new(
+            Cursor::new("fn f()\nlet x = 1\nend"),
+            "test.kobe".to_string(),
+        );
+        let mut checker = TypeChecker::new(Parser::new(lexer));
+        let functions = checker.parser_mut().parse_all().unwrap();
+        let (_, warnings) = checker.check(functions).unwrap();
+        assert_json_snapshot!(diagnostic(&warnings[0]));
+    }
+
+    #[test]
+    fn without_position() {
+        let io_error = std::io::Error::from(std::io::ErrorKind::NotFound);
+        let rendered = render(&Error::io(io_error, "missing.kobe".to_string()));
+        assert!(rendered.starts_with(r#"{"code":"K0001","severity":"error","#));
+        assert!(rendered.contains(r#""file":"missing.kobe","spans":[],"#));
+    }
+
+    #[test]
+    fn single_line() {
+        let rendered = render(&first_error("fn f()\nx\nend"));
+        assert!(!rendered.contains('\n'));
+        assert!(rendered.starts_with(r#"{"code":"K0302","severity":"error","#));
+    }
+}
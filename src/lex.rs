@@ -1,19 +1,99 @@
-use std::io::{BufRead, BufReader, Read};
+use std::{
+    collections::VecDeque,
+    io::{self, BufReader, Read},
+    time::{Duration, Instant},
+};
 
 use crate::{
     error::{Error, ErrorKind, LexicalErrorKind, Result},
-    token::{Token, TokenType},
+    span::{Pos, Span},
+    token::{Literal, Token, TokenType},
 };
 
-/// The Kobe lexer. Takes an input stream (anything [`Read`]) and lazily produces
-/// tokens through its implementation of [`Iterator`].
+/// The unit in which the lexer counts columns (i.e., the `column` of a [`Pos`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColumnUnit {
+    /// Unicode scalar values (i.e., Rust `char`s).
+    #[default]
+    Chars,
+    /// UTF-16 code units, as used by many editors and the Language Server
+    /// Protocol. Characters outside the Basic Multilingual Plane count as two
+    /// columns.
+    Utf16,
+}
+
+impl ColumnUnit {
+    fn width(self, c: char) -> usize {
+        match self {
+            ColumnUnit::Chars => 1,
+            ColumnUnit::Utf16 => c.len_utf16(),
+        }
+    }
+
+    /// Convert a column on the given line from this unit to a count of
+    /// characters. Columns beyond the end of the line are one character wide.
+    pub fn char_column(self, line: &str, column: usize) -> usize {
+        let mut units = 1;
+        let mut chars = 1;
+        for c in line.chars() {
+            if units >= column {
+                break;
+            }
+            units += self.width(c);
+            chars += 1;
+        }
+        chars + column.saturating_sub(units)
+    }
+}
+
+/// The Kobe lexer. Takes an input stream (anything [`Read`]) of UTF-8 encoded
+/// text and lazily produces tokens through its implementation of [`Iterator`].
 pub struct Lexer<R> {
     reader: BufReader<R>,
     input_source_name: String,
     line_number: usize,
     char_number: usize,
-    peeked_char: Option<char>,
+    column_unit: ColumnUnit,
+    /// Position of the last character consumed. Unlike `line_number` and
+    /// `char_number`, a newline is positioned at the end of the line it ends.
+    last_position: Pos,
+    /// Number of bytes of input consumed.
+    byte_offset: usize,
+    /// Position and byte offset of the first character of the token currently
+    /// being lexed.
+    token_start: (Pos, usize),
+    /// Characters which have been read but not consumed, along with the number
+    /// of bytes from which each was decoded.
+    peeked_chars: VecDeque<(char, usize)>,
+    /// A byte which was read while decoding a character but turned out not to
+    /// be part of it.
+    peeked_byte: Option<u8>,
+    /// Number of bytes read from the input stream (including any peeked byte).
+    bytes_read: usize,
+    /// Spans of malformed UTF-8 sequences which have been read (and replaced
+    /// with U+FFFD) but not yet reported.
+    invalid_utf8_spans: VecDeque<Span>,
     line_read_so_far: String,
+    previous_lines: Vec<String>,
+    /// Value of the literal currently being lexed (if any), to be attached
+    /// to its token.
+    literal: Option<Literal>,
+    /// The number of interpolated strings within which the lexer currently is
+    /// (i.e., how many `{` have been read in strings without their matching
+    /// `}`).
+    interpolation_depth: usize,
+    /// Whether reading from the input stream has failed, after which it is
+    /// not read from again.
+    reader_failed: bool,
+    /// The error with which reading from the input stream failed, until it is
+    /// reported.
+    read_error: Option<io::Error>,
+    /// Number of tokens produced so far.
+    token_count: usize,
+    /// Total time spent producing tokens so far.
+    elapsed: Duration,
+    /// Every token produced so far, if requested.
+    recorded_tokens: Option<Vec<Token>>,
 }
 
 impl<R: Read> Lexer<R> {
@@ -23,8 +103,107 @@ impl<R: Read> Lexer<R> {
             input_source_name,
             line_number: 1,
             char_number: 0,
-            peeked_char: None,
+            column_unit: ColumnUnit::default(),
+            last_position: Pos::default(),
+            byte_offset: 0,
+            token_start: (Pos::default(), 0),
+            peeked_chars: VecDeque::new(),
+            peeked_byte: None,
+            bytes_read: 0,
+            invalid_utf8_spans: VecDeque::new(),
             line_read_so_far: String::new(),
+            previous_lines: Vec::new(),
+            literal: None,
+            interpolation_depth: 0,
+            reader_failed: false,
+            read_error: None,
+            token_count: 0,
+            elapsed: Duration::ZERO,
+            recorded_tokens: None,
+        }
+    }
+
+    /// Set the unit in which columns are counted for the positions of tokens
+    /// and errors.
+    pub fn with_column_unit(mut self, column_unit: ColumnUnit) -> Self {
+        self.column_unit = column_unit;
+        self
+    }
+
+    /// Keep a copy of every token produced, to be taken with
+    /// [`Lexer::take_tokens`].
+    pub fn recording_tokens(mut self) -> Self {
+        self.recorded_tokens = Some(Vec::new());
+        self
+    }
+
+    /// Take the tokens recorded so far (if recording).
+    pub fn take_tokens(&mut self) -> Vec<Token> {
+        self.recorded_tokens
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// The number of tokens produced so far and the time spent producing
+    /// them (including any errors).
+    pub fn stats(&self) -> (usize, Duration) {
+        (self.token_count, self.elapsed)
+    }
+
+    /// Get the full text of the line at the given line number (starting from
+    /// 1). Reads ahead in the input stream as necessary but does not consume
+    /// any characters.
+    pub fn source_line(&mut self, line_number: usize) -> String {
+        while self.previous_lines.len() < line_number {
+            match self.read_char() {
+                Some(read) => self.peeked_chars.push_back(read),
+                None => return self.line_read_so_far.trim_end().to_string(),
+            }
+        }
+        self.previous_lines[line_number - 1].clone()
+    }
+
+    /// Create an error of the given kind covering the specified span of the
+    /// input.
+    pub fn error_at(&mut self, kind: ErrorKind, span: Span) -> Error {
+        self.with_source(Error::new(kind, span))
+    }
+
+    /// Attach the name of the input and the lines of input covered by the
+    /// error's spans to an error, so that it can be rendered.
+    pub fn with_source(&mut self, mut error: Error) -> Error {
+        error.input_source_name = self.input_source_name.clone();
+        error.column_unit = self.column_unit;
+
+        let line_numbers: Vec<_> = error
+            .spans()
+            .flat_map(|span| span.start.line..=span.end.line)
+            .collect();
+        for line_number in line_numbers {
+            let line = self.source_line(line_number);
+            error.source_lines.insert(line_number, line);
+        }
+
+        error
+    }
+
+    /// The position and byte offset of the next character to be consumed.
+    fn next_position(&self) -> (Pos, usize) {
+        let pos = Pos {
+            line: self.line_number,
+            column: self.char_number + 1,
+        };
+        (pos, self.byte_offset)
+    }
+
+    /// The span from the given position (as from [`Lexer::next_position`])
+    /// up to and including the last character consumed.
+    fn span_from(&self, (start, start_byte): (Pos, usize)) -> Span {
+        Span {
+            start,
+            end: self.last_position.max(start),
+            byte_range: start_byte..self.byte_offset,
         }
     }
 
@@ -32,12 +211,10 @@ impl<R: Read> Lexer<R> {
     /// the end of input stream. This function will track the position (line and
     /// character numbers) in the input.
     fn next_char(&mut self, lexeme: &mut String) -> Option<char> {
-        let c = self.next_char_no_position_tracking();
-        if let Some(c) = c {
-            self.update_position_tracking(c);
-            lexeme.push(c);
-        }
-        c
+        let (c, len) = self.next_char_no_position_tracking()?;
+        self.update_position_tracking(c, len);
+        lexeme.push(c);
+        Some(c)
     }
 
     /// Peek the next character in the input and, if it is equal to the given
@@ -49,60 +226,183 @@ impl<R: Read> Lexer<R> {
     /// Peek the next character and consume it if the given predicate function
     /// returns `true`.
     fn next_char_if(&mut self, lexeme: &mut String, f: impl Fn(char) -> bool) -> Option<char> {
-        if let Some(c) = self.next_char_no_position_tracking() {
+        if let Some((c, len)) = self.next_char_no_position_tracking() {
             if f(c) {
-                self.update_position_tracking(c);
+                self.update_position_tracking(c, len);
                 lexeme.push(c);
                 return Some(c);
             } else {
-                self.peeked_char = Some(c);
+                self.peeked_chars.push_front((c, len));
             }
         }
         None
     }
 
-    fn next_char_no_position_tracking(&mut self) -> Option<char> {
-        if self.peeked_char.is_some() {
-            self.peeked_char.take()
+    /// Peek the character `n` places ahead in the input (with 0 being the next
+    /// character) without consuming anything.
+    fn peek_char(&mut self, n: usize) -> Option<char> {
+        while self.peeked_chars.len() <= n {
+            let read = self.read_char()?;
+            self.peeked_chars.push_back(read);
+        }
+        Some(self.peeked_chars[n].0)
+    }
+
+    fn next_char_no_position_tracking(&mut self) -> Option<(char, usize)> {
+        self.peeked_chars.pop_front().or_else(|| self.read_char())
+    }
+
+    /// Read a character directly from the input stream, keeping a record of
+    /// each complete line read so that it can be included in error messages.
+    /// Malformed UTF-8 is replaced with U+FFFD and its span recorded so that
+    /// an error can be produced once it is consumed. Also returns the number
+    /// of bytes which were decoded.
+    fn read_char(&mut self) -> Option<(char, usize)> {
+        let start_byte = self.bytes_read;
+        let decoded = self.decode_char()?;
+        let len = self.bytes_read - start_byte;
+
+        let c = match decoded {
+            Some(c) => c,
+            None => {
+                self.line_read_so_far.push(char::REPLACEMENT_CHARACTER);
+                let pos = Pos {
+                    line: self.previous_lines.len() + 1,
+                    column: self
+                        .line_read_so_far
+                        .chars()
+                        .map(|c| self.column_unit.width(c))
+                        .sum(),
+                };
+                self.invalid_utf8_spans.push_back(Span {
+                    start: pos,
+                    end: pos,
+                    byte_range: start_byte..self.bytes_read,
+                });
+                return Some((char::REPLACEMENT_CHARACTER, len));
+            }
+        };
+
+        if c == '\n' {
+            let line = std::mem::take(&mut self.line_read_so_far);
+            self.previous_lines.push(line.trim_end().to_string());
         } else {
-            let mut buf = [0];
-            let bytes_read = self.reader.read(&mut buf).unwrap();
-            let c = buf[0] as char;
-
-            (bytes_read > 0).then(|| {
-                self.line_read_so_far.push(c);
-                if c == '\n' {
-                    self.line_read_so_far.clear();
-                }
+            self.line_read_so_far.push(c);
+        }
 
-                c
-            })
+        Some((c, len))
+    }
+
+    /// Decode a single UTF-8 encoded character from the input stream. Returns
+    /// `Some(None)` if the next bytes are not valid UTF-8, in which case as
+    /// few bytes as possible are consumed.
+    fn decode_char(&mut self) -> Option<Option<char>> {
+        let first = self.read_byte()?;
+
+        let len = match first {
+            0x00..=0x7F => return Some(Some(first as char)),
+            0xC2..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF4 => 4,
+            _ => return Some(None),
+        };
+
+        let mut bytes = vec![first];
+        while bytes.len() < len {
+            match self.read_byte() {
+                Some(byte) if byte & 0xC0 == 0x80 => bytes.push(byte),
+                Some(byte) => {
+                    self.peeked_byte = Some(byte);
+                    self.bytes_read -= 1;
+                    return Some(None);
+                }
+                None => return Some(None),
+            }
         }
+
+        // rejects overlong encodings and surrogates
+        Some(
+            std::str::from_utf8(&bytes)
+                .ok()
+                .and_then(|s| s.chars().next()),
+        )
+    }
+
+    /// Read a single byte from the input stream. If reading fails, the error
+    /// is recorded to be reported by the iterator and the input is treated as
+    /// having ended, so that nothing more is read from it.
+    fn read_byte(&mut self) -> Option<u8> {
+        let byte = match self.peeked_byte.take() {
+            Some(byte) => byte,
+            None => {
+                if self.reader_failed {
+                    return None;
+                }
+
+                let mut buf = [0];
+                let bytes_read = loop {
+                    match self.reader.read(&mut buf) {
+                        Ok(bytes_read) => break bytes_read,
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                        Err(e) => {
+                            self.reader_failed = true;
+                            self.read_error = Some(e);
+                            return None;
+                        }
+                    }
+                };
+                if bytes_read == 0 {
+                    return None;
+                }
+                buf[0]
+            }
+        };
+
+        self.bytes_read += 1;
+        Some(byte)
     }
 
-    fn update_position_tracking(&mut self, c: char) {
-        self.char_number += 1;
+    fn update_position_tracking(&mut self, c: char, len: usize) {
+        self.byte_offset += len;
+        self.char_number += self.column_unit.width(c);
+        self.last_position = Pos {
+            line: self.line_number,
+            column: self.char_number,
+        };
         if c == '\n' {
             self.line_number += 1;
             self.char_number = 0;
-            self.line_read_so_far.clear();
         }
     }
 
     fn handle_number_literal(&mut self, lexeme: &mut String) -> Result<TokenType> {
-        let mut tt = TokenType::IntLiteral;
-
-        while let Some(c) = self.next_char_if(lexeme, is_number_char) {
-            if c == '.' {
-                if tt == TokenType::IntLiteral {
-                    tt = TokenType::FloatLiteral;
-                } else {
-                    return Err(self.new_error(LexicalErrorKind::InvalidFloatLiteral));
-                }
+        let has_radix_prefix = lexeme == "0" && matches!(self.peek_char(0), Some('x' | 'b' | 'o'));
+
+        // consume everything that could be part of the literal and only then
+        // check that it is valid, so that an error covers the whole literal
+        loop {
+            let is_part_of_literal = match self.peek_char(0) {
+                // two dots following an integer form a range (e.g., `0..n`)
+                // rather than a decimal point
+                Some('.') => !has_radix_prefix && self.peek_char(1) != Some('.'),
+                // sign of an exponent
+                Some('+' | '-') => !has_radix_prefix && lexeme.ends_with(['e', 'E']),
+                Some(c) => is_ident_char(c),
+                None => false,
+            };
+            if !is_part_of_literal {
+                break;
             }
+            self.next_char(lexeme);
         }
 
-        Ok(tt)
+        let value = number_literal_value(lexeme).map_err(|kind| self.new_error(kind))?;
+        let tok_type = match value {
+            Literal::Float(_) => TokenType::FloatLiteral,
+            _ => TokenType::IntLiteral,
+        };
+        self.literal = Some(value);
+        Ok(tok_type)
     }
 
     fn handle_ident_or_keyword(&mut self, lexeme: &mut String) -> TokenType {
@@ -112,82 +412,311 @@ impl<R: Read> Lexer<R> {
             "do" => TokenType::DoKeyword,
             "end" => TokenType::EndKeyword,
             "for" => TokenType::ForKeyword,
+            "in" => TokenType::InKeyword,
             "while" => TokenType::WhileKeyword,
             "if" => TokenType::IfKeyword,
             "then" => TokenType::ThenKeyword,
             "else" => TokenType::ElseKeyword,
             "fn" => TokenType::FnKeyword,
+            "pub" => TokenType::PubKeyword,
             "return" => TokenType::ReturnKeyword,
             "and" => TokenType::AndKeyword,
             "or" => TokenType::OrKeyword,
             "let" => TokenType::LetKeyword,
+            "true" => TokenType::TrueKeyword,
+            "false" => TokenType::FalseKeyword,
             _ => TokenType::Identifier,
         }
     }
 
     fn handle_char_literal(&mut self, lexeme: &mut String) -> Result<TokenType> {
-        self.handle_character_in_literal(lexeme, LexicalErrorKind::InvalidCharLiteral, |c| {
-            !matches!(c, '\'' | '\n')
-        })?;
+        let c =
+            self.handle_character_in_literal(lexeme, LexicalErrorKind::InvalidCharLiteral, |c| {
+                !matches!(c, '\'' | '\n')
+            })?;
 
         if self.next_char_if_equals(lexeme, '\'') {
+            self.literal = Some(Literal::Char(c));
             Ok(TokenType::CharLiteral)
         } else {
             Err(self.new_error(LexicalErrorKind::InvalidCharLiteral))
         }
     }
 
-    fn handle_string_literal(&mut self, lexeme: &mut String) -> Result<TokenType> {
-        while !self.next_char_if_equals(lexeme, '"') {
-            self.handle_character_in_literal(
+    /// Lex a string literal up until either its closing `"` or the `{` which
+    /// begins an interpolated expression. `continued` is set when this is the
+    /// rest of an interpolated string following the `}` which closes an
+    /// interpolated expression. Each segment of an interpolated string is a
+    /// separate token, between which are the tokens of the interpolated
+    /// expressions.
+    fn handle_string_literal(&mut self, lexeme: &mut String, continued: bool) -> Result<TokenType> {
+        let mut value = String::new();
+
+        let tok_type = loop {
+            if self.next_char_if_equals(lexeme, '"') {
+                if continued {
+                    self.interpolation_depth -= 1;
+                    break TokenType::StringEnd;
+                } else {
+                    break TokenType::StringLiteral;
+                }
+            }
+
+            if self.next_char_if_equals(lexeme, '{') {
+                if continued {
+                    break TokenType::StringMiddle;
+                } else {
+                    self.interpolation_depth += 1;
+                    break TokenType::StringStart;
+                }
+            }
+
+            value.push(self.handle_character_in_literal(
                 lexeme,
                 LexicalErrorKind::InvalidStringLiteral,
                 |_| true,
-            )?;
+            )?);
+        };
+
+        self.literal = Some(Literal::String(value));
+        Ok(tok_type)
+    }
+
+    /// Lex a raw string (e.g., `r"C:\path"`), the `r` of which has just been
+    /// consumed. Raw strings contain no escape codes or interpolation. Any
+    /// number of `#` may surround the quotes so that the string itself can
+    /// contain quotes (e.g., `r#"say "hi""#`).
+    fn handle_raw_string_literal(&mut self, lexeme: &mut String) -> Result<TokenType> {
+        let mut hashes = 0;
+        while self.next_char_if_equals(lexeme, '#') {
+            hashes += 1;
+        }
+        if !self.next_char_if_equals(lexeme, '"') {
+            return Err(self.new_error(LexicalErrorKind::InvalidStringLiteral));
         }
 
+        let mut value = String::new();
+        loop {
+            match self.next_char(lexeme) {
+                Some('"') if (0..hashes).all(|i| self.peek_char(i) == Some('#')) => {
+                    for _ in 0..hashes {
+                        self.next_char(lexeme);
+                    }
+                    break;
+                }
+                Some(c) => value.push(c),
+                None => return Err(self.new_error(LexicalErrorKind::InvalidStringLiteral)),
+            }
+        }
+
+        self.literal = Some(Literal::String(value));
         Ok(TokenType::StringLiteral)
     }
 
+    /// Consume a single (possibly escaped) character in a char or string
+    /// literal, returning the character which it represents.
     fn handle_character_in_literal(
         &mut self,
         lexeme: &mut String,
         invalid_literal_error: LexicalErrorKind,
         is_valid_character_in_literal: impl Fn(char) -> bool,
-    ) -> Result<()> {
+    ) -> Result<char> {
+        let start = self.next_position();
         if self.next_char_if_equals(lexeme, '\\') {
-            if self.next_char_if(lexeme, is_escape_code_char).is_none() {
-                return Err(self.new_error(LexicalErrorKind::InvalidEscapeCode));
+            return self.handle_escape(lexeme, start);
+        }
+
+        match self.next_char_if(lexeme, is_valid_character_in_literal) {
+            Some(c) => Ok(c),
+            None => Err(self.new_error(invalid_literal_error)),
+        }
+    }
+
+    /// Consume the remainder of an escape sequence, the `\` of which has just
+    /// been consumed from the given position. Any error covers the escape
+    /// sequence from the `\` onwards.
+    fn handle_escape(&mut self, lexeme: &mut String, start: (Pos, usize)) -> Result<char> {
+        let kind = match self.next_char_if(lexeme, |c| c != '\n') {
+            Some('n') => return Ok('\n'),
+            Some('t') => return Ok('\t'),
+            Some('r') => return Ok('\r'),
+            Some('0') => return Ok('\0'),
+            Some(c @ ('\\' | '\'' | '"' | '{' | '}')) => return Ok(c),
+            Some('x') => match self.handle_hex_escape(lexeme) {
+                Some(c) => return Ok(c),
+                None => LexicalErrorKind::InvalidHexEscape,
+            },
+            Some('u') => match self.handle_unicode_escape(lexeme) {
+                Some(c) => return Ok(c),
+                None => LexicalErrorKind::InvalidUnicodeEscape,
+            },
+            _ => LexicalErrorKind::InvalidEscapeCode,
+        };
+
+        let span = self.span_from(start);
+        Err(self.new_error_at(kind, span))
+    }
+
+    /// `\xNN`, where `NN` is exactly two hex digits no greater than `7F`.
+    fn handle_hex_escape(&mut self, lexeme: &mut String) -> Option<char> {
+        let mut value = 0;
+        for _ in 0..2 {
+            let digit = self.next_char_if(lexeme, |c| c.is_ascii_hexdigit())?;
+            value = value * 16 + digit.to_digit(16)?;
+        }
+
+        if value <= 0x7F {
+            char::from_u32(value)
+        } else {
+            None
+        }
+    }
+
+    /// `\u{XXXX}`, where `XXXX` is between one and six hex digits giving a
+    /// Unicode scalar value (i.e., not a surrogate).
+    fn handle_unicode_escape(&mut self, lexeme: &mut String) -> Option<char> {
+        if !self.next_char_if_equals(lexeme, '{') {
+            return None;
+        }
+
+        let mut digits = String::new();
+        while let Some(digit) = self.next_char_if(lexeme, |c| c.is_ascii_hexdigit()) {
+            digits.push(digit);
+        }
+
+        if !self.next_char_if_equals(lexeme, '}') || !(1..=6).contains(&digits.len()) {
+            return None;
+        }
+
+        char::from_u32(u32::from_str_radix(&digits, 16).ok()?)
+    }
+
+    /// Skip the remainder of a comment, the opening `--` of which has just
+    /// been consumed. Block comments (`--[[ ... ]]`) may be nested, while any
+    /// other comment continues until the end of the line.
+    fn skip_comment(&mut self) -> Result<()> {
+        let mut skipped = String::new();
+
+        if self.peek_char(0) != Some('[') || self.peek_char(1) != Some('[') {
+            self.skip_line();
+            return Ok(());
+        }
+
+        self.next_char(&mut skipped);
+        self.next_char(&mut skipped);
+        // the opening delimiter is the start of the token
+        let opened_by = self.span_from(self.token_start);
+
+        let mut depth = 1;
+        let mut nested = false;
+        while depth > 0 {
+            match self.next_char(&mut skipped) {
+                Some(']') if self.next_char_if_equals(&mut skipped, ']') => depth -= 1,
+                Some('-')
+                    if self.peek_char(0) == Some('-')
+                        && self.peek_char(1) == Some('[')
+                        && self.peek_char(2) == Some('[') =>
+                {
+                    for _ in 0..3 {
+                        self.next_char(&mut skipped);
+                    }
+                    depth += 1;
+                    nested = true;
+                }
+                Some(_) => {}
+                None => {
+                    let kind = ErrorKind::Lexical(LexicalErrorKind::UnterminatedComment);
+                    let mut error = Error::new(kind, opened_by).with_label("comment opened here");
+                    if nested {
+                        error = error
+                            .with_note("block comments nest, so each '--[[' needs its own ']]'");
+                    }
+                    return Err(self.with_source(error));
+                }
             }
-        } else if self
-            .next_char_if(lexeme, is_valid_character_in_literal)
-            .is_none()
-        {
-            return Err(self.new_error(invalid_literal_error));
         }
+
         Ok(())
     }
 
+    /// Create an error covering the token consumed so far.
     fn new_error(&mut self, kind: LexicalErrorKind) -> Error {
-        // get remainder of the current line
-        let _ = self.reader.read_line(&mut self.line_read_so_far);
+        let span = self.span_from(self.token_start);
+        self.new_error_at(kind, span)
+    }
 
-        Error {
-            kind: ErrorKind::Lexical(kind),
-            line_number: self.line_number,
-            char_number: self.char_number,
-            line: self.line_read_so_far.trim_end().to_string(),
-            input_source_name: self.input_source_name.clone(),
+    /// Create an error covering the given span and skip the rest of the line,
+    /// abandoning any interpolated strings in progress.
+    fn new_error_at(&mut self, kind: LexicalErrorKind, span: Span) -> Error {
+        let error = with_hints(Error::new(ErrorKind::Lexical(kind), span));
+        let error = self.with_source(error);
+        self.skip_line();
+        self.interpolation_depth = 0;
+        error
+    }
+
+    /// Skip the remainder of the current line so that lexing can resume from a
+    /// sensible point after an error.
+    fn skip_line(&mut self) {
+        let mut skipped = String::new();
+        while self.next_char_if(&mut skipped, |c| c != '\n').is_some() {}
+    }
+
+    /// Produce an error for the first malformed UTF-8 sequence to have been
+    /// consumed (if any). As with any other error, the rest of the line is
+    /// skipped, so later malformed sequences on the same line are not
+    /// reported.
+    fn invalid_utf8_error(&mut self) -> Option<Error> {
+        let span = self.invalid_utf8_spans.front()?.clone();
+        if span.byte_range.end > self.byte_offset {
+            return None;
         }
+
+        self.skip_line();
+        while self
+            .invalid_utf8_spans
+            .front()
+            .is_some_and(|span| span.byte_range.end <= self.byte_offset)
+        {
+            self.invalid_utf8_spans.pop_front();
+        }
+
+        let kind = ErrorKind::Lexical(LexicalErrorKind::InvalidUtf8);
+        let error = Error::new(kind, span).with_note("source files must be encoded as UTF-8");
+        Some(self.with_source(error))
     }
-}
 
-impl<R: Read> Iterator for Lexer<R> {
-    type Item = Result<Token>;
+    /// Produce an error for a failure to read from the input stream, if one
+    /// has occurred and not yet been reported. The error points just past the
+    /// last character which was read, unless nothing could be read at all.
+    fn io_error(&mut self) -> Option<Error> {
+        let error = self.read_error.take()?;
+        if self.bytes_read == 0 {
+            return Some(Error::io(error, self.input_source_name.clone()));
+        }
 
-    fn next(&mut self) -> Option<Self::Item> {
+        let pos = Pos {
+            line: self.previous_lines.len() + 1,
+            column: self
+                .line_read_so_far
+                .chars()
+                .map(|c| self.column_unit.width(c))
+                .sum::<usize>()
+                + 1,
+        };
+        let span = Span {
+            start: pos,
+            end: pos,
+            byte_range: self.bytes_read..self.bytes_read,
+        };
+        let error = Error::new(error.into(), span).with_label("input could not be read past here");
+        Some(self.with_source(error))
+    }
+
+    fn lex_token(&mut self) -> Option<Result<Token>> {
         let mut lexeme = String::new();
 
+        self.token_start = self.next_position();
         let c = self.next_char(&mut lexeme)?;
 
         let tok_type = match c {
@@ -198,6 +727,8 @@ impl<R: Read> Iterator for Lexer<R> {
             '[' => Ok(TokenType::OpenSquare),
             ']' => Ok(TokenType::CloseSquare),
 
+            '.' if self.next_char_if_equals(&mut lexeme, '.') => Ok(TokenType::DotDot),
+
             '+' => Ok(if self.next_char_if_equals(&mut lexeme, '=') {
                 TokenType::PlusAssign
             } else {
@@ -216,6 +747,21 @@ impl<R: Read> Iterator for Lexer<R> {
                 TokenType::Divide
             }),
 
+            // a doc comment starts with exactly three dashes, so that lines of
+            // dashes used as separators remain ordinary comments
+            '-' if self.peek_char(0) == Some('-')
+                && self.peek_char(1) == Some('-')
+                && self.peek_char(2) != Some('-') =>
+            {
+                while self.next_char_if(&mut lexeme, |c| c != '\n').is_some() {}
+                Ok(TokenType::DocComment)
+            }
+
+            '-' if self.next_char_if_equals(&mut lexeme, '-') => match self.skip_comment() {
+                Ok(()) => return self.lex_token(),
+                Err(e) => Err(e),
+            },
+
             '-' => Ok(if self.next_char_if_equals(&mut lexeme, '>') {
                 TokenType::Arrow
             } else if self.next_char_if_equals(&mut lexeme, '=') {
@@ -260,37 +806,227 @@ impl<R: Read> Iterator for Lexer<R> {
             }),
 
             '0'..='9' => self.handle_number_literal(&mut lexeme),
+            '.' if self.peek_char(0).is_some_and(|c| c.is_ascii_digit()) => {
+                self.handle_number_literal(&mut lexeme)
+            }
+
+            'r' if matches!(self.peek_char(0), Some('"' | '#')) => {
+                self.handle_raw_string_literal(&mut lexeme)
+            }
 
             'a'..='z' | 'A'..='Z' | '_' => Ok(self.handle_ident_or_keyword(&mut lexeme)),
 
             '\'' => self.handle_char_literal(&mut lexeme),
 
-            '"' => self.handle_string_literal(&mut lexeme),
+            '"' => self.handle_string_literal(&mut lexeme, false),
+            '}' if self.interpolation_depth > 0 => self.handle_string_literal(&mut lexeme, true),
 
-            _ if c.is_whitespace() => return self.next(),
+            _ if c.is_whitespace() => return self.lex_token(),
 
             _ => Err(self.new_error(LexicalErrorKind::UnexpectedCharacter)),
         };
 
+        let value = self.literal.take();
+
+        if let Some(error) = self.invalid_utf8_error() {
+            return Some(Err(error));
+        }
+
         Some(tok_type.map(|tok_type| Token {
             tok_type,
             lexeme,
-            line_number: self.line_number,
-            char_number: self.char_number,
+            value,
+            span: self.span_from(self.token_start),
         }))
     }
 }
 
-fn is_ident_char(c: char) -> bool {
-    matches!(c, 'a'..='z' | 'A'..='Z' | '0'..='9' | '_')
+impl<R: Read> Iterator for Lexer<R> {
+    type Item = Result<Token>;
+
+    /// Produce the next token, or an error if the input is malformed. If the
+    /// input stream fails, an error is produced in place of whatever token was
+    /// being read and the lexer then behaves as though the input had ended.
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = Instant::now();
+        let token = self.lex_token();
+        let token = match self.io_error() {
+            Some(error) => Some(Err(error)),
+            None => token,
+        };
+
+        if let Some(Ok(token)) = &token {
+            self.token_count += 1;
+            if let Some(recorded) = &mut self.recorded_tokens {
+                recorded.push(token.clone());
+            }
+        }
+        self.elapsed += start.elapsed();
+        token
+    }
+}
+
+/// Determine the value of a number literal, as consumed by the lexer. An
+/// integer may have a radix prefix (`0x`, `0b` or `0o`), while a decimal
+/// number may have a fractional part (with at least one digit either side of
+/// the decimal point, or just after it) and/or an exponent, either of which
+/// makes it a float. Any number may contain `_` separators after its first
+/// digit and end with a suffix giving its type: `i64` or `i32` for integers,
+/// and `f64` or `f32` for decimal numbers (making them floats).
+///
+/// The suffixes do not give literals distinct types: every integer is an
+/// `int` (64 bits) and every float a `float` (64 bits). The 32-bit suffixes
+/// only check that the value is in range, except that an `f32` literal is also
+/// rounded to single precision.
+///
+/// An integer one greater than the largest value of its type is permitted, so
+/// that the smallest value can be written by negating it (see
+/// [`negation_only_type`]).
+fn number_literal_value(lexeme: &str) -> std::result::Result<Literal, LexicalErrorKind> {
+    let (radix, rest) = match lexeme.get(..2) {
+        Some("0x") => (16, &lexeme[2..]),
+        Some("0b") => (2, &lexeme[2..]),
+        Some("0o") => (8, &lexeme[2..]),
+        _ => (10, lexeme),
+    };
+
+    let skip_digits = |from: usize| {
+        let is_digit =
+            |c: char| c == '_' || (radix == 16 && c.is_ascii_hexdigit()) || c.is_ascii_digit();
+        rest[from..]
+            .find(|c| !is_digit(c))
+            .map_or(rest.len(), |i| from + i)
+    };
+    let has_digits = |s: &str| s.chars().any(|c| c.is_ascii_hexdigit());
+
+    let mut end = skip_digits(0);
+    let mut is_float = false;
+
+    if radix == 10 && rest[end..].starts_with('.') {
+        is_float = true;
+        let start = end + 1;
+        end = skip_digits(start);
+        if !has_digits(&rest[start..end]) {
+            return Err(LexicalErrorKind::InvalidFloatLiteral);
+        }
+    }
+
+    if radix == 10 && rest[end..].starts_with(['e', 'E']) {
+        is_float = true;
+        let mut start = end + 1;
+        if rest[start..].starts_with(['+', '-']) {
+            start += 1;
+        }
+        end = skip_digits(start);
+        if !has_digits(&rest[start..end]) {
+            return Err(LexicalErrorKind::InvalidFloatLiteral);
+        }
+    }
+
+    let (body, suffix) = rest.split_at(end);
+    if suffix.starts_with('.') {
+        return Err(LexicalErrorKind::InvalidFloatLiteral);
+    }
+
+    if let Some(digit) = body
+        .chars()
+        .find(|c| c.is_ascii_digit() && !c.is_digit(radix))
+    {
+        return Err(LexicalErrorKind::InvalidDigit { digit, radix });
+    }
+
+    let digits: String = body.chars().filter(|&c| c != '_').collect();
+
+    match suffix {
+        "" | "i64" | "i32" if !is_float => {
+            if digits.is_empty() {
+                return Err(LexicalErrorKind::MissingDigits);
+            }
+
+            let magnitude = u64::from_str_radix(&digits, radix)
+                .ok()
+                .filter(|&magnitude| magnitude <= i64::MIN.unsigned_abs())
+                .ok_or(LexicalErrorKind::NumberLiteralOutOfRange("i64"))?;
+            if suffix == "i32" && magnitude > i32::MIN.unsigned_abs() as u64 {
+                return Err(LexicalErrorKind::NumberLiteralOutOfRange("i32"));
+            }
+
+            // the magnitude of i64::MIN wraps around to i64::MIN itself, which
+            // is what it must be negated to anyway
+            Ok(Literal::Int(magnitude as i64))
+        }
+
+        "" | "f64" | "f32" if radix == 10 => {
+            let value: f64 = digits.parse().expect("float literal digits are valid");
+            if value.is_infinite() {
+                return Err(LexicalErrorKind::NumberLiteralOutOfRange("f64"));
+            }
+
+            if suffix == "f32" {
+                let value = value as f32;
+                if value.is_infinite() {
+                    return Err(LexicalErrorKind::NumberLiteralOutOfRange("f32"));
+                }
+                return Ok(Literal::Float(value as f64));
+            }
+
+            Ok(Literal::Float(value))
+        }
+
+        _ => Err(LexicalErrorKind::InvalidNumberSuffix(suffix.to_string())),
+    }
 }
 
-fn is_number_char(c: char) -> bool {
-    matches!(c, '0'..='9' | '.')
+/// The type (`"i64"` or `"i32"`) of an integer literal token if its magnitude
+/// is one greater than the largest value of that type (e.g.,
+/// `9223372036854775808`). Such literals are only valid when negated, which the
+/// parser checks.
+pub fn negation_only_type(token: &Token) -> Option<&'static str> {
+    match token.value {
+        Some(Literal::Int(i64::MIN)) => Some("i64"),
+        Some(Literal::Int(value))
+            if token.lexeme.ends_with("i32") && value == i32::MIN.unsigned_abs() as i64 =>
+        {
+            Some("i32")
+        }
+        _ => None,
+    }
+}
+
+/// Add any notes or help for an error produced by the lexer which depend only
+/// on the kind of the error.
+pub fn with_hints(error: Error) -> Error {
+    let ErrorKind::Lexical(kind) = &error.kind else {
+        return error;
+    };
+
+    match kind {
+        LexicalErrorKind::InvalidEscapeCode => error.with_help(
+            "valid escapes are \\n, \\t, \\r, \\0, \\\\, \\', \\\", \\{, \\}, \\xNN and \\u{NNNN}",
+        ),
+        LexicalErrorKind::InvalidHexEscape => {
+            error.with_help("'\\x' must be followed by exactly two hex digits from 00 to 7F")
+        }
+        LexicalErrorKind::InvalidUnicodeEscape => error.with_help(
+            "'\\u' must be followed by 1 to 6 hex digits in braces giving a Unicode scalar value",
+        ),
+        LexicalErrorKind::InvalidNumberSuffix(_) => error
+            .with_help("valid suffixes are i64 and i32 for integers, and f64 and f32 for floats"),
+        LexicalErrorKind::NumberLiteralOutOfRange(ty) => {
+            let note = match *ty {
+                "i32" => format!("i32 values range from {} to {}", i32::MIN, i32::MAX),
+                "i64" => format!("i64 values range from {} to {}", i64::MIN, i64::MAX),
+                "f32" => format!("f32 values cannot exceed {:e} in magnitude", f32::MAX),
+                _ => format!("f64 values cannot exceed {:e} in magnitude", f64::MAX),
+            };
+            error.with_note(note)
+        }
+        _ => error,
+    }
 }
 
-fn is_escape_code_char(c: char) -> bool {
-    matches!(c, '\\' | 'n' | 't' | '\'' | '"' | '0')
+fn is_ident_char(c: char) -> bool {
+    matches!(c, 'a'..='z' | 'A'..='Z' | '0'..='9' | '_')
 }
 
 #[cfg(test)]
@@ -301,16 +1037,23 @@ mod tests {
 
     macro_rules! assert_token {
         ($input:literal, $type:expr, $lexeme:literal, $line_no:literal, $char_no:literal) => {
-            let expected = Token {
-                tok_type: $type,
-                lexeme: $lexeme.to_string(),
-                line_number: $line_no,
-                char_number: $char_no,
+            let cursor = Cursor::new($input);
+            let mut l = Lexer::new(cursor, "test".to_string());
+            let token = l.next().unwrap().unwrap();
+            assert_eq!((token.tok_type, token.lexeme.as_str()), ($type, $lexeme));
+            let start = Pos {
+                line: $line_no,
+                column: $char_no,
             };
+            assert_eq!(token.span.start, start);
+        };
+    }
 
+    macro_rules! assert_literal {
+        ($input:literal, $value:expr) => {
             let cursor = Cursor::new($input);
             let mut l = Lexer::new(cursor, "test".to_string());
-            assert_eq!(l.next(), Some(Ok(expected)));
+            assert_eq!(l.next().unwrap().unwrap().value, Some($value));
         };
     }
 
@@ -319,17 +1062,21 @@ mod tests {
             assert_error!($input, $kind, $line_no, $char_no, $input.trim_end())
         };
         ($input:literal, $kind:expr, $line_no:literal, $char_no:literal, $error_line:expr) => {
-            let error = Error {
-                kind: ErrorKind::Lexical($kind),
-                line_number: $line_no,
-                char_number: $char_no,
-                line: $error_line.to_string(),
-                input_source_name: "test".to_string(),
-            };
-
             let cursor = Cursor::new($input);
             let mut l = Lexer::new(cursor, "test".to_string());
-            assert_eq!(l.next(), Some(Err(error)));
+            let error = l.next().unwrap().unwrap_err();
+            let start = Pos {
+                line: $line_no,
+                column: $char_no,
+            };
+            assert_eq!(
+                (
+                    error.kind,
+                    error.span.start,
+                    error.source_lines[&error.span.start.line].clone()
+                ),
+                (ErrorKind::Lexical($kind), start, $error_line.to_string())
+            );
         };
     }
 
@@ -338,7 +1085,7 @@ mod tests {
         let input = "if (x + 2) / 3 >= foo then\nfunc(abc5, 2.5)\nend";
 
         let expected_tokens = [
-            (TokenType::IfKeyword, "if", 1, 2),
+            (TokenType::IfKeyword, "if", 1, 1),
             (TokenType::OpenBracket, "(", 1, 4),
             (TokenType::Identifier, "x", 1, 5),
             (TokenType::Plus, "+", 1, 7),
@@ -346,32 +1093,36 @@ mod tests {
             (TokenType::CloseBracket, ")", 1, 10),
             (TokenType::Divide, "/", 1, 12),
             (TokenType::IntLiteral, "3", 1, 14),
-            (TokenType::GreaterThanOrEqual, ">=", 1, 17),
-            (TokenType::Identifier, "foo", 1, 21),
-            (TokenType::ThenKeyword, "then", 1, 26),
-            (TokenType::EndStatement, "\n", 2, 0),
-            (TokenType::Identifier, "func", 2, 4),
+            (TokenType::GreaterThanOrEqual, ">=", 1, 16),
+            (TokenType::Identifier, "foo", 1, 19),
+            (TokenType::ThenKeyword, "then", 1, 23),
+            (TokenType::EndStatement, "\n", 1, 27),
+            (TokenType::Identifier, "func", 2, 1),
             (TokenType::OpenBracket, "(", 2, 5),
-            (TokenType::Identifier, "abc5", 2, 9),
+            (TokenType::Identifier, "abc5", 2, 6),
             (TokenType::Comma, ",", 2, 10),
-            (TokenType::FloatLiteral, "2.5", 2, 14),
+            (TokenType::FloatLiteral, "2.5", 2, 12),
             (TokenType::CloseBracket, ")", 2, 15),
-            (TokenType::EndStatement, "\n", 3, 0),
-            (TokenType::EndKeyword, "end", 3, 3),
+            (TokenType::EndStatement, "\n", 2, 16),
+            (TokenType::EndKeyword, "end", 3, 1),
         ];
 
         let cursor = Cursor::new(input);
         let mut lexer = Lexer::new(cursor, "test".to_string());
 
-        for (tok_type, lexeme, line_number, char_number) in expected_tokens {
-            let expected_token = Token {
-                tok_type,
-                lexeme: lexeme.to_string(),
-                line_number,
-                char_number,
+        for (tok_type, lexeme, line, column) in expected_tokens {
+            let value = match tok_type {
+                TokenType::IntLiteral => Some(Literal::Int(lexeme.parse().unwrap())),
+                TokenType::FloatLiteral => Some(Literal::Float(lexeme.parse().unwrap())),
+                _ => None,
             };
 
-            assert_eq!(lexer.next(), Some(Ok(expected_token)));
+            let token = lexer.next().unwrap().unwrap();
+            assert_eq!(
+                (token.tok_type, token.lexeme.as_str(), token.value),
+                (tok_type, lexeme, value)
+            );
+            assert_eq!(token.span.start, Pos { line, column });
         }
 
         assert!(lexer.next().is_none());
@@ -380,7 +1131,7 @@ mod tests {
     #[test]
     fn simple_tokens() {
         assert_token!("=", TokenType::Assign, "=", 1, 1);
-        assert_token!("==", TokenType::Equivalent, "==", 1, 2);
+        assert_token!("==", TokenType::Equivalent, "==", 1, 1);
         assert_token!(" :", TokenType::Colon, ":", 1, 2);
         assert_token!(", ", TokenType::Comma, ",", 1, 1);
         assert_token!("\t(", TokenType::OpenBracket, "(", 1, 2);
@@ -388,67 +1139,587 @@ mod tests {
         assert_token!(" [ ", TokenType::OpenSquare, "[", 1, 2);
         assert_token!(" ] ", TokenType::CloseSquare, "]", 1, 2);
         assert_token!("+", TokenType::Plus, "+", 1, 1);
-        assert_token!("\t+=", TokenType::PlusAssign, "+=", 1, 3);
+        assert_token!("\t+=", TokenType::PlusAssign, "+=", 1, 2);
         assert_token!("-", TokenType::Minus, "-", 1, 1);
-        assert_token!("-=\t", TokenType::MinusAssign, "-=", 1, 2);
-        assert_token!("->", TokenType::Arrow, "->", 1, 2);
+        assert_token!("-=\t", TokenType::MinusAssign, "-=", 1, 1);
+        assert_token!("->", TokenType::Arrow, "->", 1, 1);
         assert_token!("-\t>", TokenType::Minus, "-", 1, 1);
         assert_token!("\t *", TokenType::Times, "*", 1, 3);
-        assert_token!("*=", TokenType::TimesAssign, "*=", 1, 2);
+        assert_token!("*=", TokenType::TimesAssign, "*=", 1, 1);
         assert_token!("/ \t", TokenType::Divide, "/", 1, 1);
-        assert_token!("/=", TokenType::DivideAssign, "/=", 1, 2);
+        assert_token!("/=", TokenType::DivideAssign, "/=", 1, 1);
         assert_token!("<", TokenType::LessThan, "<", 1, 1);
-        assert_token!(" <= ", TokenType::LessThanOrEqual, "<=", 1, 3);
+        assert_token!(" <= ", TokenType::LessThanOrEqual, "<=", 1, 2);
         assert_token!(">", TokenType::GreaterThan, ">", 1, 1);
-        assert_token!(" >= ", TokenType::GreaterThanOrEqual, ">=", 1, 3);
+        assert_token!(" >= ", TokenType::GreaterThanOrEqual, ">=", 1, 2);
         assert_token!("!", TokenType::Not, "!", 1, 1);
-        assert_token!("!=", TokenType::NotEquivalent, "!=", 1, 2);
+        assert_token!("!=", TokenType::NotEquivalent, "!=", 1, 1);
+        assert_token!(" .. ", TokenType::DotDot, "..", 1, 2);
     }
 
     #[test]
     fn identifiers_and_keywords() {
         assert_token!("a", TokenType::Identifier, "a", 1, 1);
         assert_token!("_", TokenType::Identifier, "_", 1, 1);
-        assert_token!(" ABC_123 ", TokenType::Identifier, "ABC_123", 1, 8);
-        assert_token!("\tif", TokenType::IfKeyword, "if", 1, 3);
+        assert_token!(" ABC_123 ", TokenType::Identifier, "ABC_123", 1, 2);
+        assert_token!("\tif", TokenType::IfKeyword, "if", 1, 2);
+        assert_token!("in ", TokenType::InKeyword, "in", 1, 1);
+        assert_token!("pub fn", TokenType::PubKeyword, "pub", 1, 1);
+        assert_token!("true", TokenType::TrueKeyword, "true", 1, 1);
+        assert_token!("false", TokenType::FalseKeyword, "false", 1, 1);
+        assert_token!("int", TokenType::Identifier, "int", 1, 1);
     }
 
     #[test]
     fn number_literals() {
         assert_token!("0", TokenType::IntLiteral, "0", 1, 1);
-        assert_token!("1234", TokenType::IntLiteral, "1234", 1, 4);
-        assert_token!("1.\n", TokenType::FloatLiteral, "1.", 1, 2);
-        assert_token!(" 123.456 ", TokenType::FloatLiteral, "123.456", 1, 8);
+        assert_token!("1234", TokenType::IntLiteral, "1234", 1, 1);
+        assert_token!(" 123.456 ", TokenType::FloatLiteral, "123.456", 1, 2);
+        assert_token!(".5", TokenType::FloatLiteral, ".5", 1, 1);
+        assert_token!("0xFF_ff", TokenType::IntLiteral, "0xFF_ff", 1, 1);
+        assert_token!("0b1010", TokenType::IntLiteral, "0b1010", 1, 1);
+        assert_token!("0o17", TokenType::IntLiteral, "0o17", 1, 1);
+        assert_token!("1_000_000", TokenType::IntLiteral, "1_000_000", 1, 1);
+        assert_token!("1e-9", TokenType::FloatLiteral, "1e-9", 1, 1);
+        assert_token!("2.5E+3 ", TokenType::FloatLiteral, "2.5E+3", 1, 1);
+        assert_token!("10i32", TokenType::IntLiteral, "10i32", 1, 1);
+        assert_token!("2.0f32", TokenType::FloatLiteral, "2.0f32", 1, 1);
+        assert_token!("2f64", TokenType::FloatLiteral, "2f64", 1, 1);
+        assert_token!("0x1-1", TokenType::IntLiteral, "0x1", 1, 1);
+        assert_token!("1e5-1", TokenType::FloatLiteral, "1e5", 1, 1);
+        assert_token!("0..n", TokenType::IntLiteral, "0", 1, 1);
+        assert_token!("0x1..n", TokenType::IntLiteral, "0x1", 1, 1);
+        assert_token!("1.5..", TokenType::FloatLiteral, "1.5", 1, 1);
+
         assert_error!(".", LexicalErrorKind::UnexpectedCharacter, 1, 1);
-        assert_error!("1.2.3", LexicalErrorKind::InvalidFloatLiteral, 1, 4);
+        assert_error!("1.\n", LexicalErrorKind::InvalidFloatLiteral, 1, 1, "1.");
+        assert_error!("1.2.3", LexicalErrorKind::InvalidFloatLiteral, 1, 1);
+        assert_error!("1.e5", LexicalErrorKind::InvalidFloatLiteral, 1, 1);
+        assert_error!("1e", LexicalErrorKind::InvalidFloatLiteral, 1, 1);
+        assert_error!("1e+_", LexicalErrorKind::InvalidFloatLiteral, 1, 1);
+        assert_error!(
+            "0b102",
+            LexicalErrorKind::InvalidDigit {
+                digit: '2',
+                radix: 2
+            },
+            1,
+            1
+        );
+        assert_error!(
+            "0o8",
+            LexicalErrorKind::InvalidDigit {
+                digit: '8',
+                radix: 8
+            },
+            1,
+            1
+        );
+        assert_error!("0x", LexicalErrorKind::MissingDigits, 1, 1);
+        assert_error!("0b_i32", LexicalErrorKind::MissingDigits, 1, 1);
+        assert_error!(
+            "0xFG",
+            LexicalErrorKind::InvalidNumberSuffix("G".to_string()),
+            1,
+            1
+        );
+        assert_error!(
+            "1.5i32",
+            LexicalErrorKind::InvalidNumberSuffix("i32".to_string()),
+            1,
+            1
+        );
+        assert_error!(
+            "0b1f32",
+            LexicalErrorKind::InvalidNumberSuffix("f32".to_string()),
+            1,
+            1
+        );
+        assert_error!(
+            "123abc",
+            LexicalErrorKind::InvalidNumberSuffix("abc".to_string()),
+            1,
+            1
+        );
+        assert_error!(
+            "99999999999999999999",
+            LexicalErrorKind::NumberLiteralOutOfRange("i64"),
+            1,
+            1
+        );
+        assert_error!(
+            "9223372036854775809",
+            LexicalErrorKind::NumberLiteralOutOfRange("i64"),
+            1,
+            1
+        );
+        assert_error!(
+            "3_000_000_000i32",
+            LexicalErrorKind::NumberLiteralOutOfRange("i32"),
+            1,
+            1
+        );
+        assert_error!(
+            "1e400",
+            LexicalErrorKind::NumberLiteralOutOfRange("f64"),
+            1,
+            1
+        );
+        assert_error!(
+            "1e39f32",
+            LexicalErrorKind::NumberLiteralOutOfRange("f32"),
+            1,
+            1
+        );
+    }
+
+    #[test]
+    fn number_literal_values() {
+        let value = |lexeme| number_literal_value(lexeme).unwrap();
+
+        assert_eq!(value("0xFF_ff"), Literal::Int(0xFFFF));
+        assert_eq!(value("0b1010"), Literal::Int(10));
+        assert_eq!(value("0o17"), Literal::Int(15));
+        assert_eq!(value("1_000_000"), Literal::Int(1_000_000));
+        assert_eq!(value("0x7FFF_FFFF_FFFF_FFFF"), Literal::Int(i64::MAX));
+        assert_eq!(value("10i32"), Literal::Int(10));
+        assert_eq!(value("9223372036854775808"), Literal::Int(i64::MIN));
+        assert_eq!(value("2147483648i32"), Literal::Int(1 << 31));
+        assert_eq!(value(".5"), Literal::Float(0.5));
+        assert_eq!(value("1e-9"), Literal::Float(1e-9));
+        assert_eq!(value("1_0.2_5e1_0"), Literal::Float(10.25e10));
+        assert_eq!(value("2f64"), Literal::Float(2.0));
+        assert_eq!(value("0.1f32"), Literal::Float(0.1f32 as f64));
+
+        assert_literal!("0x10", Literal::Int(16));
+        assert_literal!("2.5e1", Literal::Float(25.0));
     }
 
     #[test]
     fn char_literals() {
-        assert_token!("'x'", TokenType::CharLiteral, "'x'", 1, 3);
-        assert_token!(" ' ' ", TokenType::CharLiteral, "' '", 1, 4);
-        assert_token!("'\\n'", TokenType::CharLiteral, "'\\n'", 1, 4);
-        assert_token!("'\\t'", TokenType::CharLiteral, "'\\t'", 1, 4);
-        assert_token!("'\\''", TokenType::CharLiteral, "'\\''", 1, 4);
-        assert_token!("'\"'", TokenType::CharLiteral, "'\"'", 1, 3);
+        assert_token!("'x'", TokenType::CharLiteral, "'x'", 1, 1);
+        assert_token!(" ' ' ", TokenType::CharLiteral, "' '", 1, 2);
+        assert_token!("'\\n'", TokenType::CharLiteral, "'\\n'", 1, 1);
+        assert_token!("'\\t'", TokenType::CharLiteral, "'\\t'", 1, 1);
+        assert_token!("'\\''", TokenType::CharLiteral, "'\\''", 1, 1);
+        assert_token!("'\"'", TokenType::CharLiteral, "'\"'", 1, 1);
+        assert_literal!("'\\n'", Literal::Char('\n'));
+        assert_literal!("'\\''", Literal::Char('\''));
+        assert_literal!("'é'", Literal::Char('é'));
         assert_error!("'\\j'", LexicalErrorKind::InvalidEscapeCode, 1, 2);
         assert_error!("'", LexicalErrorKind::InvalidCharLiteral, 1, 1);
         assert_error!("''", LexicalErrorKind::InvalidCharLiteral, 1, 1);
         assert_error!("'''", LexicalErrorKind::InvalidCharLiteral, 1, 1);
-        assert_error!("'xy'", LexicalErrorKind::InvalidCharLiteral, 1, 2);
+        assert_error!("'xy'", LexicalErrorKind::InvalidCharLiteral, 1, 1);
         assert_error!("'\n'", LexicalErrorKind::InvalidCharLiteral, 1, 1, "'");
     }
 
     #[test]
     fn string_literals() {
-        assert_token!("\"\"", TokenType::StringLiteral, "\"\"", 1, 2);
-        assert_token!("\"abc def\"", TokenType::StringLiteral, "\"abc def\"", 1, 9);
-        assert_token!("\" \\\" \"", TokenType::StringLiteral, "\" \\\" \"", 1, 6);
+        assert_token!("\"\"", TokenType::StringLiteral, "\"\"", 1, 1);
+        assert_token!("\"abc def\"", TokenType::StringLiteral, "\"abc def\"", 1, 1);
+        assert_token!("\" \\\" \"", TokenType::StringLiteral, "\" \\\" \"", 1, 1);
+        assert_literal!("\"\"", Literal::String(String::new()));
+        assert_literal!("\"a \\\"b\\\"\"", Literal::String("a \"b\"".to_string()));
+    }
+
+    #[test]
+    fn escapes() {
+        assert_literal!(
+            "\"\\n\\t\\r\\0\\\\\\'\\\"\"",
+            Literal::String("\n\t\r\0\\'\"".to_string())
+        );
+        assert_literal!("'\\x41'", Literal::Char('A'));
+        assert_literal!("'\\x7f'", Literal::Char('\x7F'));
+        assert_literal!("'\\u{E9}'", Literal::Char('é'));
+        assert_literal!("'\\u{1F600}'", Literal::Char('😀'));
+        assert_literal!("'\\u{0}'", Literal::Char('\0'));
+        assert_literal!(
+            "\"\\u{10FFFF}x\\x20\"",
+            Literal::String("\u{10FFFF}x ".to_string())
+        );
+
+        // errors are reported at the '\' of the escape
+        assert_error!("\"ab\\q\"", LexicalErrorKind::InvalidEscapeCode, 1, 4);
+        assert_error!("\"é\\x4\"", LexicalErrorKind::InvalidHexEscape, 1, 3);
+        assert_error!("'\\x80'", LexicalErrorKind::InvalidHexEscape, 1, 2);
+        assert_error!("'\\xG0'", LexicalErrorKind::InvalidHexEscape, 1, 2);
+        assert_error!("\"  \\u41\"", LexicalErrorKind::InvalidUnicodeEscape, 1, 4);
+        assert_error!("'\\u{}'", LexicalErrorKind::InvalidUnicodeEscape, 1, 2);
+        assert_error!(
+            "'\\u{1234567}'",
+            LexicalErrorKind::InvalidUnicodeEscape,
+            1,
+            2
+        );
+        assert_error!("'\\u{D800}'", LexicalErrorKind::InvalidUnicodeEscape, 1, 2);
+        assert_error!(
+            "'\\u{110000}'",
+            LexicalErrorKind::InvalidUnicodeEscape,
+            1,
+            2
+        );
+        assert_error!("'\\u{41'", LexicalErrorKind::InvalidUnicodeEscape, 1, 2);
+        assert_error!(
+            "\"\\\n\"",
+            LexicalErrorKind::InvalidEscapeCode,
+            1,
+            2,
+            "\"\\"
+        );
+    }
+
+    #[test]
+    fn unicode() {
+        assert_token!("'é'", TokenType::CharLiteral, "'é'", 1, 1);
+        assert_token!("'😀'", TokenType::CharLiteral, "'😀'", 1, 1);
+        assert_token!(
+            "\"héllo wörld\"",
+            TokenType::StringLiteral,
+            "\"héllo wörld\"",
+            1,
+            1
+        );
+        assert_token!("\u{3000}x", TokenType::Identifier, "x", 1, 2);
+        assert_error!("é", LexicalErrorKind::UnexpectedCharacter, 1, 1);
+        assert_error!("'日本'", LexicalErrorKind::InvalidCharLiteral, 1, 1);
+    }
+
+    #[test]
+    fn utf16_columns() {
+        let cursor = Cursor::new("\"😀é\" x");
+        let lexer = Lexer::new(cursor, "test".to_string()).with_column_unit(ColumnUnit::Utf16);
+        let positions: Vec<_> = lexer
+            .map(|t| t.map(|t| (t.span.start.column, t.span.end.column)))
+            .collect();
+        assert_eq!(positions, vec![Ok((1, 5)), Ok((7, 7))]);
+
+        let cursor = Cursor::new("😀 😀 é");
+        let mut lexer = Lexer::new(cursor, "test".to_string()).with_column_unit(ColumnUnit::Utf16);
+        let expected = Span {
+            start: Pos { line: 1, column: 1 },
+            end: Pos { line: 1, column: 2 },
+            byte_range: 0..4,
+        };
+        assert_eq!(lexer.next().unwrap().unwrap_err().span, expected);
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let invalid_utf8 = |input: &[u8]| {
+            let mut lexer = Lexer::new(input, "test".to_string());
+            match lexer.next() {
+                Some(Err(e)) => {
+                    assert_eq!(e.kind, ErrorKind::Lexical(LexicalErrorKind::InvalidUtf8));
+                    (
+                        e.span.start.line,
+                        e.span.start.column,
+                        e.source_lines[&e.span.start.line].clone(),
+                    )
+                }
+                result => panic!("expected invalid UTF-8 error but got {result:?}"),
+            }
+        };
+
+        // unexpected continuation byte
+        assert_eq!(invalid_utf8(b"\x80"), (1, 1, "\u{FFFD}".to_string()));
+        // within a literal
+        assert_eq!(
+            invalid_utf8(b"\"ab\xFFc\""),
+            (1, 4, "\"ab\u{FFFD}c\"".to_string())
+        );
+        // truncated sequence followed by a valid character
+        assert_eq!(invalid_utf8(b"'\xC3'"), (1, 2, "'\u{FFFD}'".to_string()));
+        // overlong encoding of '/'
+        assert_eq!(
+            invalid_utf8(b"\xE0\x80\xAF"),
+            (1, 1, "\u{FFFD}".to_string())
+        );
+        // encoded surrogate
+        assert_eq!(
+            invalid_utf8(b"\xED\xA0\x80"),
+            (1, 1, "\u{FFFD}".to_string())
+        );
+
+        // lexing resumes on the next line
+        let mut lexer = Lexer::new(&b"\"\xFF\" \xFF\nx"[..], "test".to_string());
+        let expected = Span {
+            start: Pos { line: 1, column: 2 },
+            end: Pos { line: 1, column: 2 },
+            byte_range: 1..2,
+        };
+        assert_eq!(lexer.next().unwrap().unwrap_err().span, expected);
+        assert!(matches!(
+            lexer.next(),
+            Some(Ok(Token {
+                tok_type: TokenType::EndStatement,
+                ..
+            }))
+        ));
+        assert!(matches!(
+            lexer.next(),
+            Some(Ok(Token {
+                tok_type: TokenType::Identifier,
+                ..
+            }))
+        ));
+        assert!(lexer.next().is_none());
+    }
+
+    /// Produces the given data and then fails, after first being interrupted
+    /// (which should be retried).
+    struct FailingReader {
+        data: &'static [u8],
+        interrupted: bool,
+    }
+
+    impl Read for FailingReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if !self.data.is_empty() {
+                self.data.read(buf)
+            } else if !self.interrupted {
+                self.interrupted = true;
+                Err(io::ErrorKind::Interrupted.into())
+            } else {
+                Err(io::Error::new(io::ErrorKind::BrokenPipe, "pipe closed"))
+            }
+        }
+    }
+
+    #[test]
+    fn read_failure() {
+        let reader = FailingReader {
+            data: b"x = ab",
+            interrupted: false,
+        };
+        let mut lexer = Lexer::new(reader, "test".to_string());
+        assert!(lexer.next().unwrap().is_ok());
+        assert!(lexer.next().unwrap().is_ok());
+
+        // the token being read when the input failed is not produced
+        let error = lexer.next().unwrap().unwrap_err();
+        assert_eq!(
+            error.kind,
+            ErrorKind::Io {
+                kind: io::ErrorKind::BrokenPipe,
+                message: "pipe closed".to_string(),
+            }
+        );
+        assert_eq!(error.span.start, Pos { line: 1, column: 7 });
+        assert_eq!(error.span.byte_range, 6..6);
+        assert_eq!(error.source_lines[&1], "x = ab");
+        assert!(lexer.next().is_none());
+    }
+
+    #[test]
+    fn comments() {
+        assert_token!("-- comment\nx", TokenType::EndStatement, "\n", 1, 11);
+        assert_token!("--[ not a block\nx", TokenType::EndStatement, "\n", 1, 16);
+        assert_token!("--[[ a\nb ]] x", TokenType::Identifier, "x", 2, 6);
+        assert_token!("--[[ --[[ a ]]\n]] x", TokenType::Identifier, "x", 2, 4);
+        assert_token!("--[[]]-=", TokenType::MinusAssign, "-=", 1, 7);
+        assert_token!("-- x\n\n-- y\n\n", TokenType::EndStatement, "\n\n", 1, 5);
+        assert_token!("- -1", TokenType::Minus, "-", 1, 1);
+
+        let cursor = Cursor::new("x--[[ ]]-y -- z");
+        let tokens: Vec<_> = Lexer::new(cursor, "test".to_string())
+            .map(|t| t.unwrap().tok_type)
+            .collect();
+        assert_eq!(
+            tokens,
+            [
+                TokenType::Identifier,
+                TokenType::Minus,
+                TokenType::Identifier
+            ]
+        );
+
+        assert_error!("--[[ a", LexicalErrorKind::UnterminatedComment, 1, 1);
+        assert_error!(
+            "  --[[\n--[[\n]]",
+            LexicalErrorKind::UnterminatedComment,
+            1,
+            3,
+            "  --[["
+        );
+        assert_error!(
+            "--[[ ]\n] ",
+            LexicalErrorKind::UnterminatedComment,
+            1,
+            1,
+            "--[[ ]"
+        );
+    }
+
+    #[test]
+    fn error_hints() {
+        let first_error = |input: &'static str| {
+            let mut lexer = Lexer::new(Cursor::new(input), "test".to_string());
+            lexer.find_map(Result::err).unwrap()
+        };
+
+        let error = first_error("1_000_000_000_000i32");
+        assert_eq!(
+            error.notes,
+            ["i32 values range from -2147483648 to 2147483647"]
+        );
+        assert!(first_error("'\\q'").help[0].starts_with("valid escapes are \\n"));
+
+        let error = first_error("--[[ --[[ ]]");
+        assert_eq!(error.label.as_deref(), Some("comment opened here"));
+        assert_eq!(error.notes.len(), 1);
+        assert!(first_error("--[[").notes.is_empty());
+    }
+
+    #[test]
+    fn doc_comments() {
+        assert_token!(
+            "--- Some docs.\nx",
+            TokenType::DocComment,
+            "--- Some docs.",
+            1,
+            1
+        );
+        assert_token!("  ---", TokenType::DocComment, "---", 1, 3);
+        assert_token!("---- x\n", TokenType::EndStatement, "\n", 1, 7);
+        assert_token!("--[[ --- ]] x", TokenType::Identifier, "x", 1, 13);
+    }
+
+    #[test]
+    fn raw_strings() {
+        assert_token!("r\"a\\n\"", TokenType::StringLiteral, "r\"a\\n\"", 1, 1);
+        assert_literal!("r\"a\\n{b}\"", Literal::String("a\\n{b}".to_string()));
+        assert_literal!(
+            "r#\"\"quoted\"\n\"#",
+            Literal::String("\"quoted\"\n".to_string())
+        );
+        assert_literal!("r##\"a\"#b\"##", Literal::String("a\"#b".to_string()));
+        assert_token!("r#\"\na\"#", TokenType::StringLiteral, "r#\"\na\"#", 1, 1);
+        assert_token!("r", TokenType::Identifier, "r", 1, 1);
+        assert_error!("r#x", LexicalErrorKind::InvalidStringLiteral, 1, 1);
+        assert_error!("r#\"a\"", LexicalErrorKind::InvalidStringLiteral, 1, 1);
+    }
+
+    #[test]
+    fn interpolated_strings() {
+        let tokens = |input: &str| -> Vec<_> {
+            Lexer::new(Cursor::new(input), "test".to_string())
+                .map(|t| {
+                    let t = t.unwrap();
+                    (t.tok_type, t.value)
+                })
+                .collect()
+        };
+        let string = |s: &str| Some(Literal::String(s.to_string()));
+
+        assert_eq!(
+            tokens("\"a {x} b {y}\""),
+            [
+                (TokenType::StringStart, string("a ")),
+                (TokenType::Identifier, None),
+                (TokenType::StringMiddle, string(" b ")),
+                (TokenType::Identifier, None),
+                (TokenType::StringEnd, string("")),
+            ]
+        );
+        assert_eq!(
+            tokens("\"{\"{1}\"}\\{}\""),
+            [
+                (TokenType::StringStart, string("")),
+                (TokenType::StringStart, string("")),
+                (TokenType::IntLiteral, Some(Literal::Int(1))),
+                (TokenType::StringEnd, string("")),
+                (TokenType::StringEnd, string("{}")),
+            ]
+        );
+        assert_token!("\"ab {", TokenType::StringStart, "\"ab {", 1, 1);
+        assert_error!("}", LexicalErrorKind::UnexpectedCharacter, 1, 1);
+
+        // an error abandons the interpolated string
+        let mut lexer = Lexer::new(Cursor::new("\"{'ab'}\"\n}"), "test".to_string());
+        assert!(matches!(lexer.next(), Some(Ok(_))));
+        assert!(matches!(lexer.next(), Some(Err(_))));
+        assert!(matches!(lexer.next(), Some(Ok(_))));
+        assert!(matches!(lexer.next(), Some(Err(_))));
+    }
+
+    #[test]
+    fn resume_after_error() {
+        let cursor = Cursor::new("x = 'ab' + y\nz");
+        let mut l = Lexer::new(cursor, "test".to_string());
+
+        assert!(matches!(l.next(), Some(Ok(Token { lexeme, .. })) if lexeme == "x"));
+        assert!(matches!(l.next(), Some(Ok(Token { lexeme, .. })) if lexeme == "="));
+        let expected = Span {
+            start: Pos { line: 1, column: 5 },
+            end: Pos { line: 1, column: 6 },
+            byte_range: 4..6,
+        };
+        assert_eq!(l.next().unwrap().unwrap_err().span, expected);
+
+        // remainder of the line is skipped
+        let expected = Token {
+            tok_type: TokenType::EndStatement,
+            lexeme: "\n".to_string(),
+            value: None,
+            span: Span {
+                start: Pos {
+                    line: 1,
+                    column: 13,
+                },
+                end: Pos {
+                    line: 1,
+                    column: 13,
+                },
+                byte_range: 12..13,
+            },
+        };
+        assert_eq!(l.next(), Some(Ok(expected)));
+
+        let expected = Token {
+            tok_type: TokenType::Identifier,
+            lexeme: "z".to_string(),
+            value: None,
+            span: Span {
+                start: Pos { line: 2, column: 1 },
+                end: Pos { line: 2, column: 1 },
+                byte_range: 13..14,
+            },
+        };
+        assert_eq!(l.next(), Some(Ok(expected)));
+        assert!(l.next().is_none());
+    }
+
+    #[test]
+    fn spans() {
+        let cursor = Cursor::new("x = \"日本\" +\nr\"a\nb\"");
+        let spans: Vec<_> = Lexer::new(cursor, "test".to_string())
+            .map(|t| {
+                let span = t.unwrap().span;
+                (
+                    (span.start.line, span.start.column),
+                    (span.end.line, span.end.column),
+                    span.byte_range,
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            spans,
+            vec![
+                ((1, 1), (1, 1), 0..1),
+                ((1, 3), (1, 3), 2..3),
+                ((1, 5), (1, 8), 4..12),
+                ((1, 10), (1, 10), 13..14),
+                ((1, 11), (1, 11), 14..15),
+                ((2, 1), (3, 2), 15..21),
+            ]
+        );
     }
 
     #[test]
     fn end_statement() {
-        assert_token!("\n\n\n", TokenType::EndStatement, "\n\n\n", 4, 0);
-        assert_token!(" ;\n", TokenType::EndStatement, ";\n", 2, 0);
+        assert_token!("\n\n\n", TokenType::EndStatement, "\n\n\n", 1, 1);
+        assert_token!(" ;\n", TokenType::EndStatement, ";\n", 1, 2);
     }
 }
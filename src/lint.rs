@@ -0,0 +1,128 @@
+//! Lints: checks for code which is valid but probably not what was intended,
+//! reported as warnings by default. The level of each lint can be changed from
+//! the command line with `-A` (allow), `-W` (warn) and `-D` (deny).
+
+use std::collections::HashMap;
+
+use clap::ValueEnum;
+
+/// A lint which can be allowed, warned about or denied as a whole.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ValueEnum)]
+pub enum Lint {
+    /// Variables and parameters which are never used
+    UnusedVariables,
+    /// Variables which are assigned to but never read
+    UnusedAssignments,
+    /// Statements which can never be executed
+    UnreachableCode,
+    /// Variables declared with the same name as another variable in scope
+    Shadowing,
+    /// Variables declared without an initial value which may be read before
+    /// they are assigned
+    ReadBeforeAssignment,
+}
+
+impl Lint {
+    /// The name of the lint, as given on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::UnusedVariables => "unused-variables",
+            Lint::UnusedAssignments => "unused-assignments",
+            Lint::UnreachableCode => "unreachable-code",
+            Lint::Shadowing => "shadowing",
+            Lint::ReadBeforeAssignment => "read-before-assignment",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    /// Not reported.
+    Allow,
+    /// Reported as a warning, which does not prevent compilation.
+    Warn,
+    /// Reported as an error.
+    Deny,
+}
+
+/// The level at which each lint is reported. All lints warn by default.
+#[derive(Debug, Clone, Default)]
+pub struct LintLevels {
+    /// Levels set explicitly, overriding the default.
+    levels: HashMap<Lint, Level>,
+    /// Whether lints which would otherwise warn are denied instead.
+    deny_warnings: bool,
+}
+
+impl LintLevels {
+    pub fn set(&mut self, lint: Lint, level: Level) {
+        self.levels.insert(lint, level);
+    }
+
+    /// Deny all lints which would otherwise only warn.
+    pub fn deny_warnings(&mut self) {
+        self.deny_warnings = true;
+    }
+
+    pub fn level(&self, lint: Lint) -> Level {
+        match self.levels.get(&lint).copied().unwrap_or(Level::Warn) {
+            Level::Warn if self.deny_warnings => Level::Deny,
+            level => level,
+        }
+    }
+
+    /// A note explaining why a lint is reported at the level it is, to be
+    /// attached to each error or warning it produces.
+    pub fn explain(&self, lint: Lint) -> String {
+        let name = lint.name();
+        match (self.levels.get(&lint), self.level(lint)) {
+            (None, Level::Deny) => format!("'-D {name}' is implied by '--deny-warnings'"),
+            (None, _) => format!("'-W {name}' is on by default"),
+            (Some(Level::Deny), _) => format!("requested on the command line with '-D {name}'"),
+            (Some(_), Level::Deny) => format!("'-W {name}' upgraded by '--deny-warnings'"),
+            (Some(_), _) => format!("requested on the command line with '-W {name}'"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels() {
+        let mut levels = LintLevels::default();
+        assert_eq!(levels.level(Lint::Shadowing), Level::Warn);
+
+        levels.set(Lint::Shadowing, Level::Allow);
+        levels.set(Lint::UnreachableCode, Level::Deny);
+        levels.deny_warnings();
+        assert_eq!(levels.level(Lint::Shadowing), Level::Allow);
+        assert_eq!(levels.level(Lint::UnreachableCode), Level::Deny);
+        assert_eq!(levels.level(Lint::UnusedVariables), Level::Deny);
+
+        assert_eq!(
+            levels.explain(Lint::UnusedVariables),
+            "'-D unused-variables' is implied by '--deny-warnings'"
+        );
+        assert_eq!(
+            levels.explain(Lint::UnreachableCode),
+            "requested on the command line with '-D unreachable-code'"
+        );
+
+        levels.set(Lint::UnusedAssignments, Level::Warn);
+        assert_eq!(levels.level(Lint::UnusedAssignments), Level::Deny);
+        assert_eq!(
+            levels.explain(Lint::UnusedAssignments),
+            "'-W unused-assignments' upgraded by '--deny-warnings'"
+        );
+    }
+
+    #[test]
+    fn names_match_command_line() {
+        for lint in Lint::value_variants() {
+            let value = lint.to_possible_value().unwrap();
+            assert_eq!(value.get_name(), lint.name());
+        }
+    }
+}
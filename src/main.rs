@@ -1,75 +1,817 @@
 mod ast;
+mod check;
 mod codegen;
+mod doc;
+mod dump;
+mod emit;
 mod error;
+mod json;
 mod lex;
+mod lint;
 mod parse;
+mod render;
+mod runtime;
+mod span;
 mod token;
+mod wat;
 
 use std::{
     fs::File,
-    io::{self, Read, Write},
-    path::PathBuf,
+    io::{self, IsTerminal, Read, Write},
+    panic,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Instant,
 };
 
+use check::TypeChecker;
 use codegen::CodeGenerator;
-use error::Result;
-use lex::Lexer;
+use doc::{DocFormat, Module};
+use emit::{Emit, EmitRequest};
+use error::{plural, Error, ErrorKind, Severity, MAX_ERRORS};
+use lex::{ColumnUnit, Lexer};
+use lint::{Level, Lint, LintLevels};
 use parse::Parser;
+use render::ColorChoice;
 
-use clap::Parser as ClapParser;
+use clap::{ArgAction, CommandFactory, Parser as ClapParser, Subcommand, ValueEnum};
+use log::{debug, info, LevelFilter};
 
-fn main() {
-    let args = Args::parse();
+// Exit statuses, on which scripts and build tools may rely. Success is 0.
 
-    if let Some(wasm) = compile_input(&args.infile) {
-        write_output(&args.outfile, wasm);
+/// The input contained errors.
+const EXIT_COMPILE_ERROR: u8 = 1;
+/// The command line was invalid.
+const EXIT_USAGE_ERROR: u8 = 2;
+/// The input could not be read or the output could not be written.
+const EXIT_IO_ERROR: u8 = 3;
+/// The compiler panicked (the same status as a Rust program which panics).
+const EXIT_INTERNAL_ERROR: u8 = 101;
+
+fn main() -> ExitCode {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        eprintln!("error: internal compiler error: this is a bug in kobe, please report it");
+    }));
+
+    panic::catch_unwind(run).unwrap_or(ExitCode::from(EXIT_INTERNAL_ERROR))
+}
+
+fn run() -> ExitCode {
+    let args = match Args::try_parse() {
+        Ok(args) => args,
+        Err(e) => {
+            // --help and --version are also reported as errors by clap
+            let _ = e.print();
+            return if e.use_stderr() {
+                ExitCode::from(EXIT_USAGE_ERROR)
+            } else {
+                ExitCode::SUCCESS
+            };
+        }
+    };
+
+    env_logger::Builder::new()
+        .filter_level(args.log_level())
+        .format_timestamp(None)
+        .parse_default_env()
+        .init();
+
+    match &args.command {
+        Some(Command::Doc(doc_args)) => return document(doc_args, args.error_reporter()),
+        Some(Command::Tokens(dump_args)) => return dump_tokens(dump_args, args.error_reporter()),
+        Some(Command::Ast(ast_args)) => return dump_ast(ast_args, args.error_reporter()),
+        None => {}
+    }
+
+    let outputs = match args.outputs() {
+        Ok(outputs) => outputs,
+        Err(e) => {
+            let _ = e.print();
+            return ExitCode::from(EXIT_USAGE_ERROR);
+        }
+    };
+    let kinds: Vec<_> = outputs.iter().map(|output| output.kind).collect();
+
+    let column_unit = if args.utf16_columns {
+        ColumnUnit::Utf16
+    } else {
+        ColumnUnit::Chars
+    };
+
+    let compilation = compile_input(&args.infile, column_unit, args.lint_levels(), &kinds);
+    args.error_reporter()
+        .report(&compilation.errors, compilation.errors_truncated);
+
+    // outputs are written for each stage which completed, even if a later
+    // one failed
+    let mut status = if compilation.failed() {
+        exit_code(&compilation.errors)
+    } else {
+        ExitCode::SUCCESS
+    };
+    for (kind, contents) in &compilation.outputs {
+        let path = outputs
+            .iter()
+            .find(|output| output.kind == *kind)
+            .and_then(|output| output.path.as_ref());
+        if !write_output(path, contents) {
+            status = ExitCode::from(EXIT_IO_ERROR);
+        }
+    }
+    status
+}
+
+/// Everything produced by compiling the input: the requested output of each
+/// stage of compilation which completed, along with any errors and warnings.
+#[derive(Default)]
+struct Compilation {
+    outputs: Vec<(Emit, Vec<u8>)>,
+    errors: Vec<Error>,
+    /// Whether there were too many errors to report them all.
+    errors_truncated: bool,
+}
+
+impl Compilation {
+    fn failed(&self) -> bool {
+        self.errors.iter().any(|e| e.severity == Severity::Error)
+    }
+
+    fn add_output(&mut self, kind: Emit, contents: impl Into<Vec<u8>>) {
+        self.outputs.push((kind, contents.into()));
     }
 }
 
-fn compile_input(maybe_path: &Option<PathBuf>) -> Option<Vec<u8>> {
-    let result = if let Some(path) = maybe_path {
+fn compile_input(
+    maybe_path: &Option<PathBuf>,
+    column_unit: ColumnUnit,
+    lint_levels: LintLevels,
+    emit: &[Emit],
+) -> Compilation {
+    if let Some(path) = maybe_path {
+        let name = path.to_string_lossy().into_owned();
         match File::open(path) {
-            Ok(file) => perform_compilation_steps(file, path.to_string_lossy().into_owned()),
+            Ok(file) => perform_compilation_steps(file, name, column_unit, lint_levels, emit),
+            Err(e) => Compilation {
+                errors: vec![Error::io(e, name)],
+                ..Compilation::default()
+            },
+        }
+    } else {
+        let name = "stdin".to_string();
+        perform_compilation_steps(io::stdin(), name, column_unit, lint_levels, emit)
+    }
+}
+
+/// The exit status after the given errors have been reported.
+fn exit_code(errors: &[Error]) -> ExitCode {
+    if errors
+        .iter()
+        .any(|e| matches!(e.kind, ErrorKind::Io { .. }))
+    {
+        ExitCode::from(EXIT_IO_ERROR)
+    } else {
+        ExitCode::from(EXIT_COMPILE_ERROR)
+    }
+}
+
+fn document(args: &DocArgs, reporter: ErrorReporter) -> ExitCode {
+    let mut modules = Vec::new();
+    let mut errors = Vec::new();
+    let mut errors_truncated = false;
+
+    for path in &args.infiles {
+        let name = path.to_string_lossy().into_owned();
+        let file = match File::open(path) {
+            Ok(file) => file,
             Err(e) => {
-                eprintln!("Could not read input file {}: {}", path.display(), e);
-                return None;
+                errors.push(Error::io(e, name));
+                continue;
             }
+        };
+
+        let mut parser = Parser::new(Lexer::new(file, name));
+        let mut functions = Vec::new();
+        for result in parser.by_ref() {
+            match result {
+                Ok(function) => functions.push(function),
+                Err(e) => errors.push(e),
+            }
+        }
+        errors_truncated |= parser.errors_truncated();
+
+        let name = path
+            .file_stem()
+            .map_or("module".into(), |s| s.to_string_lossy().into_owned());
+        modules.push(Module { name, functions });
+    }
+
+    if !errors.is_empty() {
+        reporter.report(&errors, errors_truncated);
+        return exit_code(&errors);
+    }
+
+    if let Err(e) = doc::write_docs(&args.out_dir, args.format, &modules, args.document_private) {
+        eprintln!(
+            "Could not write documentation to {}: {}",
+            args.out_dir.display(),
+            e
+        );
+        return ExitCode::from(EXIT_IO_ERROR);
+    }
+    ExitCode::SUCCESS
+}
+
+/// Print the tokens read from a source file, even if some could not be read.
+fn dump_tokens(args: &DumpArgs, reporter: ErrorReporter) -> ExitCode {
+    let (file, name) = match open_input(&args.infile) {
+        Ok(input) => input,
+        Err(e) => return finish_dump(None, vec![e], false, reporter),
+    };
+
+    let mut tokens = Vec::new();
+    let mut errors = Vec::new();
+    for result in Lexer::new(file, name.clone()) {
+        match result {
+            Ok(token) => tokens.push(token),
+            Err(e) => errors.push(e),
         }
+    }
+
+    let output = if args.json {
+        dump::tokens_json(&tokens, &name)
     } else {
-        perform_compilation_steps(io::stdin(), "stdin".to_string())
+        dump::tokens(&tokens)
     };
+    finish_dump(Some(output), errors, false, reporter)
+}
 
-    if let Err(e) = &result {
-        eprintln!("{e}");
+/// Print the syntax tree parsed from a source file, after type checking it if
+/// requested.
+fn dump_ast(args: &AstArgs, reporter: ErrorReporter) -> ExitCode {
+    let (file, name) = match open_input(&args.dump.infile) {
+        Ok(input) => input,
+        Err(e) => return finish_dump(None, vec![e], false, reporter),
+    };
+
+    let mut checker = TypeChecker::new(Parser::new(Lexer::new(file, name.clone())));
+    let result = match checker.parser_mut().parse_all() {
+        Ok(functions) if args.typed => checker.check(functions),
+        Ok(functions) => Ok((functions, Vec::new())),
+        Err(errors) => Err(errors),
+    };
+    let truncated = checker.errors_truncated();
+    let (functions, warnings) = match result {
+        Ok(checked) => checked,
+        Err(errors) => return finish_dump(None, errors, truncated, reporter),
+    };
+
+    let output = if args.dump.json {
+        dump::ast_json(&functions, &name)
+    } else {
+        dump::ast(&functions)
+    };
+    finish_dump(Some(output), warnings, false, reporter)
+}
+
+fn open_input(path: &Path) -> Result<(File, String), Error> {
+    let name = path.to_string_lossy().into_owned();
+    match File::open(path) {
+        Ok(file) => Ok((file, name)),
+        Err(e) => Err(Error::io(e, name)),
+    }
+}
+
+/// Report any errors from producing a dump, then write the dump (if there is
+/// one) to stdout.
+fn finish_dump(
+    output: Option<String>,
+    errors: Vec<Error>,
+    errors_truncated: bool,
+    reporter: ErrorReporter,
+) -> ExitCode {
+    reporter.report(&errors, errors_truncated);
+    let mut status = if errors.iter().any(|e| e.severity == Severity::Error) {
+        exit_code(&errors)
+    } else {
+        ExitCode::SUCCESS
+    };
+    if let Some(output) = output {
+        if !write_output(None, output.as_bytes()) {
+            status = ExitCode::from(EXIT_IO_ERROR);
+        }
     }
+    status
+}
 
-    result.ok()
+/// Writes errors to stderr in the format requested on the command line.
+struct ErrorReporter {
+    format: ErrorFormat,
+    colour: bool,
+    /// Whether to omit warnings.
+    quiet: bool,
 }
 
-fn write_output(maybe_path: &Option<PathBuf>, wasm: Vec<u8>) {
-    if let Some(path) = maybe_path {
-        let result = File::create(path).and_then(|mut f| f.write_all(&wasm));
+impl ErrorReporter {
+    /// Write the given errors and warnings (unless quiet), followed by a count
+    /// of each if writing for humans. If `truncated`, there were more errors
+    /// than could be reported.
+    fn report(&self, errors: &[Error], truncated: bool) {
+        let errors: Vec<_> = errors
+            .iter()
+            .filter(|e| !self.quiet || e.severity == Severity::Error)
+            .collect();
 
-        if let Err(e) = result {
-            eprintln!("Could not write output file {}: {}", path.display(), e);
+        if self.format == ErrorFormat::Json {
+            for e in &errors {
+                eprintln!("{}", json::render(e));
+            }
+            return;
+        }
+
+        for e in &errors {
+            eprintln!("{}", render::render(e, self.colour));
+        }
+
+        let count = |severity| errors.iter().filter(|e| e.severity == severity).count();
+        let (error_count, warning_count) = (count(Severity::Error), count(Severity::Warning));
+
+        if truncated {
+            eprintln!("Too many errors, stopped after the first {MAX_ERRORS}.");
+        }
+
+        let warnings = plural(warning_count, "warning");
+        match (error_count, warning_count) {
+            (0, 0) => {}
+            (0, _) => eprintln!("Compilation succeeded with {warnings}."),
+            (_, 0) => eprintln!(
+                "Compilation failed due to {}.",
+                plural(error_count, "error")
+            ),
+            (_, _) => eprintln!(
+                "Compilation failed due to {}; {warnings} emitted.",
+                plural(error_count, "error")
+            ),
+        }
+    }
+}
+
+/// Write an output to the given file, or stdout if there is none, returning
+/// whether this succeeded.
+fn write_output(maybe_path: Option<&PathBuf>, contents: &[u8]) -> bool {
+    let result = match maybe_path {
+        Some(path) => File::create(path).and_then(|mut f| f.write_all(contents)),
+        None => {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(contents).and_then(|_| stdout.flush())
+        }
+    };
+
+    if let Err(e) = &result {
+        match maybe_path {
+            Some(path) => eprintln!("Could not write output file {}: {}", path.display(), e),
+            None => eprintln!("Could not write to stdout: {e}"),
         }
-    } else {
-        println!("{:?}", wasm);
     }
+    result.is_ok()
 }
 
-fn perform_compilation_steps(input: impl Read, name: String) -> Result<Vec<u8>> {
-    let lexer = Lexer::new(input, name);
-    let parser = Parser::new(lexer);
-    let generator = CodeGenerator::new(parser);
-    generator.generate_wasm()
+/// Run each stage of compilation needed to produce the requested outputs,
+/// stopping at the first stage to fail.
+fn perform_compilation_steps(
+    input: impl Read,
+    name: String,
+    column_unit: ColumnUnit,
+    lint_levels: LintLevels,
+    emit: &[Emit],
+) -> Compilation {
+    debug!("compiling {name}");
+    let mut compilation = Compilation::default();
+    let last_stage = emit.iter().copied().max().unwrap_or(Emit::Wasm);
+
+    let source_name = name.clone();
+    let mut lexer = Lexer::new(input, name).with_column_unit(column_unit);
+    if emit.contains(&Emit::Tokens) {
+        lexer = lexer.recording_tokens();
+    }
+
+    if last_stage == Emit::Tokens {
+        compilation.errors = lexer.by_ref().filter_map(Result::err).collect();
+        let (token_count, lex_time) = lexer.stats();
+        info!("lex: {} in {lex_time:.2?}", plural(token_count, "token"));
+        compilation.add_output(Emit::Tokens, dump::tokens(&lexer.take_tokens()));
+        return compilation;
+    }
+
+    let mut checker = TypeChecker::new(Parser::new(lexer)).with_lint_levels(lint_levels);
+    let parsed = checker.parser_mut().parse_all();
+
+    let parser = checker.parser();
+    let (token_count, lex_time) = parser.lexer().stats();
+    info!("lex: {} in {lex_time:.2?}", plural(token_count, "token"));
+    let parse_time = parser.elapsed().saturating_sub(lex_time);
+    match &parsed {
+        Ok(functions) => info!(
+            "parse: {} in {parse_time:.2?}",
+            plural(functions.len(), "function")
+        ),
+        Err(errors) => info!(
+            "parse: {} in {parse_time:.2?}",
+            plural(errors.len(), "error")
+        ),
+    }
+
+    if emit.contains(&Emit::Tokens) {
+        // parsing may have been abandoned before the end of the input
+        let lexer = checker.parser_mut().lexer_mut();
+        for _ in lexer.by_ref() {}
+        compilation.add_output(Emit::Tokens, dump::tokens(&lexer.take_tokens()));
+    }
+
+    let functions = match parsed {
+        Ok(functions) => functions,
+        Err(errors) => {
+            compilation.errors = errors;
+            compilation.errors_truncated = checker.errors_truncated();
+            return compilation;
+        }
+    };
+    if emit.contains(&Emit::Ast) {
+        compilation.add_output(Emit::Ast, dump::ast(&functions));
+    }
+    if last_stage == Emit::Ast {
+        return compilation;
+    }
+
+    let start = Instant::now();
+    let result = checker.check(functions);
+    let (Ok((_, errors)) | Err(errors)) = &result;
+    let warning_count = errors
+        .iter()
+        .filter(|e| e.severity == Severity::Warning)
+        .count();
+    info!(
+        "check: {}, {} in {:.2?}",
+        plural(errors.len() - warning_count, "error"),
+        plural(warning_count, "warning"),
+        start.elapsed()
+    );
+
+    let functions = match result {
+        Ok((functions, warnings)) => {
+            compilation.errors = warnings;
+            functions
+        }
+        Err(errors) => {
+            compilation.errors = errors;
+            compilation.errors_truncated = checker.errors_truncated();
+            return compilation;
+        }
+    };
+    if emit.contains(&Emit::TypedAst) {
+        compilation.add_output(Emit::TypedAst, dump::ast(&functions));
+    }
+    if last_stage == Emit::TypedAst {
+        return compilation;
+    }
+
+    let start = Instant::now();
+    let (wasm, line_table) = CodeGenerator::new(functions).generate();
+    info!("codegen: {} bytes in {:.2?}", wasm.len(), start.elapsed());
+    if emit.contains(&Emit::Wat) {
+        compilation.add_output(Emit::Wat, wat::print(&wasm, &line_table, &source_name));
+    }
+    if emit.contains(&Emit::Wasm) {
+        compilation.add_output(Emit::Wasm, wasm);
+    }
+    compilation
     // TODO: Optimise with wasm-opt?
 }
 
 #[derive(ClapParser)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
     /// Input Kobe source file path
     infile: Option<PathBuf>,
-    /// Output WASM file path
+    /// Output file path. Without one, output is written to stdout
     outfile: Option<PathBuf>,
+    /// What to output, as a comma-separated list of KIND[=PATH] where KIND is
+    /// one of tokens, ast, typed-ast, wasm (the default) or wat. Without a path,
+    /// the output is written to OUTFILE, which can only be done for one kind
+    #[arg(long, value_name = "KIND[=PATH]", value_delimiter = ',')]
+    emit: Vec<EmitRequest>,
+    /// Count columns in error positions in UTF-16 code units rather than
+    /// characters, as expected by many editors
+    #[arg(long)]
+    utf16_columns: bool,
+    /// When to colour error messages
+    #[arg(long, value_enum, global = true, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+    /// How to write error messages
+    #[arg(long, value_enum, global = true, default_value_t = ErrorFormat::Human)]
+    error_format: ErrorFormat,
+    /// Report a lint as a warning
+    #[arg(short = 'W', long = "warn", value_enum, value_name = "LINT")]
+    warn: Vec<Lint>,
+    /// Do not report a lint
+    #[arg(short = 'A', long = "allow", value_enum, value_name = "LINT")]
+    allow: Vec<Lint>,
+    /// Report a lint as an error. Takes precedence over -W and -A
+    #[arg(short = 'D', long = "deny", value_enum, value_name = "LINT")]
+    deny: Vec<Lint>,
+    /// Report all lints which would otherwise be warnings as errors
+    #[arg(long)]
+    deny_warnings: bool,
+    /// Log what the compiler is doing and how long it takes (-vv for more
+    /// detail). RUST_LOG, if set, takes precedence
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    verbose: u8,
+    /// Report only errors, omitting warnings and logging
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+}
+
+impl Args {
+    /// Lint levels from the -A, -W, -D and --deny-warnings flags. Where a lint
+    /// is named by more than one flag, -D takes precedence over -W, which
+    /// takes precedence over -A.
+    fn lint_levels(&self) -> LintLevels {
+        let mut levels = LintLevels::default();
+        for (lints, level) in [
+            (&self.allow, Level::Allow),
+            (&self.warn, Level::Warn),
+            (&self.deny, Level::Deny),
+        ] {
+            for &lint in lints {
+                levels.set(lint, level);
+            }
+        }
+
+        if self.deny_warnings {
+            levels.deny_warnings();
+        }
+        levels
+    }
+
+    /// Each kind of output requested with --emit, with the file which it is to
+    /// be written to (or `None` for stdout).
+    fn outputs(&self) -> Result<Vec<EmitRequest>, clap::Error> {
+        let mut outputs = self.emit.clone();
+        if outputs.is_empty() {
+            outputs.push(EmitRequest {
+                kind: Emit::Wasm,
+                path: None,
+            });
+        }
+
+        let conflict =
+            |message| Args::command().error(clap::error::ErrorKind::ArgumentConflict, message);
+        if outputs
+            .iter()
+            .filter(|output| output.path.is_none())
+            .count()
+            > 1
+        {
+            return Err(conflict(
+                "only one kind of output can be written to OUTFILE or stdout; give a path \
+                 for the others with --emit KIND=PATH",
+            ));
+        }
+
+        for output in &mut outputs {
+            if output.path.is_none() {
+                output.path = self.outfile.clone();
+            }
+            if output.path.is_none() && output.kind.is_binary() && io::stdout().is_terminal() {
+                return Err(conflict(
+                    "refusing to write binary WebAssembly to a terminal; give an OUTFILE or \
+                     redirect stdout",
+                ));
+            }
+        }
+        Ok(outputs)
+    }
+
+    fn error_reporter(&self) -> ErrorReporter {
+        ErrorReporter {
+            format: self.error_format,
+            colour: self.color.use_colour(io::stderr().is_terminal()),
+            quiet: self.quiet,
+        }
+    }
+
+    /// The most detailed level of log message to write, from the -v and -q
+    /// flags.
+    fn log_level(&self) -> LevelFilter {
+        if self.quiet {
+            return LevelFilter::Off;
+        }
+        match self.verbose {
+            0 => LevelFilter::Warn,
+            1 => LevelFilter::Info,
+            2 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ErrorFormat {
+    /// Human-readable messages showing the relevant source code
+    Human,
+    /// One JSON object per line, as described in the README
+    Json,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generate documentation from doc comments (`---`) and function signatures
+    Doc(DocArgs),
+    /// Print the tokens read from a source file, one per line, for debugging
+    Tokens(DumpArgs),
+    /// Print the syntax tree parsed from a source file, for debugging
+    Ast(AstArgs),
+}
+
+#[derive(clap::Args)]
+struct DumpArgs {
+    /// Input Kobe source file path
+    infile: PathBuf,
+    /// Print JSON, as described in the README, rather than text
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(clap::Args)]
+struct AstArgs {
+    #[command(flatten)]
+    dump: DumpArgs,
+    /// Type check the tree and include the type of each variable and
+    /// expression
+    #[arg(long)]
+    typed: bool,
+}
+
+#[derive(clap::Args)]
+struct DocArgs {
+    /// Input Kobe source file paths, each documented as a separate module
+    #[arg(required = true)]
+    infiles: Vec<PathBuf>,
+    /// Directory to write the documentation to
+    #[arg(short, long, default_value = "doc")]
+    out_dir: PathBuf,
+    #[arg(long, value_enum, default_value_t = DocFormat::Markdown)]
+    format: DocFormat,
+    /// Also document functions which are not marked `pub`
+    #[arg(long)]
+    document_private: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{env, io::Cursor};
+
+    /// Compile the given source all the way to WebAssembly.
+    fn compile(source: &str) -> Compilation {
+        let input = Cursor::new(source.to_string());
+        let name = "test".to_string();
+        perform_compilation_steps(
+            input,
+            name,
+            ColumnUnit::default(),
+            LintLevels::default(),
+            &[],
+        )
+    }
+
+    /// A path within a directory which does not exist, so that it can be
+    /// neither read nor written.
+    fn missing_path(file_name: &str) -> PathBuf {
+        env::temp_dir().join("kobe-missing-dir").join(file_name)
+    }
+
+    fn reporter() -> ErrorReporter {
+        ErrorReporter {
+            format: ErrorFormat::Human,
+            colour: false,
+            quiet: true,
+        }
+    }
+
+    fn parse_args(args: &[&str]) -> Args {
+        Args::try_parse_from(["kobe"].iter().chain(args)).unwrap()
+    }
+
+    #[test]
+    fn exit_codes() {
+        let io_error = Error::io(io::ErrorKind::NotFound.into(), "in.kb".to_string());
+        let compile_errors = compile("fn f()\nreturn 1\nend").errors;
+        assert!(!compile_errors.is_empty());
+
+        assert_eq!(
+            exit_code(&compile_errors),
+            ExitCode::from(EXIT_COMPILE_ERROR)
+        );
+        assert_eq!(exit_code(&[io_error]), ExitCode::from(EXIT_IO_ERROR));
+
+        // an I/O error takes precedence over compile errors
+        let mut errors = compile_errors;
+        errors.push(Error::io(
+            io::ErrorKind::NotFound.into(),
+            "in.kb".to_string(),
+        ));
+        assert_eq!(exit_code(&errors), ExitCode::from(EXIT_IO_ERROR));
+
+        // warnings alone are not a failure
+        let warnings = compile("fn f()\nlet x = 1\nend").errors;
+        assert!(!warnings.is_empty());
+        assert_eq!(
+            finish_dump(None, warnings, false, reporter()),
+            ExitCode::SUCCESS
+        );
+        assert_eq!(
+            finish_dump(None, vec![], false, reporter()),
+            ExitCode::SUCCESS
+        );
+    }
+
+    #[test]
+    fn log_levels() {
+        assert_eq!(parse_args(&["-q"]).log_level(), LevelFilter::Off);
+        assert_eq!(parse_args(&[]).log_level(), LevelFilter::Warn);
+        assert_eq!(parse_args(&["-v"]).log_level(), LevelFilter::Info);
+        assert_eq!(parse_args(&["-vv"]).log_level(), LevelFilter::Debug);
+        assert_eq!(parse_args(&["-vvv"]).log_level(), LevelFilter::Trace);
+        assert!(Args::try_parse_from(["kobe", "-q", "-v"]).is_err());
+    }
+
+    #[test]
+    fn io_failures() {
+        let missing = missing_path("in.kb");
+
+        let compilation = compile_input(
+            &Some(missing.clone()),
+            ColumnUnit::default(),
+            LintLevels::default(),
+            &[],
+        );
+        assert!(compilation.failed());
+        assert_eq!(
+            exit_code(&compilation.errors),
+            ExitCode::from(EXIT_IO_ERROR)
+        );
+
+        let error = open_input(&missing).unwrap_err();
+        assert!(matches!(error.kind, ErrorKind::Io { .. }));
+        assert_eq!(
+            finish_dump(None, vec![error], false, reporter()),
+            ExitCode::from(EXIT_IO_ERROR)
+        );
+
+        let args = parse_args(&["tokens", missing.to_str().unwrap()]);
+        let Some(Command::Tokens(dump_args)) = &args.command else {
+            panic!("expected the tokens command");
+        };
+        assert_eq!(
+            dump_tokens(dump_args, reporter()),
+            ExitCode::from(EXIT_IO_ERROR)
+        );
+
+        let args = parse_args(&["doc", missing.to_str().unwrap()]);
+        let Some(Command::Doc(doc_args)) = &args.command else {
+            panic!("expected the doc command");
+        };
+        assert_eq!(
+            document(doc_args, reporter()),
+            ExitCode::from(EXIT_IO_ERROR)
+        );
+
+        assert!(!write_output(Some(&missing_path("out.wasm")), b"\0asm"));
+    }
+
+    #[test]
+    fn unknown_element_types_rejected() {
+        for (returns, body) in [
+            ("", "[][0]"),
+            ("", "[[]][0][0]"),
+            (" -> int", "return [][0] + 1"),
+            (" -> bool", "return [][0] == 1"),
+            (" -> bool", "return [][0] and true"),
+            ("", "let x = 0\nx += [][0]"),
+            (" -> string", "return \"{[][0]}\""),
+        ] {
+            let compilation = compile(&format!("fn f(){returns}\n{body}\nend"));
+            assert!(compilation.failed(), "{body}");
+            assert!(compilation.outputs.is_empty(), "{body}");
+            assert_eq!(
+                exit_code(&compilation.errors),
+                ExitCode::from(EXIT_COMPILE_ERROR)
+            );
+        }
+    }
 }
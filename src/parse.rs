@@ -1,30 +1,1814 @@
-use std::io::Read;
+use std::{
+    collections::VecDeque,
+    io::Read,
+    time::{Duration, Instant},
+};
 
-use crate::{ast::Node, lex::Lexer};
+use crate::{
+    ast::{
+        BinOp, Expr, ExprKind, Function, Ident, Node, Param, Stat, StatKind, Type, TypeExpr,
+        TypeExprKind, UnOp,
+    },
+    error::{Error, ErrorKind, LexicalErrorKind, Result, SyntaxErrorKind, MAX_ERRORS},
+    lex::{self, Lexer},
+    span::{Pos, Span},
+    token::{Literal, Token, TokenType},
+};
 
+/// The Kobe parser. Consumes tokens from a [`Lexer`] and lazily produces the
+/// function definitions making up a Kobe program through its implementation of
+/// [`Iterator`].
+///
+/// Upon encountering an error, the parser will skip ahead to a point from
+/// which parsing can sensibly resume so that as many errors as possible (up to
+/// [`MAX_ERRORS`]) are reported. Functions containing errors are not produced.
 pub struct Parser<R> {
     tokens: Lexer<R>,
+    peeked_token: Option<Token>,
+    /// Doc comments read since the last token other than a doc comment or end
+    /// of statement.
+    doc_lines: Vec<String>,
+    /// Doc comments which preceded the most recently read token.
+    doc: Option<String>,
+    /// Span of the most recently consumed token other than an end of
+    /// statement, which is where the node currently being parsed ends.
+    previous_span: Span,
+    /// Errors which have been recovered from but not yet produced by the
+    /// iterator.
+    pending_errors: VecDeque<Error>,
+    /// Number of errors encountered, including any beyond [`MAX_ERRORS`]
+    /// which were dropped.
+    error_count: usize,
+    /// Whether the input could not be read, after which any further errors
+    /// would only be a consequence of the input being cut short.
+    input_failed: bool,
+    /// Total time spent parsing so far, including lexing.
+    elapsed: Duration,
 }
 
-impl<R> Parser<R> {
+impl<R: Read> Parser<R> {
     pub fn new(tokens: Lexer<R>) -> Self {
-        Parser { tokens }
+        Parser {
+            tokens,
+            peeked_token: None,
+            doc_lines: Vec::new(),
+            doc: None,
+            previous_span: Span {
+                start: Pos { line: 1, column: 0 },
+                end: Pos { line: 1, column: 0 },
+                byte_range: 0..0,
+            },
+            pending_errors: VecDeque::new(),
+            error_count: 0,
+            input_failed: false,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    /// Read the next token from the lexer. Doc comments are not produced as
+    /// tokens but are instead collected so that they can be attached to the
+    /// item which they precede.
+    fn read_token(&mut self) -> Result<Option<Token>> {
+        loop {
+            let token = self.tokens.next().transpose()?;
+
+            match token.as_ref().map(|t| t.tok_type) {
+                Some(TokenType::DocComment) => {
+                    let lexeme = token.unwrap().lexeme;
+                    let text = lexeme.trim_start_matches('-');
+                    let text = text.strip_prefix(' ').unwrap_or(text);
+                    self.doc_lines.push(text.trim_end().to_string());
+                }
+                Some(TokenType::EndStatement) => return Ok(token),
+                _ => {
+                    self.doc = if self.doc_lines.is_empty() {
+                        None
+                    } else {
+                        Some(self.doc_lines.drain(..).collect::<Vec<_>>().join("\n"))
+                    };
+                    return Ok(token);
+                }
+            }
+        }
+    }
+
+    /// Take the doc comments (if any) preceding the next token.
+    fn take_doc(&mut self) -> Result<Option<String>> {
+        self.peek_type()?;
+        Ok(self.doc.take())
+    }
+
+    /// Peek the type of the next token without consuming it. Will return `None`
+    /// if the end of the token stream has been reached.
+    fn peek_type(&mut self) -> Result<Option<TokenType>> {
+        if self.peeked_token.is_none() {
+            self.peeked_token = self.read_token()?;
+        }
+        Ok(self.peeked_token.as_ref().map(|t| t.tok_type))
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>> {
+        let token = match self.peeked_token.take() {
+            Some(token) => Some(token),
+            None => self.read_token()?,
+        };
+
+        if let Some(token) = &token {
+            if token.tok_type != TokenType::EndStatement {
+                self.previous_span = token.span.clone();
+            }
+        }
+
+        Ok(token)
+    }
+
+    /// Consume the next token, producing an error if the end of the token
+    /// stream has been reached.
+    fn expect_next_token(&mut self) -> Result<Token> {
+        match self.next_token()? {
+            Some(token) => Ok(token),
+            None => Err(self.error_at_end(SyntaxErrorKind::UnexpectedEof {
+                expected: Vec::new(),
+            })),
+        }
+    }
+
+    /// Consume the next token only if it is of the given type.
+    fn next_token_if(&mut self, tok_type: TokenType) -> Result<Option<Token>> {
+        if self.peek_type()? == Some(tok_type) {
+            self.next_token()
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Consume the next token, producing an error if it is not of the given
+    /// type.
+    fn expect(&mut self, tok_type: TokenType) -> Result<Token> {
+        self.expect_one_of(&[tok_type])
+    }
+
+    /// Consume the next token, producing an error if it is not one of the given
+    /// types.
+    fn expect_one_of(&mut self, expected: &[TokenType]) -> Result<Token> {
+        if self.peek_type()?.is_some_and(|t| expected.contains(&t)) {
+            self.expect_next_token()
+        } else {
+            Err(self.unexpected_token_error(expected))
+        }
+    }
+
+    fn skip_end_statements(&mut self) -> Result<()> {
+        while self.next_token_if(TokenType::EndStatement)?.is_some() {}
+        Ok(())
+    }
+
+    fn record_error(&mut self, error: Error) {
+        if self.input_failed {
+            return;
+        }
+        if matches!(error.kind, ErrorKind::Io { .. }) {
+            self.input_failed = true;
+        }
+
+        if self.error_count < MAX_ERRORS {
+            self.pending_errors.push_back(error);
+        }
+        self.error_count += 1;
+    }
+
+    /// Skip tokens until reaching a point from which statements can continue
+    /// to be parsed. That is, just after the end of the current statement or
+    /// just before an `end`, `fn`, `pub` or the end of input.
+    fn synchronise(&mut self) {
+        loop {
+            match self.peek_type() {
+                Ok(Some(TokenType::EndStatement)) => {
+                    self.peeked_token = None;
+                    return;
+                }
+                Ok(Some(TokenType::EndKeyword) | None) => return,
+                Ok(Some(tok_type)) if begins_function(tok_type) => return,
+                Ok(Some(_)) => self.peeked_token = None,
+                Err(e) => self.record_error(e),
+            }
+        }
+    }
+
+    /// Skip tokens until reaching the next function definition or the end of
+    /// input.
+    fn synchronise_at_function(&mut self) {
+        loop {
+            match self.peek_type() {
+                Ok(None) => return,
+                Ok(Some(tok_type)) if begins_function(tok_type) => return,
+                Ok(Some(_)) => self.peeked_token = None,
+                Err(e) => self.record_error(e),
+            }
+        }
+    }
+
+    fn parse_function(&mut self) -> Result<Function> {
+        let doc = self.take_doc()?;
+        let pub_token = self.next_token_if(TokenType::PubKeyword)?;
+        let fn_token = self.expect(TokenType::FnKeyword)?;
+        let name = self.parse_ident()?;
+
+        let open_bracket = self.expect(TokenType::OpenBracket)?;
+        let mut params = Vec::new();
+        if self.next_token_if(TokenType::CloseBracket)?.is_none() {
+            loop {
+                params.push(self.parse_param()?);
+
+                let token = self.expect_one_of(&[TokenType::Comma, TokenType::CloseBracket])?;
+                if token.tok_type == TokenType::CloseBracket {
+                    break;
+                }
+            }
+        }
+        let params_span = self.span_from(&open_bracket.span);
+
+        let token = self.expect_one_of(&[TokenType::Arrow, TokenType::EndStatement])?;
+        let return_type = if token.tok_type == TokenType::Arrow {
+            let return_type = self.parse_type()?;
+            self.expect(TokenType::EndStatement)?;
+            Some(return_type)
+        } else {
+            None
+        };
+
+        let body = self.parse_block(&[TokenType::EndKeyword], &fn_token)?;
+        self.expect(TokenType::EndKeyword)?;
+
+        let start = pub_token.as_ref().unwrap_or(&fn_token);
+        Ok(Function {
+            name,
+            doc,
+            public: pub_token.is_some(),
+            params,
+            params_span,
+            return_type,
+            returns: Type::Unknown,
+            body,
+            span: self.span_from(&start.span),
+        })
+    }
+
+    fn parse_param(&mut self) -> Result<Param> {
+        let name = self.parse_ident()?;
+        self.expect(TokenType::Colon)?;
+        let param_type = self.parse_type()?;
+        Ok(Param {
+            name,
+            param_type,
+            ty: Type::Unknown,
+        })
+    }
+
+    fn parse_type(&mut self) -> Result<TypeExpr> {
+        let token = self.expect_one_of(&[TokenType::Identifier, TokenType::OpenSquare])?;
+
+        let kind = if token.tok_type == TokenType::OpenSquare {
+            let inner = self.parse_type()?;
+            self.expect(TokenType::CloseSquare)?;
+            TypeExprKind::List(Box::new(inner))
+        } else {
+            TypeExprKind::Named(token.lexeme.clone())
+        };
+
+        Ok(node_at(kind, self.span_from(&token.span)))
+    }
+
+    fn parse_ident(&mut self) -> Result<Ident> {
+        let token = self.expect(TokenType::Identifier)?;
+        Ok(Ident {
+            name: token.lexeme,
+            span: token.span,
+        })
+    }
+
+    /// Parse a sequence of statements up until (but not including) a token of
+    /// one of the given terminating types. The token that opened the block is
+    /// used to report a missing `end` should the input (or function) end before
+    /// the block does. Errors in individual statements are recorded and
+    /// recovered from.
+    fn parse_block(&mut self, terminators: &[TokenType], opened_by: &Token) -> Result<Vec<Stat>> {
+        let mut stats = Vec::new();
+
+        loop {
+            match self.skip_end_statements().and_then(|_| self.peek_type()) {
+                Ok(Some(tok_type)) if terminators.contains(&tok_type) => return Ok(stats),
+                // a function cannot be defined within a block so assume that
+                // the block was not properly closed
+                Ok(Some(TokenType::FnKeyword | TokenType::PubKeyword) | None) => {
+                    let kind = SyntaxErrorKind::MissingEnd {
+                        opened_by: opened_by.tok_type,
+                        opened_at: opened_by.span.start.line,
+                    };
+                    // insert the 'end' on a line of its own, before the next
+                    // function or after the end of the input
+                    let (span, fix_span, fix) = match &self.peeked_token {
+                        Some(token) => (
+                            self.token_error_span(token),
+                            token.span.preceding(),
+                            "end\n",
+                        ),
+                        None => {
+                            let span = self.previous_span.following();
+                            (span.clone(), span, "\nend")
+                        }
+                    };
+                    let error = Error::new(ErrorKind::Syntax(kind), span)
+                        .with_label("expected 'end' before here")
+                        .with_secondary_label(
+                            opened_by.span.clone(),
+                            format!("this {} is never closed", opened_by.tok_type),
+                        )
+                        .with_suggestion(
+                            fix_span,
+                            fix,
+                            format!("add 'end' to close the {}", opened_by.tok_type),
+                        );
+                    return Err(self.tokens.with_source(error));
+                }
+                Ok(Some(_)) => match self.parse_stat() {
+                    Ok(stat) => stats.push(stat),
+                    Err(e) => {
+                        self.record_error(e);
+                        self.synchronise();
+                    }
+                },
+                Err(e) => self.record_error(e),
+            }
+        }
+    }
+
+    fn parse_stat(&mut self) -> Result<Stat> {
+        match self.peek_type()? {
+            Some(TokenType::LetKeyword) => self.parse_let(),
+            Some(TokenType::ReturnKeyword) => self.parse_return(),
+            Some(TokenType::IfKeyword) => self.parse_if(),
+            Some(TokenType::WhileKeyword) => self.parse_while(),
+            Some(TokenType::ForKeyword) => self.parse_for(),
+            _ => self.parse_expr_or_assign(),
+        }
+    }
+
+    fn parse_let(&mut self) -> Result<Stat> {
+        let doc = self.take_doc()?;
+        let let_token = self.expect(TokenType::LetKeyword)?;
+
+        let name = self.parse_ident()?;
+
+        // type annotation is optional as it can instead be inferred from the
+        // initial value
+        let mut token =
+            self.expect_one_of(&[TokenType::Colon, TokenType::Assign, TokenType::EndStatement])?;
+        let var_type = if token.tok_type == TokenType::Colon {
+            let var_type = self.parse_type()?;
+            token = self.expect_one_of(&[TokenType::Assign, TokenType::EndStatement])?;
+            Some(var_type)
+        } else {
+            None
+        };
+
+        let value = if token.tok_type == TokenType::Assign {
+            let value = self.parse_expr()?;
+            self.expect(TokenType::EndStatement)?;
+            Some(value)
+        } else {
+            None
+        };
+
+        Ok(node_at(
+            StatKind::Let {
+                name,
+                doc,
+                var_type,
+                value,
+                ty: Type::Unknown,
+            },
+            self.span_from(&let_token.span),
+        ))
+    }
+
+    fn parse_return(&mut self) -> Result<Stat> {
+        let return_token = self.expect(TokenType::ReturnKeyword)?;
+        let value = self.parse_expr()?;
+        self.expect(TokenType::EndStatement)?;
+
+        Ok(node_at(
+            StatKind::Return(value),
+            self.span_from(&return_token.span),
+        ))
+    }
+
+    fn parse_if(&mut self) -> Result<Stat> {
+        let if_token = self.expect(TokenType::IfKeyword)?;
+        let condition = self.parse_expr()?;
+        self.expect(TokenType::ThenKeyword)?;
+
+        let then_body =
+            self.parse_block(&[TokenType::ElseKeyword, TokenType::EndKeyword], &if_token)?;
+        let else_body = if self.next_token_if(TokenType::ElseKeyword)?.is_some() {
+            self.parse_block(&[TokenType::EndKeyword], &if_token)?
+        } else {
+            Vec::new()
+        };
+        self.expect(TokenType::EndKeyword)?;
+
+        Ok(node_at(
+            StatKind::If {
+                condition,
+                then_body,
+                else_body,
+            },
+            self.span_from(&if_token.span),
+        ))
+    }
+
+    fn parse_while(&mut self) -> Result<Stat> {
+        let while_token = self.expect(TokenType::WhileKeyword)?;
+        let condition = self.parse_expr()?;
+        self.expect(TokenType::DoKeyword)?;
+
+        let body = self.parse_block(&[TokenType::EndKeyword], &while_token)?;
+        self.expect(TokenType::EndKeyword)?;
+
+        Ok(node_at(
+            StatKind::While { condition, body },
+            self.span_from(&while_token.span),
+        ))
+    }
+
+    fn parse_for(&mut self) -> Result<Stat> {
+        let for_token = self.expect(TokenType::ForKeyword)?;
+        let var = self.parse_ident()?;
+        self.expect(TokenType::InKeyword)?;
+
+        let mut iterable = self.parse_expr()?;
+        if self.next_token_if(TokenType::DotDot)?.is_some() {
+            let end = self.parse_expr()?;
+            let span = iterable.span.to(&end.span);
+            iterable = expr_at(
+                ExprKind::Range {
+                    start: Box::new(iterable),
+                    end: Box::new(end),
+                },
+                span,
+            );
+        }
+
+        self.expect(TokenType::DoKeyword)?;
+
+        let body = self.parse_block(&[TokenType::EndKeyword], &for_token)?;
+        self.expect(TokenType::EndKeyword)?;
+
+        Ok(node_at(
+            StatKind::For {
+                var,
+                iterable,
+                body,
+            },
+            self.span_from(&for_token.span),
+        ))
+    }
+
+    /// Parse either an expression statement or, if the expression is followed
+    /// by an assignment operator, an assignment to an identifier.
+    fn parse_expr_or_assign(&mut self) -> Result<Stat> {
+        let expr = self.parse_expr()?;
+
+        let stat = match self.peek_type()?.and_then(assignment_operator) {
+            Some(op) => {
+                self.expect_next_token()?;
+
+                let target = match expr.kind {
+                    ExprKind::Identifier(name) => Ident {
+                        name,
+                        span: expr.span,
+                    },
+                    _ => {
+                        let kind = ErrorKind::Syntax(SyntaxErrorKind::InvalidAssignmentTarget);
+                        let error = Error::new(kind, expr.span).with_label("cannot be assigned to");
+                        return Err(self.tokens.with_source(error));
+                    }
+                };
+                let value = self.parse_expr()?;
+
+                let span = target.span.to(&value.span);
+                let kind = match op {
+                    Some(op) => StatKind::CompoundAssign { target, op, value },
+                    None => StatKind::Assign { target, value },
+                };
+                node_at(kind, span)
+            }
+            None => {
+                let span = expr.span.clone();
+                node_at(StatKind::Expr(expr), span)
+            }
+        };
+        self.expect(TokenType::EndStatement)?;
+
+        Ok(stat)
+    }
+
+    fn parse_expr(&mut self) -> Result<Expr> {
+        self.parse_binary(BinOp::LOWEST_PRECEDENCE)
+    }
+
+    /// Parse an expression using precedence climbing. Only binary operators
+    /// with a precedence of at least `min_precedence` will be consumed, with
+    /// operators of equal precedence being grouped from left to right.
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expr> {
+        let mut left = self.parse_unary()?;
+
+        while let Some(op) = self.peek_type()?.and_then(binary_operator) {
+            if op.precedence() < min_precedence {
+                break;
+            }
+
+            self.expect_next_token()?;
+            let right = self.parse_binary(op.precedence() + 1)?;
+
+            let span = left.span.to(&right.span);
+            left = expr_at(
+                ExprKind::Binary {
+                    op,
+                    left: Box::new(left),
+                    right: Box::new(right),
+                },
+                span,
+            );
+        }
+
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr> {
+        let op = match self.peek_type()? {
+            Some(TokenType::Minus) => UnOp::Negate,
+            Some(TokenType::Not) => UnOp::Not,
+            _ => return self.parse_postfix(),
+        };
+
+        let op_token = self.expect_next_token()?;
+
+        // the smallest integer of each type can only be written as a negated
+        // literal whose magnitude would otherwise be out of range, so these
+        // are negated here rather than at run time
+        if op == UnOp::Negate
+            && self.peek_type()? == Some(TokenType::IntLiteral)
+            && self
+                .peeked_token
+                .as_ref()
+                .is_some_and(|token| lex::negation_only_type(token).is_some())
+        {
+            let token = self.expect_next_token()?;
+            let Some(Literal::Int(magnitude)) = token.value else {
+                unreachable!("integer literal token without an integer value");
+            };
+            let span = op_token.span.to(&token.span);
+            return Ok(expr_at(
+                ExprKind::IntLiteral(magnitude.wrapping_neg()),
+                span,
+            ));
+        }
+
+        let operand = self.parse_unary()?;
+
+        let span = op_token.span.to(&operand.span);
+        Ok(expr_at(
+            ExprKind::Unary {
+                op,
+                operand: Box::new(operand),
+            },
+            span,
+        ))
+    }
+
+    /// Parse a primary expression followed by any number of indexing
+    /// operations (e.g., `xs[i][j]`).
+    fn parse_postfix(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_primary()?;
+
+        while self.next_token_if(TokenType::OpenSquare)?.is_some() {
+            let index = self.parse_expr()?;
+            self.expect(TokenType::CloseSquare)?;
+
+            let span = self.span_from(&expr.span);
+            expr = expr_at(
+                ExprKind::Index {
+                    target: Box::new(expr),
+                    index: Box::new(index),
+                },
+                span,
+            );
+        }
+
+        Ok(expr)
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr> {
+        self.peek_type()?;
+        if let Some(token) = self
+            .peeked_token
+            .clone()
+            .filter(|t| !begins_expression(t.tok_type))
+        {
+            let kind = SyntaxErrorKind::ExpectedExpression {
+                found: token.tok_type,
+            };
+            return Err(self.error_at_token(kind, &token));
+        }
+
+        let token = self.expect_next_token()?;
+
+        if let Some(ty) = lex::negation_only_type(&token) {
+            let kind = ErrorKind::Lexical(LexicalErrorKind::NumberLiteralOutOfRange(ty));
+            let error = lex::with_hints(Error::new(kind, token.span.clone()));
+            return Err(self.tokens.with_source(error));
+        }
+
+        let kind = match token.tok_type {
+            TokenType::IntLiteral
+            | TokenType::FloatLiteral
+            | TokenType::CharLiteral
+            | TokenType::StringLiteral => match token.value.clone() {
+                Some(Literal::Int(value)) => ExprKind::IntLiteral(value),
+                Some(Literal::Float(value)) => ExprKind::FloatLiteral(value),
+                Some(Literal::Char(value)) => ExprKind::CharLiteral(value),
+                Some(Literal::String(value)) => ExprKind::StringLiteral(value),
+                None => unreachable!("literal token without a value"),
+            },
+            TokenType::StringStart => ExprKind::Interpolation(self.parse_interpolation(&token)?),
+            TokenType::TrueKeyword => ExprKind::BoolLiteral(true),
+            TokenType::FalseKeyword => ExprKind::BoolLiteral(false),
+            TokenType::Identifier => {
+                if self.next_token_if(TokenType::OpenBracket)?.is_some() {
+                    ExprKind::Call {
+                        function: token.lexeme.clone(),
+                        args: self.parse_exprs(TokenType::CloseBracket)?,
+                    }
+                } else {
+                    ExprKind::Identifier(token.lexeme.clone())
+                }
+            }
+            TokenType::OpenBracket => {
+                let mut expr = self.parse_expr()?;
+                self.expect(TokenType::CloseBracket)?;
+                // include the brackets so that, e.g., `(a + b) * c` is
+                // covered in its entirety
+                expr.span = self.span_from(&token.span);
+                return Ok(expr);
+            }
+            TokenType::OpenSquare => ExprKind::List(self.parse_exprs(TokenType::CloseSquare)?),
+            _ => unreachable!("token does not begin an expression"),
+        };
+
+        Ok(expr_at(kind, self.span_from(&token.span)))
+    }
+
+    /// Parse the remainder of an interpolated string, the first segment of
+    /// which is the given token, producing its parts. Empty segments are
+    /// omitted.
+    fn parse_interpolation(&mut self, start: &Token) -> Result<Vec<Expr>> {
+        let mut parts = Vec::new();
+        let mut segment = start.clone();
+
+        loop {
+            if let Some(Literal::String(value)) = &segment.value {
+                if !value.is_empty() {
+                    parts.push(expr_at(
+                        ExprKind::StringLiteral(value.clone()),
+                        segment.span.clone(),
+                    ));
+                }
+            }
+            if segment.tok_type == TokenType::StringEnd {
+                return Ok(parts);
+            }
+
+            parts.push(self.parse_expr()?);
+
+            segment = match self.peek_type()? {
+                Some(TokenType::StringMiddle | TokenType::StringEnd) => self.expect_next_token()?,
+                _ => return Err(self.unexpected_token_error(&[TokenType::StringEnd])),
+            };
+        }
+    }
+
+    /// Parse a comma-separated (and possibly empty) sequence of expressions
+    /// up to and including the given closing token.
+    fn parse_exprs(&mut self, close: TokenType) -> Result<Vec<Expr>> {
+        let mut exprs = Vec::new();
+        if self.next_token_if(close)?.is_none() {
+            loop {
+                exprs.push(self.parse_expr()?);
+
+                let token = self.expect_one_of(&[TokenType::Comma, close])?;
+                if token.tok_type == close {
+                    break;
+                }
+            }
+        }
+        Ok(exprs)
+    }
+
+    /// Parse the entire input, producing either every function or every
+    /// error encountered.
+    pub fn parse_all(&mut self) -> std::result::Result<Vec<Function>, Vec<Error>> {
+        let mut functions = Vec::new();
+        let mut errors = Vec::new();
+
+        for result in self {
+            match result {
+                Ok(function) => functions.push(function),
+                Err(e) => errors.push(e),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(functions)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Whether parsing was abandoned after more than [`MAX_ERRORS`] errors,
+    /// so that some were not reported.
+    pub fn errors_truncated(&self) -> bool {
+        self.error_count > MAX_ERRORS
+    }
+
+    /// The lexer from which tokens are read.
+    pub fn lexer(&self) -> &Lexer<R> {
+        &self.tokens
+    }
+
+    pub fn lexer_mut(&mut self) -> &mut Lexer<R> {
+        &mut self.tokens
+    }
+
+    /// The total time spent parsing so far, including lexing.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Attach the source lines covered by an error's spans to it, as for
+    /// [`Lexer::with_source`].
+    pub fn with_source(&mut self, error: Error) -> Error {
+        self.tokens.with_source(error)
+    }
+
+    /// The span from the start of the given span up to the end of the most
+    /// recently consumed token (excluding any end of statement).
+    fn span_from(&self, start: &Span) -> Span {
+        start.to(&self.previous_span)
+    }
+
+    /// Produce an error for the next token (which must have already been
+    /// peeked) not being one of the expected types. The token is not consumed.
+    fn unexpected_token_error(&mut self, expected: &[TokenType]) -> Error {
+        let expected = expected.to_vec();
+
+        match self.peeked_token.clone() {
+            Some(token) => {
+                let kind = SyntaxErrorKind::UnexpectedToken {
+                    expected,
+                    found: token.tok_type,
+                };
+                self.error_at_token(kind, &token)
+            }
+            None => self.error_at_end(SyntaxErrorKind::UnexpectedEof { expected }),
+        }
+    }
+
+    fn error_at_token(&mut self, kind: SyntaxErrorKind, token: &Token) -> Error {
+        let span = self.token_error_span(token);
+        self.tokens.error_at(ErrorKind::Syntax(kind), span)
+    }
+
+    /// The span to report an error at a token with.
+    fn token_error_span(&self, token: &Token) -> Span {
+        // end statement tokens may span several lines, or be preceded by
+        // whitespace and comments, so instead point just past the end of the
+        // preceding token
+        if token.tok_type == TokenType::EndStatement {
+            self.previous_span.following()
+        } else {
+            token.span.clone()
+        }
+    }
+
+    fn error_at_end(&mut self, kind: SyntaxErrorKind) -> Error {
+        let span = self.previous_span.following();
+        self.tokens.error_at(ErrorKind::Syntax(kind), span)
     }
 }
 
 impl<R: Read> Iterator for Parser<R> {
-    type Item = Node;
+    type Item = Result<Function>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        for token in &mut self.tokens {
-            match token {
-                Ok(token) => println!("{}", token),
-                Err(e) => {
-                    println!("{}", e);
-                    break;
+        let start = Instant::now();
+        let function = self.parse_next();
+        self.elapsed += start.elapsed();
+        function
+    }
+}
+
+impl<R: Read> Parser<R> {
+    /// Produce the next function or error, as for [`Iterator::next`].
+    fn parse_next(&mut self) -> Option<Result<Function>> {
+        loop {
+            if let Some(error) = self.pending_errors.pop_front() {
+                return Some(Err(error));
+            }
+
+            // parsing continues after the last error which can be reported
+            // so as to find out whether any errors are dropped
+            if self.error_count > MAX_ERRORS || self.input_failed {
+                return None;
+            }
+
+            match self.skip_end_statements().and_then(|_| self.peek_type()) {
+                Ok(Some(tok_type)) if begins_function(tok_type) => {
+                    let error_count = self.error_count;
+
+                    match self.parse_function() {
+                        Ok(function) if self.error_count == error_count => {
+                            return Some(Ok(function))
+                        }
+                        Ok(_) => {}
+                        Err(e) => {
+                            self.record_error(e);
+                            self.synchronise_at_function();
+                        }
+                    }
                 }
+                Ok(Some(_)) => {
+                    let e = self.unexpected_token_error(&[TokenType::FnKeyword]);
+                    self.record_error(e);
+                    self.synchronise_at_function();
+                }
+                Ok(None) => return None,
+                Err(e) => self.record_error(e),
             }
         }
-        unimplemented!()
+    }
+}
+
+fn node_at<K>(kind: K, span: Span) -> Node<K> {
+    Node { kind, span }
+}
+
+fn expr_at(kind: ExprKind, span: Span) -> Expr {
+    Expr {
+        kind,
+        ty: Type::Unknown,
+        span,
+    }
+}
+
+/// Whether a token of the given type can appear at the start of a function
+/// definition.
+fn begins_function(tok_type: TokenType) -> bool {
+    matches!(tok_type, TokenType::FnKeyword | TokenType::PubKeyword)
+}
+
+/// Whether a token of the given type can appear at the start of an expression.
+fn begins_expression(tok_type: TokenType) -> bool {
+    matches!(
+        tok_type,
+        TokenType::IntLiteral
+            | TokenType::FloatLiteral
+            | TokenType::CharLiteral
+            | TokenType::StringLiteral
+            | TokenType::StringStart
+            | TokenType::TrueKeyword
+            | TokenType::FalseKeyword
+            | TokenType::Identifier
+            | TokenType::OpenBracket
+            | TokenType::OpenSquare
+            | TokenType::Minus
+            | TokenType::Not
+    )
+}
+
+/// Get the binary operator a token represents (if any).
+fn binary_operator(tok_type: TokenType) -> Option<BinOp> {
+    match tok_type {
+        TokenType::Equivalent => Some(BinOp::Equivalent),
+        TokenType::NotEquivalent => Some(BinOp::NotEquivalent),
+        TokenType::AndKeyword => Some(BinOp::And),
+        TokenType::OrKeyword => Some(BinOp::Or),
+        TokenType::LessThan => Some(BinOp::LessThan),
+        TokenType::GreaterThan => Some(BinOp::GreaterThan),
+        TokenType::LessThanOrEqual => Some(BinOp::LessThanOrEqual),
+        TokenType::GreaterThanOrEqual => Some(BinOp::GreaterThanOrEqual),
+        TokenType::Plus => Some(BinOp::Add),
+        TokenType::Minus => Some(BinOp::Subtract),
+        TokenType::Times => Some(BinOp::Multiply),
+        TokenType::Divide => Some(BinOp::Divide),
+        _ => None,
+    }
+}
+
+/// Get the assignment operator a token represents (if any). The inner value is
+/// the binary operator applied by a compound assignment (`None` for plain `=`).
+fn assignment_operator(tok_type: TokenType) -> Option<Option<BinOp>> {
+    match tok_type {
+        TokenType::Assign => Some(None),
+        TokenType::PlusAssign => Some(Some(BinOp::Add)),
+        TokenType::MinusAssign => Some(Some(BinOp::Subtract)),
+        TokenType::TimesAssign => Some(Some(BinOp::Multiply)),
+        TokenType::DivideAssign => Some(Some(BinOp::Divide)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Cursor};
+
+    use super::*;
+
+    macro_rules! assert_syntax_error {
+        ($input:literal, $kind:expr, $line_no:literal, $char_no:literal, $error_line:literal) => {
+            let error = first_error($input);
+            let start = Pos {
+                line: $line_no,
+                column: $char_no,
+            };
+            assert_eq!(
+                (
+                    error.kind,
+                    error.span.start,
+                    error.source_lines[&error.span.start.line].clone()
+                ),
+                (ErrorKind::Syntax($kind), start, $error_line.to_string())
+            );
+        };
+    }
+
+    fn parse(input: &str) -> Vec<Result<Function>> {
+        let lexer = Lexer::new(Cursor::new(input), "test".to_string());
+        Parser::new(lexer).collect()
+    }
+
+    fn first_error(input: &str) -> Error {
+        parse(input).into_iter().find_map(Result::err).unwrap()
+    }
+
+    /// Parse the given input, returning the line and column at which each
+    /// error encountered starts.
+    fn error_positions(input: &str) -> Vec<(usize, usize)> {
+        parse(input)
+            .into_iter()
+            .filter_map(Result::err)
+            .map(|e| (e.span.start.line, e.span.start.column))
+            .collect()
+    }
+
+    /// Parse the given statements as the body of a function.
+    fn parse_body(input: &str) -> Vec<Stat> {
+        let mut functions = parse(&format!("fn f()\n{input}\nend"));
+        assert_eq!(functions.len(), 1);
+        functions.pop().unwrap().unwrap().body
+    }
+
+    /// Parse a single expression statement.
+    fn parse_expr(input: &str) -> Expr {
+        let mut body = parse_body(input);
+        assert_eq!(body.len(), 1);
+        match body.pop().unwrap().kind {
+            StatKind::Expr(expr) => expr,
+            kind => panic!("expected expression statement but got {kind:?}"),
+        }
+    }
+
+    /// Render an expression made up of identifiers and operators as an
+    /// S-expression so that its structure can be easily checked.
+    fn sexpr(expr: &Expr) -> String {
+        match &expr.kind {
+            ExprKind::Identifier(name) => name.clone(),
+            ExprKind::Binary { op, left, right } => {
+                let op = match op {
+                    BinOp::Equivalent => "==",
+                    BinOp::NotEquivalent => "!=",
+                    BinOp::And => "and",
+                    BinOp::Or => "or",
+                    BinOp::LessThan => "<",
+                    BinOp::GreaterThan => ">",
+                    BinOp::LessThanOrEqual => "<=",
+                    BinOp::GreaterThanOrEqual => ">=",
+                    BinOp::Add => "+",
+                    BinOp::Subtract => "-",
+                    BinOp::Multiply => "*",
+                    BinOp::Divide => "/",
+                };
+                format!("({op} {} {})", sexpr(left), sexpr(right))
+            }
+            ExprKind::Unary { op, operand } => {
+                let op = match op {
+                    UnOp::Negate => "-",
+                    UnOp::Not => "!",
+                };
+                format!("({op} {})", sexpr(operand))
+            }
+            ExprKind::Call { function, args } => {
+                let args: Vec<_> = args.iter().map(sexpr).collect();
+                format!("{function}({})", args.join(", "))
+            }
+            ExprKind::Index { target, index } => format!("{}[{}]", sexpr(target), sexpr(index)),
+            ExprKind::IntLiteral(value) => value.to_string(),
+            ExprKind::StringLiteral(value) => format!("{value:?}"),
+            ExprKind::Interpolation(parts) => {
+                let parts: Vec<_> = parts.iter().map(sexpr).collect();
+                format!("(str {})", parts.join(" "))
+            }
+            kind => panic!("unexpected expression {kind:?}"),
+        }
+    }
+
+    /// The span of columns `start` to `end` (inclusive) on a line of ASCII
+    /// input which begins at the byte offset `line_offset`.
+    fn span(line: usize, start: usize, end: usize, line_offset: usize) -> Span {
+        Span {
+            start: Pos {
+                line,
+                column: start,
+            },
+            end: Pos { line, column: end },
+            byte_range: line_offset + start - 1..line_offset + end,
+        }
+    }
+
+    /// As [`span`], but on the first line of a function body parsed by
+    /// [`parse_body`] (i.e., the second line of input).
+    fn body_span(start: usize, end: usize) -> Span {
+        span(2, start, end, "fn f()\n".len())
+    }
+
+    fn ident(name: &str, span: Span) -> Ident {
+        Ident {
+            name: name.to_string(),
+            span,
+        }
+    }
+
+    fn expr(kind: ExprKind, span: Span) -> Expr {
+        Expr {
+            kind,
+            ty: Type::Unknown,
+            span,
+        }
+    }
+
+    fn named_type(name: &str, span: Span) -> TypeExpr {
+        Node {
+            kind: TypeExprKind::Named(name.to_string()),
+            span,
+        }
+    }
+
+    #[test]
+    fn functions() {
+        assert!(parse("").is_empty());
+        assert!(parse("\n;\n").is_empty());
+
+        let expected = Function {
+            name: ident("f", span(2, 4, 4, 1)),
+            doc: None,
+            public: false,
+            params: vec![
+                Param {
+                    name: ident("a", span(2, 6, 6, 1)),
+                    param_type: named_type("int", span(2, 9, 11, 1)),
+                    ty: Type::Unknown,
+                },
+                Param {
+                    name: ident("bc", span(2, 14, 15, 1)),
+                    param_type: named_type("float", span(2, 18, 22, 1)),
+                    ty: Type::Unknown,
+                },
+            ],
+            params_span: span(2, 5, 23, 1),
+            return_type: Some(named_type("bool", span(2, 28, 31, 1))),
+            returns: Type::Unknown,
+            body: Vec::new(),
+            span: span(2, 1, 1, 1).to(&span(3, 1, 3, 33)),
+        };
+        assert_eq!(
+            parse("\nfn f(a: int, bc: float) -> bool\nend\n"),
+            vec![Ok(expected)]
+        );
+
+        let functions = parse("fn f()\nend\npub fn g(x: int)\n\nend");
+        assert_eq!(functions.len(), 2);
+        assert!(matches!(
+            functions.as_slice(),
+            [
+                Ok(Function { public: false, .. }),
+                Ok(Function { public: true, .. })
+            ]
+        ));
+    }
+
+    #[test]
+    fn doc_comments() {
+        let functions = parse(
+            "--- Adds one.\n---\n---   Indented.\npub fn f()\n--- x\nlet x = 1\nend\n\nfn g()\nend",
+        );
+        let f = functions[0].as_ref().unwrap();
+        assert_eq!(f.doc.as_deref(), Some("Adds one.\n\n  Indented."));
+        assert!(matches!(
+            &f.body[0].kind,
+            StatKind::Let { doc: Some(doc), .. } if doc == "x"
+        ));
+        assert_eq!(functions[1].as_ref().unwrap().doc, None);
+
+        // doc comments which do not precede a function or variable are ignored
+        let functions = parse("fn f()\n--- ignored\nx = 1\nlet y = 2\nend\n--- g\n\nfn g()\nend");
+        let f = functions[0].as_ref().unwrap();
+        assert!(matches!(f.body[1].kind, StatKind::Let { doc: None, .. }));
+        assert_eq!(functions[1].as_ref().unwrap().doc.as_deref(), Some("g"));
+    }
+
+    #[test]
+    fn type_annotations() {
+        let functions = parse("fn f(xs: [[int]]) -> [bool]\nend");
+        let function = functions[0].as_ref().unwrap();
+
+        let expected = Node {
+            kind: TypeExprKind::List(Box::new(Node {
+                kind: TypeExprKind::List(Box::new(named_type("int", span(1, 12, 14, 0)))),
+                span: span(1, 11, 15, 0),
+            })),
+            span: span(1, 10, 16, 0),
+        };
+        assert_eq!(function.params[0].param_type, expected);
+        assert_eq!(
+            function.return_type.as_ref().unwrap().kind.to_string(),
+            "[bool]"
+        );
+    }
+
+    #[test]
+    fn simple_statements() {
+        let body =
+            parse_body("let x: int\nlet y: float = 1.5\nx = 2; x += 3\nreturn x\nlet z = 'z'");
+
+        let expected = vec![
+            Node {
+                kind: StatKind::Let {
+                    name: ident("x", span(2, 5, 5, 7)),
+                    doc: None,
+                    var_type: Some(named_type("int", span(2, 8, 10, 7))),
+                    value: None,
+                    ty: Type::Unknown,
+                },
+                span: span(2, 1, 10, 7),
+            },
+            Node {
+                kind: StatKind::Let {
+                    name: ident("y", span(3, 5, 5, 18)),
+                    doc: None,
+                    var_type: Some(named_type("float", span(3, 8, 12, 18))),
+                    value: Some(expr(ExprKind::FloatLiteral(1.5), span(3, 16, 18, 18))),
+                    ty: Type::Unknown,
+                },
+                span: span(3, 1, 18, 18),
+            },
+            Node {
+                kind: StatKind::Assign {
+                    target: ident("x", span(4, 1, 1, 37)),
+                    value: expr(ExprKind::IntLiteral(2), span(4, 5, 5, 37)),
+                },
+                span: span(4, 1, 5, 37),
+            },
+            Node {
+                kind: StatKind::CompoundAssign {
+                    target: ident("x", span(4, 8, 8, 37)),
+                    op: BinOp::Add,
+                    value: expr(ExprKind::IntLiteral(3), span(4, 13, 13, 37)),
+                },
+                span: span(4, 8, 13, 37),
+            },
+            Node {
+                kind: StatKind::Return(expr(
+                    ExprKind::Identifier("x".to_string()),
+                    span(5, 8, 8, 51),
+                )),
+                span: span(5, 1, 8, 51),
+            },
+            Node {
+                kind: StatKind::Let {
+                    name: ident("z", span(6, 5, 5, 60)),
+                    doc: None,
+                    var_type: None,
+                    value: Some(expr(ExprKind::CharLiteral('z'), span(6, 9, 11, 60))),
+                    ty: Type::Unknown,
+                },
+                span: span(6, 1, 11, 60),
+            },
+        ];
+        assert_eq!(body, expected);
+    }
+
+    #[test]
+    fn control_flow_statements() {
+        let body = parse_body(
+            "if a then\nb\nelse\nc\nend\nwhile x do\ny\nend\nfor i in xs do\nif z then end\nend",
+        );
+        assert_eq!(body.len(), 3);
+
+        match &body[0].kind {
+            StatKind::If {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                assert_eq!(condition.kind, ExprKind::Identifier("a".to_string()));
+                assert_eq!(then_body.len(), 1);
+                assert_eq!(else_body.len(), 1);
+            }
+            kind => panic!("expected if statement but got {kind:?}"),
+        }
+
+        match &body[1].kind {
+            StatKind::While { condition, body } => {
+                assert_eq!(condition.kind, ExprKind::Identifier("x".to_string()));
+                assert_eq!(body.len(), 1);
+            }
+            kind => panic!("expected while statement but got {kind:?}"),
+        }
+
+        match &body[2].kind {
+            StatKind::For {
+                var,
+                iterable,
+                body,
+            } => {
+                assert_eq!(var.name, "i");
+                assert_eq!(iterable.kind, ExprKind::Identifier("xs".to_string()));
+                assert!(matches!(
+                    body.as_slice(),
+                    [Node { kind: StatKind::If { then_body, else_body, .. }, .. }]
+                        if then_body.is_empty() && else_body.is_empty()
+                ));
+            }
+            kind => panic!("expected for statement but got {kind:?}"),
+        }
+    }
+
+    #[test]
+    fn range_loops() {
+        let body = parse_body("for i in 0..n + 1 do\nend");
+
+        match &body[0].kind {
+            StatKind::For { iterable, .. } => match &iterable.kind {
+                ExprKind::Range { start, end } => {
+                    assert_eq!(iterable.span, body_span(10, 17));
+                    assert_eq!(start.kind, ExprKind::IntLiteral(0));
+                    assert_eq!(sexpr(end), "(+ n 1)");
+                }
+                kind => panic!("expected range but got {kind:?}"),
+            },
+            kind => panic!("expected for statement but got {kind:?}"),
+        }
+
+        assert_eq!(
+            first_error("fn f()\nx = 0..n\nend").kind,
+            ErrorKind::Syntax(SyntaxErrorKind::UnexpectedToken {
+                expected: vec![TokenType::EndStatement],
+                found: TokenType::DotDot,
+            })
+        );
+    }
+
+    #[test]
+    fn literal_expressions() {
+        assert_eq!(parse_expr("123").kind, ExprKind::IntLiteral(123));
+        assert_eq!(parse_expr("0.25").kind, ExprKind::FloatLiteral(0.25));
+        assert_eq!(parse_expr("'x'").kind, ExprKind::CharLiteral('x'));
+        assert_eq!(parse_expr("'\\n'").kind, ExprKind::CharLiteral('\n'));
+        assert_eq!(
+            parse_expr("\"a\\tb\\\"\"").kind,
+            ExprKind::StringLiteral("a\tb\"".to_string())
+        );
+        assert_eq!(
+            parse_expr("\"\\u{E9}\\x21\"").kind,
+            ExprKind::StringLiteral("é!".to_string())
+        );
+        assert_eq!(parse_expr("0x_ff").kind, ExprKind::IntLiteral(255));
+        assert_eq!(parse_expr("true").kind, ExprKind::BoolLiteral(true));
+        assert_eq!(parse_expr("false").kind, ExprKind::BoolLiteral(false));
+        assert_eq!(parse_expr("[]").kind, ExprKind::List(Vec::new()));
+        assert_eq!(
+            parse_expr("[1, (2)]").kind,
+            ExprKind::List(vec![
+                expr(ExprKind::IntLiteral(1), body_span(2, 2)),
+                expr(ExprKind::IntLiteral(2), body_span(5, 7)),
+            ])
+        );
+    }
+
+    #[test]
+    fn smallest_integer_literals() {
+        assert_eq!(
+            parse_expr("-9223372036854775808"),
+            expr(ExprKind::IntLiteral(i64::MIN), body_span(1, 20))
+        );
+        assert_eq!(
+            parse_expr("- 0x8000_0000_0000_0000").kind,
+            ExprKind::IntLiteral(i64::MIN)
+        );
+        assert_eq!(
+            parse_expr("-2147483648i32").kind,
+            ExprKind::IntLiteral(i32::MIN as i64)
+        );
+        assert!(matches!(
+            parse_expr("-2147483648").kind,
+            ExprKind::Unary { .. }
+        ));
+
+        for input in [
+            "9223372036854775808",
+            "1 - 9223372036854775808",
+            "-(9223372036854775808)",
+        ] {
+            let error = first_error(&format!("fn f()\nx = {input}\nend"));
+            assert_eq!(
+                error.kind,
+                ErrorKind::Lexical(LexicalErrorKind::NumberLiteralOutOfRange("i64"))
+            );
+        }
+        let error = first_error("fn f()\nx = 2147483648i32\nend");
+        assert_eq!(
+            error.kind,
+            ErrorKind::Lexical(LexicalErrorKind::NumberLiteralOutOfRange("i32"))
+        );
+        assert_eq!(
+            error.notes,
+            ["i32 values range from -2147483648 to 2147483647"]
+        );
+    }
+
+    #[test]
+    fn call_expressions() {
+        assert_eq!(
+            parse_expr("f()").kind,
+            ExprKind::Call {
+                function: "f".to_string(),
+                args: Vec::new(),
+            }
+        );
+        assert_eq!(
+            parse_expr("g(a, h(1))").kind,
+            ExprKind::Call {
+                function: "g".to_string(),
+                args: vec![
+                    expr(ExprKind::Identifier("a".to_string()), body_span(3, 3)),
+                    expr(
+                        ExprKind::Call {
+                            function: "h".to_string(),
+                            args: vec![expr(ExprKind::IntLiteral(1), body_span(8, 8))],
+                        },
+                        body_span(6, 9)
+                    ),
+                ],
+            }
+        );
+        assert_eq!(sexpr(&parse_expr("-f() * g(x)")), "(* (- f()) g(x))");
+    }
+
+    #[test]
+    fn index_expressions() {
+        assert_eq!(
+            parse_expr("xs[1]"),
+            expr(
+                ExprKind::Index {
+                    target: Box::new(expr(
+                        ExprKind::Identifier("xs".to_string()),
+                        body_span(1, 2)
+                    )),
+                    index: Box::new(expr(ExprKind::IntLiteral(1), body_span(4, 4))),
+                },
+                body_span(1, 5)
+            )
+        );
+        assert_eq!(sexpr(&parse_expr("-xs[i][j + 1]")), "(- xs[i][(+ j 1)])");
+        assert_eq!(sexpr(&parse_expr("f(a)[0]")), "f(a)[0]");
+    }
+
+    #[test]
+    fn interpolated_strings() {
+        assert_eq!(sexpr(&parse_expr("\"x = {x}\"")), "(str \"x = \" x)");
+        assert_eq!(
+            sexpr(&parse_expr("\"{a + 1}, {f(b)}!\"")),
+            "(str (+ a 1) \", \" f(b) \"!\")"
+        );
+        assert_eq!(
+            sexpr(&parse_expr("\"{\"[{x}]\"}\"")),
+            "(str (str \"[\" x \"]\"))"
+        );
+
+        let errors = parse("fn f()\nx = \"a {b c}\"\nend");
+        assert!(matches!(
+            errors.as_slice(),
+            [Err(Error {
+                kind: ErrorKind::Syntax(SyntaxErrorKind::UnexpectedToken {
+                    found: TokenType::Identifier,
+                    ..
+                }),
+                ..
+            })]
+        ));
+    }
+
+    #[test]
+    fn operator_expressions() {
+        let a = expr(ExprKind::Identifier("a".to_string()), body_span(2, 2));
+        let b = expr(ExprKind::Identifier("b".to_string()), body_span(6, 6));
+        let c = expr(ExprKind::Identifier("c".to_string()), body_span(10, 10));
+
+        assert_eq!(
+            parse_expr("-a + b * c"),
+            expr(
+                ExprKind::Binary {
+                    op: BinOp::Add,
+                    left: Box::new(expr(
+                        ExprKind::Unary {
+                            op: UnOp::Negate,
+                            operand: Box::new(a),
+                        },
+                        body_span(1, 2)
+                    )),
+                    right: Box::new(expr(
+                        ExprKind::Binary {
+                            op: BinOp::Multiply,
+                            left: Box::new(b),
+                            right: Box::new(c),
+                        },
+                        body_span(6, 10)
+                    )),
+                },
+                body_span(1, 10)
+            )
+        );
+        assert_eq!(
+            parse_expr("!(a == b)").kind,
+            ExprKind::Unary {
+                op: UnOp::Not,
+                operand: Box::new(expr(
+                    ExprKind::Binary {
+                        op: BinOp::Equivalent,
+                        left: Box::new(expr(
+                            ExprKind::Identifier("a".to_string()),
+                            body_span(3, 3)
+                        )),
+                        right: Box::new(expr(
+                            ExprKind::Identifier("b".to_string()),
+                            body_span(8, 8)
+                        )),
+                    },
+                    body_span(2, 9)
+                )),
+            }
+        );
+    }
+
+    #[test]
+    fn operator_precedence() {
+        assert_eq!(sexpr(&parse_expr("-a * b")), "(* (- a) b)");
+        assert_eq!(sexpr(&parse_expr("-(a * b)")), "(- (* a b))");
+        assert_eq!(sexpr(&parse_expr("a < b == c")), "(== (< a b) c)");
+        assert_eq!(sexpr(&parse_expr("a == b < c")), "(== a (< b c))");
+        assert_eq!(sexpr(&parse_expr("!x and y")), "(and (! x) y)");
+        assert_eq!(sexpr(&parse_expr("!!x or y")), "(or (! (! x)) y)");
+        assert_eq!(
+            sexpr(&parse_expr("a + b * c == d and e or f")),
+            "(or (and (== (+ a (* b c)) d) e) f)"
+        );
+        assert_eq!(
+            sexpr(&parse_expr("a or b and c != d >= e - f / g")),
+            "(or a (and b (!= c (>= d (- e (/ f g))))))"
+        );
+        assert_eq!(sexpr(&parse_expr("(a + b) * c")), "(* (+ a b) c)");
+    }
+
+    #[test]
+    fn operator_associativity() {
+        assert_eq!(sexpr(&parse_expr("a - b - c")), "(- (- a b) c)");
+        assert_eq!(sexpr(&parse_expr("a / b * c")), "(* (/ a b) c)");
+        assert_eq!(sexpr(&parse_expr("a < b > c")), "(> (< a b) c)");
+        assert_eq!(sexpr(&parse_expr("a == b != c")), "(!= (== a b) c)");
+        assert_eq!(sexpr(&parse_expr("a and b and c")), "(and (and a b) c)");
+        assert_eq!(sexpr(&parse_expr("a or b or c")), "(or (or a b) c)");
+        assert_eq!(sexpr(&parse_expr("a - (b - c)")), "(- a (- b c))");
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert_syntax_error!(
+            "fn f()",
+            SyntaxErrorKind::UnexpectedEof {
+                expected: vec![TokenType::Arrow, TokenType::EndStatement]
+            },
+            1,
+            7,
+            "fn f()"
+        );
+        assert_syntax_error!(
+            "fn f() x\nend",
+            SyntaxErrorKind::UnexpectedToken {
+                expected: vec![TokenType::Arrow, TokenType::EndStatement],
+                found: TokenType::Identifier
+            },
+            1,
+            8,
+            "fn f() x"
+        );
+        assert_syntax_error!(
+            "fn f(a int)\nend",
+            SyntaxErrorKind::UnexpectedToken {
+                expected: vec![TokenType::Colon],
+                found: TokenType::Identifier
+            },
+            1,
+            8,
+            "fn f(a int)"
+        );
+        assert_syntax_error!(
+            "fn f(a: int b: int)\nend",
+            SyntaxErrorKind::UnexpectedToken {
+                expected: vec![TokenType::Comma, TokenType::CloseBracket],
+                found: TokenType::Identifier
+            },
+            1,
+            13,
+            "fn f(a: int b: int)"
+        );
+        assert_syntax_error!(
+            "fn f()\nlet x: int = \nend",
+            SyntaxErrorKind::ExpectedExpression {
+                found: TokenType::EndStatement
+            },
+            2,
+            13,
+            "let x: int ="
+        );
+        assert_syntax_error!(
+            "fn f()\nif x do\nend\nend",
+            SyntaxErrorKind::UnexpectedToken {
+                expected: vec![TokenType::ThenKeyword],
+                found: TokenType::DoKeyword
+            },
+            2,
+            6,
+            "if x do"
+        );
+        assert_syntax_error!(
+            "fn f()\nreturn [1, 2\nend",
+            SyntaxErrorKind::UnexpectedToken {
+                expected: vec![TokenType::Comma, TokenType::CloseSquare],
+                found: TokenType::EndStatement
+            },
+            2,
+            13,
+            "return [1, 2"
+        );
+        assert_syntax_error!(
+            "fn f()\nlet x 1\nend",
+            SyntaxErrorKind::UnexpectedToken {
+                expected: vec![TokenType::Colon, TokenType::Assign, TokenType::EndStatement],
+                found: TokenType::IntLiteral
+            },
+            2,
+            7,
+            "let x 1"
+        );
+        assert_syntax_error!(
+            "let x: int\n",
+            SyntaxErrorKind::UnexpectedToken {
+                expected: vec![TokenType::FnKeyword],
+                found: TokenType::LetKeyword
+            },
+            1,
+            1,
+            "let x: int"
+        );
+    }
+
+    #[test]
+    fn invalid_assignment_target() {
+        assert_syntax_error!(
+            "fn f()\n1 = 2\nend",
+            SyntaxErrorKind::InvalidAssignmentTarget,
+            2,
+            1,
+            "1 = 2"
+        );
+        assert_syntax_error!(
+            "fn f()\n  a + b += 2\nend",
+            SyntaxErrorKind::InvalidAssignmentTarget,
+            2,
+            3,
+            "  a + b += 2"
+        );
+    }
+
+    #[test]
+    fn missing_end() {
+        assert_syntax_error!(
+            "fn f()\nx\n",
+            SyntaxErrorKind::MissingEnd {
+                opened_by: TokenType::FnKeyword,
+                opened_at: 1
+            },
+            2,
+            2,
+            "x"
+        );
+        assert_syntax_error!(
+            "fn f()\nwhile x do\n  if y then\n    z\n  end\n",
+            SyntaxErrorKind::MissingEnd {
+                opened_by: TokenType::WhileKeyword,
+                opened_at: 2
+            },
+            5,
+            6,
+            "  end"
+        );
+        assert_syntax_error!(
+            "fn f()\nif x then\ny\nelse\nz",
+            SyntaxErrorKind::UnexpectedEof {
+                expected: vec![TokenType::EndStatement]
+            },
+            5,
+            2,
+            "z"
+        );
+    }
+
+    #[test]
+    fn syntax_error_messages() {
+        let error = first_error("fn f(a: int b: int)\nend");
+        assert_eq!(
+            error.to_string(),
+            "error[K0201]: expected ',' or ')', found identifier\n\
+             \x20--> test:1:13\n\
+             \x20 |\n\
+             1 | fn f(a: int b: int)\n\
+             \x20 |             ^\n"
+        );
+
+        let error = first_error("fn f()\nif x then\n");
+        assert_eq!(
+            error.kind.to_string(),
+            "Syntax error: missing 'end' to close 'if' on line 2."
+        );
+        assert_eq!(
+            error.to_string(),
+            "error[K0204]: missing 'end' to close 'if' on line 2\n\
+             \x20--> test:2:10\n\
+             \x20 |\n\
+             2 | if x then\n\
+             \x20 |          ^ expected 'end' before here\n\
+             \x20 | -- this 'if' is never closed\n\
+             \x20 |\n\
+             \x20 = help: add 'end' to close the 'if'\n"
+        );
+
+        let error = first_error("fn f()\nreturn\nend");
+        assert_eq!(
+            error.kind.to_string(),
+            "Syntax error: expected expression, found newline or ';'."
+        );
+    }
+
+    #[test]
+    fn recover_from_statement_errors() {
+        let input = "fn f()\nlet x: int = \nx = )\nlet y: float = 1.0\n1 = y\nend";
+        assert_eq!(error_positions(input), vec![(2, 13), (3, 5), (5, 1)]);
+
+        let input = "fn f()\nwhile x do\ny = ]\nelse\nend\nend";
+        assert_eq!(error_positions(input), vec![(3, 5), (4, 1)]);
+    }
+
+    #[test]
+    fn recover_from_function_errors() {
+        let input = "fn f(a int)\nx = )\nend\nlet y: int\nfn f()\nx = ]\nend\nfn f()\nend";
+        let results = parse(input);
+
+        let error_positions: Vec<_> = results
+            .iter()
+            .filter_map(|r| r.as_ref().err())
+            .map(|e| (e.span.start.line, e.span.start.column))
+            .collect();
+        assert_eq!(error_positions, vec![(1, 8), (6, 5)]);
+
+        // only the final function is free of errors
+        assert!(matches!(
+            results.as_slice(),
+            [Err(_), Err(_), Ok(f)] if f.span.start.line == 8
+        ));
+    }
+
+    #[test]
+    fn recover_from_missing_end() {
+        let input = "fn f()\nif x then\ny\nfn f()\nz = ]\nend";
+        let errors: Vec<_> = parse(input).into_iter().filter_map(Result::err).collect();
+
+        assert_eq!(errors.len(), 3);
+        assert_eq!(
+            errors[0].kind,
+            ErrorKind::Syntax(SyntaxErrorKind::MissingEnd {
+                opened_by: TokenType::IfKeyword,
+                opened_at: 2
+            })
+        );
+        assert_eq!(errors[0].span.start, Pos { line: 4, column: 1 });
+        assert_eq!(
+            errors[1].kind,
+            ErrorKind::Syntax(SyntaxErrorKind::MissingEnd {
+                opened_by: TokenType::FnKeyword,
+                opened_at: 1
+            })
+        );
+        assert_eq!(errors[2].span.start, Pos { line: 5, column: 5 });
+    }
+
+    #[test]
+    fn recover_from_lexical_errors() {
+        let input = "fn f()\nx = 1.2.3 + 4\ny = 'ab'\nz = 5\nend";
+        let errors: Vec<_> = parse(input).into_iter().filter_map(Result::err).collect();
+
+        assert_eq!(errors.len(), 2);
+        assert!(errors
+            .iter()
+            .all(|e| matches!(e.kind, ErrorKind::Lexical(_))));
+        assert_eq!(errors[0].span.start.line, 2);
+        assert_eq!(errors[1].span.start.line, 3);
+    }
+
+    #[test]
+    fn error_limit() {
+        let errors = |count| "fn f()\n".to_string() + &"x = )\n".repeat(count) + "end";
+        let mut parser = Parser::new(Lexer::new(
+            Cursor::new(errors(MAX_ERRORS * 2)),
+            "test".to_string(),
+        ));
+        assert_eq!(parser.parse_all().unwrap_err().len(), MAX_ERRORS);
+        assert!(parser.errors_truncated());
+
+        let mut parser = Parser::new(Lexer::new(
+            Cursor::new(errors(MAX_ERRORS)),
+            "test".to_string(),
+        ));
+        assert_eq!(parser.parse_all().unwrap_err().len(), MAX_ERRORS);
+        assert!(!parser.errors_truncated());
+    }
+
+    #[test]
+    fn stop_after_read_failure() {
+        struct BrokenPipe;
+
+        impl Read for BrokenPipe {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::BrokenPipe.into())
+            }
+        }
+
+        // the function and if statement left open are not reported
+        let input = Cursor::new("fn f()\nif true then\nx = ").chain(BrokenPipe);
+        let lexer = Lexer::new(input, "test".to_string());
+        let errors: Vec<_> = Parser::new(lexer).filter_map(Result::err).collect();
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(
+            errors[0].kind,
+            ErrorKind::Io {
+                kind: io::ErrorKind::BrokenPipe,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn lexical_errors_are_propagated() {
+        let mut results = parse("fn f()\nx = 1.2.3\nend");
+        assert!(matches!(
+            results.pop(),
+            Some(Err(Error {
+                kind: ErrorKind::Lexical(_),
+                span: Span {
+                    start: Pos { line: 2, .. },
+                    ..
+                },
+                ..
+            }))
+        ));
     }
 }
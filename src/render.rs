@@ -0,0 +1,433 @@
+//! Rendering of errors for the terminal, as a header giving the kind of error
+//! followed by the relevant lines of input with the primary span underlined
+//! with `^` and any secondary spans with `-`, e.g.,
+//!
+//! ```text
+//! error[K0307]: expected int, found float
+//!  --> main.kobe:3:5
+//!   |
+//! 2 | let x: int = 0
+//!   |     - 'x' declared here as int
+//! 3 | x = 1.5 * 2.0
+//!   |     ^^^^^^^^^ expected int
+//! ```
+
+use std::collections::BTreeMap;
+
+use clap::ValueEnum;
+
+use crate::{
+    error::{Error, Severity},
+    span::{Pos, Span},
+};
+
+/// When to colour rendered errors using ANSI escape codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ColorChoice {
+    /// Only when writing to a terminal
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether to use colour when writing to a stream which may or may not be
+    /// a terminal.
+    pub fn use_colour(self, is_terminal: bool) -> bool {
+        match self {
+            ColorChoice::Auto => is_terminal,
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Style {
+    Error,
+    Warning,
+    Secondary,
+    Gutter,
+    Bold,
+}
+
+impl Style {
+    fn code(self) -> &'static str {
+        match self {
+            Style::Error => "\x1b[1;31m",
+            Style::Warning => "\x1b[1;33m",
+            Style::Secondary | Style::Gutter => "\x1b[1;34m",
+            Style::Bold => "\x1b[1m",
+        }
+    }
+}
+
+struct Painter {
+    colour: bool,
+}
+
+impl Painter {
+    fn paint(&self, style: Style, text: &str) -> String {
+        if self.colour && !text.is_empty() {
+            format!("{}{text}\x1b[0m", style.code())
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// The width at which tabs in the input are displayed.
+const TAB_WIDTH: usize = 4;
+
+/// Spans covering more lines than this have the lines in between their first
+/// two and last elided.
+const MAX_SPAN_LINES: usize = 4;
+
+/// An underline beneath a single line of input.
+struct Underline<'a> {
+    /// Columns of the first and last characters underlined.
+    columns: (usize, usize),
+    primary: bool,
+    message: Option<&'a str>,
+}
+
+/// Render an error as described in the [module documentation](self), using
+/// ANSI escape codes for colour if requested.
+pub fn render(error: &Error, colour: bool) -> String {
+    let painter = Painter { colour };
+    let mut out = String::new();
+
+    let severity_style = match error.severity {
+        Severity::Error => Style::Error,
+        Severity::Warning => Style::Warning,
+    };
+    let header = format!("{}[{}]", error.severity, error.kind.code());
+    out += &painter.paint(severity_style, &header);
+    out += &painter.paint(Style::Bold, &format!(": {}", error.kind.message()));
+    out += "\n";
+
+    let mut underlines: BTreeMap<usize, Vec<Underline>> = BTreeMap::new();
+    if error.span.has_position() {
+        add_underlines(
+            &mut underlines,
+            &error.span,
+            true,
+            error.label.as_deref(),
+            error,
+        );
+    }
+    for label in &error.secondary_labels {
+        add_underlines(
+            &mut underlines,
+            &label.span,
+            false,
+            Some(&label.message),
+            error,
+        );
+    }
+
+    let last_line = underlines.keys().next_back().copied().unwrap_or(1);
+    let gutter_width = last_line.to_string().len();
+    let blank_gutter = " ".repeat(gutter_width);
+    let bar = painter.paint(Style::Gutter, "|");
+
+    // errors which do not relate to any part of the input are only given the
+    // name of the input
+    let arrow = painter.paint(Style::Gutter, "-->");
+    if error.span.has_position() {
+        out += &format!(
+            "{blank_gutter}{arrow} {}:{}\n",
+            error.input_source_name, error.span.start
+        );
+    } else {
+        out += &format!("{blank_gutter}{arrow} {}\n", error.input_source_name);
+    }
+    if !underlines.is_empty() {
+        out += &format!("{blank_gutter} {bar}\n");
+    }
+
+    let mut previous_line = None;
+    for (&line_number, line_underlines) in &underlines {
+        if previous_line.is_some_and(|previous| line_number > previous + 1) {
+            out += &painter.paint(Style::Gutter, "...");
+            out += "\n";
+        }
+        previous_line = Some(line_number);
+
+        let line = error
+            .source_lines
+            .get(&line_number)
+            .map_or(String::new(), |line| {
+                line.replace('\t', &" ".repeat(TAB_WIDTH))
+            });
+        let number = format!("{line_number:>gutter_width$}");
+        out += format!("{} {bar} {line}", painter.paint(Style::Gutter, &number)).trim_end();
+        out += "\n";
+
+        let source_line = error.source_lines.get(&line_number).map_or("", |l| l);
+        for underline in line_underlines {
+            let (start, end) = underline.columns;
+            let indent = display_width(source_line, 1, start);
+            let width = display_width(source_line, start, end + 1).max(1);
+
+            let (mark, style) = if underline.primary {
+                ("^", severity_style)
+            } else {
+                ("-", Style::Secondary)
+            };
+            let mut marks = mark.repeat(width);
+            if let Some(message) = underline.message {
+                marks = format!("{marks} {message}");
+            }
+
+            out += &format!(
+                "{blank_gutter} {bar} {}{}\n",
+                " ".repeat(indent),
+                painter.paint(style, &marks)
+            );
+        }
+    }
+
+    let help = error.help.iter().chain(
+        error
+            .suggestions
+            .iter()
+            .map(|suggestion| &suggestion.message),
+    );
+
+    if !error.notes.is_empty() || !error.help.is_empty() || !error.suggestions.is_empty() {
+        out += &format!("{blank_gutter} {bar}\n");
+    }
+    let equals = painter.paint(Style::Gutter, "=");
+    for note in &error.notes {
+        let heading = painter.paint(Style::Bold, "note");
+        out += &format!("{blank_gutter} {equals} {heading}: {note}\n");
+    }
+    for help in help {
+        let heading = painter.paint(Style::Bold, "help");
+        out += &format!("{blank_gutter} {equals} {heading}: {help}\n");
+    }
+
+    out
+}
+
+/// Add the underlines for a span, which are on each of the lines it covers
+/// (other than any which are elided) with the message on the last. Underline
+/// columns count characters, whatever the unit of the error's spans.
+fn add_underlines<'a>(
+    underlines: &mut BTreeMap<usize, Vec<Underline<'a>>>,
+    span: &Span,
+    primary: bool,
+    message: Option<&'a str>,
+    error: &Error,
+) {
+    let source_lines = &error.source_lines;
+    let char_position = |pos: Pos| Pos {
+        column: error.column_unit.char_column(
+            source_lines.get(&pos.line).map_or("", |line| line),
+            pos.column,
+        ),
+        ..pos
+    };
+    let (start, end) = (char_position(span.start), char_position(span.end));
+    let line_length = |line_number| {
+        source_lines
+            .get(&line_number)
+            .map_or(0, |line: &String| line.chars().count())
+    };
+
+    let mut add = |line_number, columns, message| {
+        let line_underlines = underlines.entry(line_number).or_default();
+        let underline = Underline {
+            columns,
+            primary,
+            message,
+        };
+        // primary underlines come first
+        if primary {
+            line_underlines.insert(0, underline);
+        } else {
+            line_underlines.push(underline);
+        }
+    };
+
+    if start.line == end.line {
+        add(start.line, (start.column, end.column), message);
+        return;
+    }
+
+    add(start.line, (start.column, line_length(start.line)), None);
+    let last_shown = if end.line - start.line + 1 > MAX_SPAN_LINES {
+        start.line + 1
+    } else {
+        end.line - 1
+    };
+    for line_number in start.line + 1..=last_shown {
+        let indent = first_non_whitespace_column(source_lines.get(&line_number));
+        add(line_number, (indent, line_length(line_number)), None);
+    }
+    let indent = first_non_whitespace_column(source_lines.get(&end.line));
+    add(end.line, (indent, end.column), message);
+}
+
+fn first_non_whitespace_column(line: Option<&String>) -> usize {
+    line.and_then(|line| line.chars().position(|c| !c.is_whitespace()))
+        .map_or(1, |i| i + 1)
+}
+
+/// The display width of the characters of a line from column `start` up to
+/// (but not including) column `end`, with tabs expanded. Columns beyond the
+/// end of the line are one character wide.
+fn display_width(line: &str, start: usize, end: usize) -> usize {
+    (start..end)
+        .map(|column| match line.chars().nth(column - 1) {
+            Some('\t') => TAB_WIDTH,
+            _ => 1,
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ast::Type,
+        error::{ErrorKind, LexicalErrorKind, LintKind, TypeErrorKind},
+        lex::ColumnUnit,
+        span::Pos,
+    };
+
+    fn span(start: (usize, usize), end: (usize, usize)) -> Span {
+        Span {
+            start: Pos {
+                line: start.0,
+                column: start.1,
+            },
+            end: Pos {
+                line: end.0,
+                column: end.1,
+            },
+            byte_range: 0..0,
+        }
+    }
+
+    fn error(kind: ErrorKind, span: Span, lines: &[&str]) -> Error {
+        let mut error = Error::new(kind, span);
+        error.input_source_name = "test".to_string();
+        error.source_lines = (1..).zip(lines.iter().map(|l| l.to_string())).collect();
+        error
+    }
+
+    #[test]
+    fn single_line() {
+        let kind = ErrorKind::Lexical(LexicalErrorKind::InvalidCharLiteral);
+        let error = error(kind, span((1, 5), (1, 6)), &["x = 'ab'"]);
+        assert_eq!(
+            render(&error, false),
+            "error[K0103]: invalid character literal\n\
+             \x20--> test:1:5\n\
+             \x20 |\n\
+             1 | x = 'ab'\n\
+             \x20 |     ^^\n"
+        );
+    }
+
+    #[test]
+    fn utf16_columns() {
+        // the musical symbol is outside the BMP, so is two UTF-16 columns wide
+        let kind = ErrorKind::Lexical(LexicalErrorKind::InvalidCharLiteral);
+        let mut error = error(kind, span((1, 12), (1, 15)), &["x = \"𝄞\" + 'ab'"]);
+        error.column_unit = ColumnUnit::Utf16;
+        assert_eq!(
+            render(&error, false),
+            "error[K0103]: invalid character literal\n\
+             \x20--> test:1:12\n\
+             \x20 |\n\
+             1 | x = \"𝄞\" + 'ab'\n\
+             \x20 |           ^^^^\n"
+        );
+    }
+
+    #[test]
+    fn labels_and_notes() {
+        let kind = ErrorKind::Type(TypeErrorKind::Mismatch {
+            expected: Type::Int,
+            found: Type::Float,
+        });
+        let lines = ["fn f(x: int)", "", "", "", "", "", "", "", "", "\tx = 1.5"];
+        let error = error(kind, span((10, 6), (10, 8)), &lines)
+            .with_label("expected int")
+            .with_secondary_label(span((1, 6), (1, 6)), "declared here")
+            .with_note("a note")
+            .with_help("some help");
+        assert_eq!(
+            render(&error, false),
+            "error[K0307]: expected int, found float\n\
+             \x20 --> test:10:6\n\
+             \x20  |\n\
+             \x201 | fn f(x: int)\n\
+             \x20  |      - declared here\n\
+             ...\n\
+             10 |     x = 1.5\n\
+             \x20  |         ^^^ expected int\n\
+             \x20  |\n\
+             \x20  = note: a note\n\
+             \x20  = help: some help\n"
+        );
+    }
+
+    #[test]
+    fn multiple_lines() {
+        let kind = ErrorKind::Lexical(LexicalErrorKind::UnterminatedComment);
+        let lines = ["x --[[ a", "  b", "  c", "  d", "e"];
+        let error = error(kind, span((1, 3), (5, 1)), &lines).with_label("opened here");
+        assert_eq!(
+            render(&error, false),
+            "error[K0109]: unterminated block comment\n\
+             \x20--> test:1:3\n\
+             \x20 |\n\
+             1 | x --[[ a\n\
+             \x20 |   ^^^^^^\n\
+             2 |   b\n\
+             \x20 |   ^\n\
+             ...\n\
+             5 | e\n\
+             \x20 | ^ opened here\n"
+        );
+    }
+
+    #[test]
+    fn without_position() {
+        let kind = ErrorKind::Io {
+            kind: std::io::ErrorKind::NotFound,
+            message: "not found".to_string(),
+        };
+        let error = error(kind, Span::default(), &[]);
+        assert_eq!(
+            render(&error, false),
+            "error[K0001]: could not read input: not found\n\
+             \x20--> test\n"
+        );
+    }
+
+    #[test]
+    fn colour() {
+        let kind = ErrorKind::Lexical(LexicalErrorKind::UnexpectedCharacter);
+        let rendered = render(&error(kind, span((1, 1), (1, 1)), &["$"]), true);
+        assert!(rendered.starts_with("\x1b[1;31merror[K0101]\x1b[0m\x1b[1m: unexpected"));
+        assert!(rendered.ends_with("\x1b[1;31m^\x1b[0m\n"));
+
+        let kind = ErrorKind::Lint(LintKind::UnreachableCode);
+        let warning = error(kind, span((1, 1), (1, 1)), &["$"]).with_severity(Severity::Warning);
+        assert!(render(&warning, true).starts_with("\x1b[1;33mwarning[K0404]\x1b[0m"));
+        let denied = warning.with_severity(Severity::Error);
+        assert!(render(&denied, true).starts_with("\x1b[1;31merror[K0404]\x1b[0m"));
+        assert_eq!(denied.kind.to_string(), "Lint: unreachable code.");
+
+        assert!(ColorChoice::Auto.use_colour(true));
+        assert!(!ColorChoice::Auto.use_colour(false));
+        assert!(ColorChoice::Always.use_colour(false));
+        assert!(!ColorChoice::Never.use_colour(true));
+    }
+}
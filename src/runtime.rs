@@ -0,0 +1,825 @@
+//! Support code emitted into every generated WASM module.
+//!
+//! Strings and lists are stored in linear memory and referred to by an `i32`
+//! pointer to a 4-byte length followed immediately by the elements themselves.
+//! A string is stored in the same way as a list of chars (i.e., as UTF-32).
+//! Address 0 is never allocated and always holds a length of zero, so a zeroed
+//! pointer is a valid empty string or list.
+//!
+//! Memory is allocated by bumping a pointer and is never freed.
+
+use wasm_encoder::{BlockType, Function, Instruction, MemArg, ValType};
+
+use crate::ast::Type;
+
+/// Size in bytes of the length which precedes the elements of a string or
+/// list.
+pub const HEADER_SIZE: u32 = 4;
+
+/// Address at which string constants are placed in memory.
+pub const DATA_START: u32 = 8;
+
+/// Index of the mutable `i32` global holding the address of the next free
+/// byte of memory.
+pub const HEAP_POINTER: u32 = 0;
+
+/// Index of the mutable `i32` global which is set to a [`RuntimeError`] code
+/// just before trapping. It is exported as [`ERROR_CODE_EXPORT`] so that hosts
+/// can determine why execution failed.
+pub const ERROR_CODE: u32 = 1;
+
+/// Name under which the module's memory is exported. Like the other runtime
+/// exports, it contains a `.` so that it cannot clash with the name of an
+/// exported Kobe function.
+pub const MEMORY_EXPORT: &str = "kobe.memory";
+
+/// Name under which the [`ERROR_CODE`] global is exported.
+pub const ERROR_CODE_EXPORT: &str = "kobe.error_code";
+
+/// Why a Kobe program trapped, as stored in the [`ERROR_CODE`] global.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimeError {
+    IndexOutOfBounds = 1,
+    OutOfMemory = 2,
+}
+
+/// The functions making up the runtime, which are placed after the Kobe
+/// program's own functions in the module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimeFunction {
+    /// `(size: i32) -> i32`: allocate `size` bytes of memory, growing memory
+    /// as necessary, and return the address of the first byte.
+    Alloc,
+    /// `(list: i32, index: i64, element_size: i32) -> i32`: get the address
+    /// of an element of a string or list, trapping if the index is out of
+    /// bounds.
+    ElementAddress,
+    /// `(a: i32, b: i32) -> i32`: whether two strings have the same contents.
+    StringsEqual,
+    /// `(a: i32, b: i32) -> i32`: a new string made up of the chars of `a`
+    /// followed by those of `b`. Either string is returned as is if the
+    /// other is empty.
+    Concat,
+    /// `(value: i64, min_width: i32) -> i32`: the decimal digits of `value`
+    /// (treated as unsigned) as a string, padded with leading zeros to at
+    /// least `min_width` digits.
+    Digits,
+    /// `(value: i64) -> i32`: an `int` as a string.
+    IntToString,
+    /// `(value: f64) -> i32`: a `float` as a string with between one and six
+    /// decimal places (e.g., `1.0` or `0.333333`). Magnitudes of at least
+    /// 10^15 or less than 10^-4 are written in scientific notation (e.g.,
+    /// `1.5e20`).
+    FloatToString,
+    /// `(value: i32) -> i32`: a `char` as a string.
+    CharToString,
+    /// `(value: i32) -> i32`: a `bool` as a string.
+    BoolToString,
+}
+
+impl RuntimeFunction {
+    pub const ALL: [RuntimeFunction; 9] = [
+        RuntimeFunction::Alloc,
+        RuntimeFunction::ElementAddress,
+        RuntimeFunction::StringsEqual,
+        RuntimeFunction::Concat,
+        RuntimeFunction::Digits,
+        RuntimeFunction::IntToString,
+        RuntimeFunction::FloatToString,
+        RuntimeFunction::CharToString,
+        RuntimeFunction::BoolToString,
+    ];
+
+    /// The index of this function in a module in which the runtime functions
+    /// start at the given index.
+    pub fn index(self, runtime_start: u32) -> u32 {
+        let position = RuntimeFunction::ALL
+            .iter()
+            .position(|&f| f == self)
+            .expect("all runtime functions are listed");
+        runtime_start + position as u32
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RuntimeFunction::Alloc => "runtime.alloc",
+            RuntimeFunction::ElementAddress => "runtime.element_address",
+            RuntimeFunction::StringsEqual => "runtime.strings_equal",
+            RuntimeFunction::Concat => "runtime.concat",
+            RuntimeFunction::Digits => "runtime.digits",
+            RuntimeFunction::IntToString => "runtime.int_to_string",
+            RuntimeFunction::FloatToString => "runtime.float_to_string",
+            RuntimeFunction::CharToString => "runtime.char_to_string",
+            RuntimeFunction::BoolToString => "runtime.bool_to_string",
+        }
+    }
+
+    pub fn params(self) -> Vec<ValType> {
+        match self {
+            RuntimeFunction::Alloc => vec![ValType::I32],
+            RuntimeFunction::ElementAddress => vec![ValType::I32, ValType::I64, ValType::I32],
+            RuntimeFunction::StringsEqual | RuntimeFunction::Concat => {
+                vec![ValType::I32, ValType::I32]
+            }
+            RuntimeFunction::Digits => vec![ValType::I64, ValType::I32],
+            RuntimeFunction::IntToString => vec![ValType::I64],
+            RuntimeFunction::FloatToString => vec![ValType::F64],
+            RuntimeFunction::CharToString | RuntimeFunction::BoolToString => vec![ValType::I32],
+        }
+    }
+
+    pub fn results(self) -> Vec<ValType> {
+        vec![ValType::I32]
+    }
+
+    /// The body of this function in a module in which the runtime functions
+    /// start at the given index.
+    pub fn body(self, runtime_start: u32) -> Function {
+        use Instruction as I;
+
+        let call = |function: RuntimeFunction| I::Call(function.index(runtime_start));
+
+        let (locals, instructions) = match self {
+            // params: size (0), locals: address (1), end (2)
+            RuntimeFunction::Alloc => (
+                vec![ValType::I32, ValType::I32],
+                [
+                    vec![
+                        I::GlobalGet(HEAP_POINTER),
+                        I::LocalSet(1),
+                        // round the end of the allocation up so that every
+                        // allocation is 8-byte aligned
+                        I::LocalGet(1),
+                        I::LocalGet(0),
+                        I::I32Add,
+                        I::I32Const(7),
+                        I::I32Add,
+                        I::I32Const(-8),
+                        I::I32And,
+                        I::LocalTee(2),
+                        I::LocalGet(1),
+                        I::I32LtU,
+                        I::If(BlockType::Empty),
+                    ],
+                    trap(RuntimeError::OutOfMemory),
+                    vec![
+                        I::End,
+                        // grow memory by enough pages to fit the allocation
+                        I::LocalGet(2),
+                        I::MemorySize(0),
+                        I::I32Const(16),
+                        I::I32Shl,
+                        I::I32GtU,
+                        I::If(BlockType::Empty),
+                        I::LocalGet(2),
+                        I::MemorySize(0),
+                        I::I32Const(16),
+                        I::I32Shl,
+                        I::I32Sub,
+                        I::I32Const(0xFFFF),
+                        I::I32Add,
+                        I::I32Const(16),
+                        I::I32ShrU,
+                        I::MemoryGrow(0),
+                        I::I32Const(-1),
+                        I::I32Eq,
+                        I::If(BlockType::Empty),
+                    ],
+                    trap(RuntimeError::OutOfMemory),
+                    vec![
+                        I::End,
+                        I::End,
+                        I::LocalGet(2),
+                        I::GlobalSet(HEAP_POINTER),
+                        I::LocalGet(1),
+                        I::End,
+                    ],
+                ]
+                .concat(),
+            ),
+
+            // params: list (0), index (1), element_size (2)
+            RuntimeFunction::ElementAddress => (
+                vec![],
+                [
+                    vec![
+                        // a negative index is treated as a very large unsigned
+                        // one and so is also out of bounds
+                        I::LocalGet(1),
+                        I::LocalGet(0),
+                        load_length(),
+                        I::I64ExtendI32U,
+                        I::I64GeU,
+                        I::If(BlockType::Empty),
+                    ],
+                    trap(RuntimeError::IndexOutOfBounds),
+                    vec![
+                        I::End,
+                        I::LocalGet(0),
+                        I::I32Const(HEADER_SIZE as i32),
+                        I::I32Add,
+                        I::LocalGet(1),
+                        I::I32WrapI64,
+                        I::LocalGet(2),
+                        I::I32Mul,
+                        I::I32Add,
+                        I::End,
+                    ],
+                ]
+                .concat(),
+            ),
+
+            // params: a (0), b (1), locals: length (2), offset (3)
+            RuntimeFunction::StringsEqual => (
+                vec![ValType::I32, ValType::I32],
+                vec![
+                    I::LocalGet(0),
+                    load_length(),
+                    I::LocalTee(2),
+                    I::LocalGet(1),
+                    load_length(),
+                    I::I32Ne,
+                    I::If(BlockType::Empty),
+                    I::I32Const(0),
+                    I::Return,
+                    I::End,
+                    // compare each char in turn, with `offset` counting up in
+                    // bytes to the end of the strings
+                    I::LocalGet(2),
+                    I::I32Const(element_size(&Type::Char) as i32),
+                    I::I32Mul,
+                    I::LocalSet(2),
+                    I::Block(BlockType::Empty),
+                    I::Loop(BlockType::Empty),
+                    I::LocalGet(3),
+                    I::LocalGet(2),
+                    I::I32GeU,
+                    I::BrIf(1),
+                    I::LocalGet(0),
+                    I::LocalGet(3),
+                    I::I32Add,
+                    I::I32Load(mem_arg(HEADER_SIZE)),
+                    I::LocalGet(1),
+                    I::LocalGet(3),
+                    I::I32Add,
+                    I::I32Load(mem_arg(HEADER_SIZE)),
+                    I::I32Ne,
+                    I::If(BlockType::Empty),
+                    I::I32Const(0),
+                    I::Return,
+                    I::End,
+                    I::LocalGet(3),
+                    I::I32Const(element_size(&Type::Char) as i32),
+                    I::I32Add,
+                    I::LocalSet(3),
+                    I::Br(0),
+                    I::End,
+                    I::End,
+                    I::I32Const(1),
+                    I::End,
+                ],
+            ),
+
+            // params: a (0), b (1), locals: a_length (2), b_length (3),
+            // result (4), offset (5), b_start (6)
+            RuntimeFunction::Concat => (
+                vec![ValType::I32; 5],
+                [
+                    vec![
+                        I::LocalGet(0),
+                        load_length(),
+                        I::LocalSet(2),
+                        I::LocalGet(1),
+                        load_length(),
+                        I::LocalSet(3),
+                        // strings are immutable so can be shared
+                        I::LocalGet(2),
+                        I::I32Eqz,
+                        I::If(BlockType::Empty),
+                        I::LocalGet(1),
+                        I::Return,
+                        I::End,
+                        I::LocalGet(3),
+                        I::I32Eqz,
+                        I::If(BlockType::Empty),
+                        I::LocalGet(0),
+                        I::Return,
+                        I::End,
+                        I::LocalGet(2),
+                        I::LocalGet(3),
+                        I::I32Add,
+                        I::I32Const(element_size(&Type::Char) as i32),
+                        I::I32Mul,
+                        I::I32Const(HEADER_SIZE as i32),
+                        I::I32Add,
+                        call(RuntimeFunction::Alloc),
+                        I::LocalTee(4),
+                        I::LocalGet(2),
+                        I::LocalGet(3),
+                        I::I32Add,
+                        store_length(),
+                        // the chars of b are placed immediately after those
+                        // of a
+                        I::LocalGet(4),
+                        I::LocalGet(2),
+                        I::I32Const(element_size(&Type::Char) as i32),
+                        I::I32Mul,
+                        I::I32Add,
+                        I::LocalSet(6),
+                    ],
+                    copy_chars(0, 4, 2, 5),
+                    copy_chars(1, 6, 3, 5),
+                    vec![I::LocalGet(4), I::End],
+                ]
+                .concat(),
+            ),
+
+            // params: value (0), min_width (1), locals: width (2), rest (3),
+            // result (4), address (5)
+            RuntimeFunction::Digits => (
+                vec![ValType::I32, ValType::I64, ValType::I32, ValType::I32],
+                vec![
+                    // count the digits
+                    I::I32Const(1),
+                    I::LocalSet(2),
+                    I::LocalGet(0),
+                    I::LocalSet(3),
+                    I::Block(BlockType::Empty),
+                    I::Loop(BlockType::Empty),
+                    I::LocalGet(3),
+                    I::I64Const(10),
+                    I::I64LtU,
+                    I::BrIf(1),
+                    I::LocalGet(3),
+                    I::I64Const(10),
+                    I::I64DivU,
+                    I::LocalSet(3),
+                    I::LocalGet(2),
+                    I::I32Const(1),
+                    I::I32Add,
+                    I::LocalSet(2),
+                    I::Br(0),
+                    I::End,
+                    I::End,
+                    // width = max(width, min_width)
+                    I::LocalGet(1),
+                    I::LocalGet(2),
+                    I::LocalGet(1),
+                    I::LocalGet(2),
+                    I::I32GtS,
+                    I::Select,
+                    I::LocalSet(2),
+                    I::LocalGet(2),
+                    I::I32Const(element_size(&Type::Char) as i32),
+                    I::I32Mul,
+                    I::I32Const(HEADER_SIZE as i32),
+                    I::I32Add,
+                    call(RuntimeFunction::Alloc),
+                    I::LocalTee(4),
+                    I::LocalGet(2),
+                    store_length(),
+                    // write the digits from last to first, starting just
+                    // past the end of the string
+                    I::LocalGet(4),
+                    I::LocalGet(2),
+                    I::I32Const(element_size(&Type::Char) as i32),
+                    I::I32Mul,
+                    I::I32Add,
+                    I::I32Const(HEADER_SIZE as i32),
+                    I::I32Add,
+                    I::LocalSet(5),
+                    I::Block(BlockType::Empty),
+                    I::Loop(BlockType::Empty),
+                    I::LocalGet(5),
+                    I::LocalGet(4),
+                    I::I32Const(HEADER_SIZE as i32),
+                    I::I32Add,
+                    I::I32LeU,
+                    I::BrIf(1),
+                    I::LocalGet(5),
+                    I::I32Const(element_size(&Type::Char) as i32),
+                    I::I32Sub,
+                    I::LocalTee(5),
+                    I::LocalGet(0),
+                    I::I64Const(10),
+                    I::I64RemU,
+                    I::I32WrapI64,
+                    I::I32Const('0' as i32),
+                    I::I32Add,
+                    store(&Type::Char, 0),
+                    I::LocalGet(0),
+                    I::I64Const(10),
+                    I::I64DivU,
+                    I::LocalSet(0),
+                    I::Br(0),
+                    I::End,
+                    I::End,
+                    I::LocalGet(4),
+                    I::End,
+                ],
+            ),
+
+            // params: value (0), locals: string (1)
+            RuntimeFunction::IntToString => (
+                vec![ValType::I32],
+                [
+                    vec![
+                        I::LocalGet(0),
+                        I::I64Const(0),
+                        I::I64LtS,
+                        I::If(BlockType::Result(ValType::I32)),
+                    ],
+                    string_constant("-", 1, runtime_start),
+                    vec![
+                        // the magnitude of the smallest int does not fit in
+                        // an int but is correct when treated as unsigned
+                        I::I64Const(0),
+                        I::LocalGet(0),
+                        I::I64Sub,
+                        I::I32Const(1),
+                        call(RuntimeFunction::Digits),
+                        call(RuntimeFunction::Concat),
+                        I::Else,
+                        I::LocalGet(0),
+                        I::I32Const(1),
+                        call(RuntimeFunction::Digits),
+                        I::End,
+                        I::End,
+                    ],
+                ]
+                .concat(),
+            ),
+
+            // params: value (0), locals: exponent (1), integer_part (2),
+            // decimal_places (3), fraction (4), result (5), string (6),
+            // negative (7)
+            RuntimeFunction::FloatToString => (
+                vec![
+                    ValType::I64,
+                    ValType::I64,
+                    ValType::I32,
+                    ValType::I64,
+                    ValType::I32,
+                    ValType::I32,
+                    ValType::I32,
+                ],
+                [
+                    // NaN is the only value not equal to itself
+                    vec![
+                        I::LocalGet(0),
+                        I::LocalGet(0),
+                        I::F64Ne,
+                        I::If(BlockType::Empty),
+                    ],
+                    string_constant("NaN", 6, runtime_start),
+                    vec![
+                        I::Return,
+                        I::End,
+                        I::LocalGet(0),
+                        I::F64Const(0.0),
+                        I::F64Lt,
+                        I::LocalSet(7),
+                        I::LocalGet(0),
+                        I::F64Abs,
+                        I::LocalSet(0),
+                        I::LocalGet(0),
+                        I::F64Const(f64::INFINITY),
+                        I::F64Eq,
+                        I::If(BlockType::Empty),
+                        I::LocalGet(7),
+                        I::If(BlockType::Result(ValType::I32)),
+                    ],
+                    string_constant("-inf", 6, runtime_start),
+                    vec![I::Else],
+                    string_constant("inf", 6, runtime_start),
+                    vec![
+                        I::End,
+                        I::Return,
+                        I::End,
+                        // normalise very large or small magnitudes to between
+                        // 1 and 10, counting the exponent
+                        I::LocalGet(0),
+                        I::F64Const(1e15),
+                        I::F64Ge,
+                        I::If(BlockType::Empty),
+                        I::Block(BlockType::Empty),
+                        I::Loop(BlockType::Empty),
+                        I::LocalGet(0),
+                        I::F64Const(10.0),
+                        I::F64Lt,
+                        I::BrIf(1),
+                        I::LocalGet(0),
+                        I::F64Const(10.0),
+                        I::F64Div,
+                        I::LocalSet(0),
+                        I::LocalGet(1),
+                        I::I64Const(1),
+                        I::I64Add,
+                        I::LocalSet(1),
+                        I::Br(0),
+                        I::End,
+                        I::End,
+                        I::Else,
+                        I::LocalGet(0),
+                        I::F64Const(1e-4),
+                        I::F64Lt,
+                        I::LocalGet(0),
+                        I::F64Const(0.0),
+                        I::F64Ne,
+                        I::I32And,
+                        I::If(BlockType::Empty),
+                        I::Block(BlockType::Empty),
+                        I::Loop(BlockType::Empty),
+                        I::LocalGet(0),
+                        I::F64Const(1.0),
+                        I::F64Ge,
+                        I::BrIf(1),
+                        I::LocalGet(0),
+                        I::F64Const(10.0),
+                        I::F64Mul,
+                        I::LocalSet(0),
+                        I::LocalGet(1),
+                        I::I64Const(1),
+                        I::I64Sub,
+                        I::LocalSet(1),
+                        I::Br(0),
+                        I::End,
+                        I::End,
+                        I::End,
+                        I::End,
+                        // round the fractional part to six decimal places
+                        // (carrying into the integer part if need be), then
+                        // drop trailing zeros while keeping at least one
+                        // decimal place
+                        I::LocalGet(0),
+                        I::I64TruncF64U,
+                        I::LocalSet(2),
+                        I::LocalGet(0),
+                        I::LocalGet(0),
+                        I::F64Trunc,
+                        I::F64Sub,
+                        I::F64Const(1e6),
+                        I::F64Mul,
+                        I::F64Nearest,
+                        I::I64TruncF64U,
+                        I::LocalTee(4),
+                        I::I64Const(1_000_000),
+                        I::I64Eq,
+                        I::If(BlockType::Empty),
+                        I::LocalGet(2),
+                        I::I64Const(1),
+                        I::I64Add,
+                        I::LocalSet(2),
+                        I::I64Const(0),
+                        I::LocalSet(4),
+                        I::End,
+                        I::I32Const(6),
+                        I::LocalSet(3),
+                        I::Block(BlockType::Empty),
+                        I::Loop(BlockType::Empty),
+                        I::LocalGet(3),
+                        I::I32Const(1),
+                        I::I32LeS,
+                        I::BrIf(1),
+                        I::LocalGet(4),
+                        I::I64Const(10),
+                        I::I64RemU,
+                        I::I64Const(0),
+                        I::I64Ne,
+                        I::BrIf(1),
+                        I::LocalGet(4),
+                        I::I64Const(10),
+                        I::I64DivU,
+                        I::LocalSet(4),
+                        I::LocalGet(3),
+                        I::I32Const(1),
+                        I::I32Sub,
+                        I::LocalSet(3),
+                        I::Br(0),
+                        I::End,
+                        I::End,
+                        I::LocalGet(2),
+                        I::I32Const(1),
+                        call(RuntimeFunction::Digits),
+                    ],
+                    string_constant(".", 6, runtime_start),
+                    vec![
+                        call(RuntimeFunction::Concat),
+                        I::LocalGet(4),
+                        I::LocalGet(3),
+                        call(RuntimeFunction::Digits),
+                        call(RuntimeFunction::Concat),
+                        I::LocalSet(5),
+                        I::LocalGet(7),
+                        I::If(BlockType::Empty),
+                    ],
+                    string_constant("-", 6, runtime_start),
+                    vec![
+                        I::LocalGet(5),
+                        call(RuntimeFunction::Concat),
+                        I::LocalSet(5),
+                        I::End,
+                        I::LocalGet(1),
+                        I::I64Const(0),
+                        I::I64Ne,
+                        I::If(BlockType::Empty),
+                        I::LocalGet(5),
+                    ],
+                    string_constant("e", 6, runtime_start),
+                    vec![
+                        I::LocalGet(1),
+                        call(RuntimeFunction::IntToString),
+                        call(RuntimeFunction::Concat),
+                        call(RuntimeFunction::Concat),
+                        I::LocalSet(5),
+                        I::End,
+                        I::LocalGet(5),
+                        I::End,
+                    ],
+                ]
+                .concat(),
+            ),
+
+            // params: value (0), locals: string (1)
+            RuntimeFunction::CharToString => (
+                vec![ValType::I32],
+                vec![
+                    I::I32Const((HEADER_SIZE + element_size(&Type::Char)) as i32),
+                    call(RuntimeFunction::Alloc),
+                    I::LocalTee(1),
+                    I::I32Const(1),
+                    store_length(),
+                    I::LocalGet(1),
+                    I::LocalGet(0),
+                    store(&Type::Char, HEADER_SIZE),
+                    I::LocalGet(1),
+                    I::End,
+                ],
+            ),
+
+            // params: value (0), locals: string (1)
+            RuntimeFunction::BoolToString => (
+                vec![ValType::I32],
+                [
+                    vec![I::LocalGet(0), I::If(BlockType::Result(ValType::I32))],
+                    string_constant("true", 1, runtime_start),
+                    vec![I::Else],
+                    string_constant("false", 1, runtime_start),
+                    vec![I::End, I::End],
+                ]
+                .concat(),
+            ),
+        };
+
+        let mut function = Function::new_with_locals_types(locals);
+        for instruction in &instructions {
+            function.instruction(instruction);
+        }
+        function
+    }
+}
+
+/// Instructions which allocate a new string with the given contents, leaving
+/// its address on the stack. The `i32` local `string` is overwritten.
+fn string_constant(contents: &str, string: u32, runtime_start: u32) -> Vec<Instruction<'static>> {
+    let length = contents.chars().count() as u32;
+    let mut instructions = vec![
+        Instruction::I32Const((HEADER_SIZE + length * element_size(&Type::Char)) as i32),
+        Instruction::Call(RuntimeFunction::Alloc.index(runtime_start)),
+        Instruction::LocalTee(string),
+        Instruction::I32Const(length as i32),
+        store_length(),
+    ];
+
+    for (i, c) in contents.chars().enumerate() {
+        instructions.extend([
+            Instruction::LocalGet(string),
+            Instruction::I32Const(c as i32),
+            store(
+                &Type::Char,
+                HEADER_SIZE + i as u32 * element_size(&Type::Char),
+            ),
+        ]);
+    }
+
+    instructions.push(Instruction::LocalGet(string));
+    instructions
+}
+
+/// Instructions which copy the chars of the string `source` into the chars of
+/// the string at `destination`, which must have room for them. The `i32`
+/// locals `length` (initially the length of `source`) and `offset` are
+/// overwritten.
+fn copy_chars(
+    source: u32,
+    destination: u32,
+    length: u32,
+    offset: u32,
+) -> Vec<Instruction<'static>> {
+    use Instruction as I;
+
+    vec![
+        I::I32Const(0),
+        I::LocalSet(offset),
+        // count in bytes rather than chars
+        I::LocalGet(length),
+        I::I32Const(element_size(&Type::Char) as i32),
+        I::I32Mul,
+        I::LocalSet(length),
+        I::Block(BlockType::Empty),
+        I::Loop(BlockType::Empty),
+        I::LocalGet(offset),
+        I::LocalGet(length),
+        I::I32GeU,
+        I::BrIf(1),
+        I::LocalGet(destination),
+        I::LocalGet(offset),
+        I::I32Add,
+        I::LocalGet(source),
+        I::LocalGet(offset),
+        I::I32Add,
+        load(&Type::Char, HEADER_SIZE),
+        store(&Type::Char, HEADER_SIZE),
+        I::LocalGet(offset),
+        I::I32Const(element_size(&Type::Char) as i32),
+        I::I32Add,
+        I::LocalSet(offset),
+        I::Br(0),
+        I::End,
+        I::End,
+    ]
+}
+
+/// Instructions which record the given error before trapping.
+pub fn trap(error: RuntimeError) -> Vec<Instruction<'static>> {
+    vec![
+        Instruction::I32Const(error as i32),
+        Instruction::GlobalSet(ERROR_CODE),
+        Instruction::Unreachable,
+    ]
+}
+
+/// Get the WASM type used to represent values of the given Kobe type.
+pub fn value_type(ty: &Type) -> ValType {
+    match ty {
+        Type::Int => ValType::I64,
+        Type::Float => ValType::F64,
+        // strings and lists are pointers into linear memory
+        Type::Char | Type::Bool | Type::String | Type::List(_) => ValType::I32,
+        Type::Void | Type::Unknown => panic!("no value type for '{ty}'"),
+    }
+}
+
+/// Size in bytes of a value of the given type when stored in memory.
+pub fn element_size(ty: &Type) -> u32 {
+    match value_type(ty) {
+        ValType::I64 | ValType::F64 => 8,
+        _ => 4,
+    }
+}
+
+/// Instruction to load a value of the given type from the address on the
+/// stack plus `offset`.
+pub fn load(ty: &Type, offset: u32) -> Instruction<'static> {
+    let mem_arg = mem_arg(offset);
+
+    match value_type(ty) {
+        ValType::I64 => Instruction::I64Load(mem_arg),
+        ValType::F64 => Instruction::F64Load(mem_arg),
+        _ => Instruction::I32Load(mem_arg),
+    }
+}
+
+/// Instruction to store a value of the given type at the address on the stack
+/// plus `offset`.
+pub fn store(ty: &Type, offset: u32) -> Instruction<'static> {
+    let mem_arg = mem_arg(offset);
+
+    match value_type(ty) {
+        ValType::I64 => Instruction::I64Store(mem_arg),
+        ValType::F64 => Instruction::F64Store(mem_arg),
+        _ => Instruction::I32Store(mem_arg),
+    }
+}
+
+/// Instruction to load the length of the string or list at the address on the
+/// stack.
+pub fn load_length() -> Instruction<'static> {
+    Instruction::I32Load(mem_arg(0))
+}
+
+/// Instruction to store the length of a string or list at the address on the
+/// stack.
+pub fn store_length() -> Instruction<'static> {
+    Instruction::I32Store(mem_arg(0))
+}
+
+fn mem_arg(offset: u32) -> MemArg {
+    // values following the 4-byte header are only guaranteed to be 4-byte
+    // aligned, even if they are 8 bytes in size
+    MemArg {
+        offset: offset as u64,
+        align: 2,
+        memory_index: 0,
+    }
+}
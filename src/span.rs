@@ -0,0 +1,77 @@
+use std::{fmt, ops::Range};
+
+/// A position in the input, as a line number and a column within that line
+/// (both starting from 1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Pos {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Pos {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// A region of the input. `start` and `end` are the positions of the first and
+/// last characters in the region (so both are inclusive), while `byte_range` is
+/// the region's offsets in bytes from the start of the input (with the usual
+/// exclusive end).
+///
+/// The default span, at line 0, is used for errors which do not relate to any
+/// particular part of the input (such as the input not being readable at all).
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: Pos,
+    pub end: Pos,
+    pub byte_range: Range<usize>,
+}
+
+impl Span {
+    /// Whether this span points at part of the input, rather than being the
+    /// default span.
+    pub fn has_position(&self) -> bool {
+        self.start.line != 0
+    }
+
+    /// The span covering both this span and the given later span, along with
+    /// anything in between.
+    pub fn to(&self, other: &Span) -> Span {
+        Span {
+            start: self.start,
+            end: other.end,
+            byte_range: self.byte_range.start..other.byte_range.end,
+        }
+    }
+
+    /// An empty span immediately preceding this one, for pointing at where
+    /// something should be inserted.
+    pub fn preceding(&self) -> Span {
+        Span {
+            start: self.start,
+            end: self.start,
+            byte_range: self.byte_range.start..self.byte_range.start,
+        }
+    }
+
+    /// An empty span immediately following this one, for pointing at
+    /// something which is missing.
+    pub fn following(&self) -> Span {
+        let pos = Pos {
+            line: self.end.line,
+            column: self.end.column + 1,
+        };
+        Span {
+            start: pos,
+            end: pos,
+            byte_range: self.byte_range.end..self.byte_range.end,
+        }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
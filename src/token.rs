@@ -1,24 +1,33 @@
 use std::fmt;
 
-#[derive(Debug, PartialEq)]
+use crate::span::Span;
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub tok_type: TokenType,
     pub lexeme: String,
-    pub line_number: usize,
-    pub char_number: usize,
+    /// The value of a literal, decoded from its lexeme by the lexer. `None`
+    /// for all other tokens.
+    pub value: Option<Literal>,
+    pub span: Span,
+}
+
+/// The value of a number, char or string literal.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Int(i64),
+    Float(f64),
+    Char(char),
+    String(String),
 }
 
 impl fmt::Display for Token {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "{} ({:?}) line: {} character: {}",
-            self.lexeme, self.tok_type, self.line_number, self.char_number
-        )
+        write!(f, "{} ({:?}) at {}", self.lexeme, self.tok_type, self.span)
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TokenType {
     Assign,
     Colon,
@@ -42,23 +51,96 @@ pub enum TokenType {
     Equivalent,
     NotEquivalent,
     Arrow,
+    DotDot,
     Not,
     IntLiteral,
     FloatLiteral,
     CharLiteral,
     StringLiteral,
+    /// The segment of an interpolated string up to and including the `{`
+    /// which begins its first interpolated expression.
+    StringStart,
+    /// The segment of an interpolated string from the `}` which ends one
+    /// interpolated expression up to the `{` which begins the next.
+    StringMiddle,
+    /// The segment of an interpolated string from the `}` which ends its last
+    /// interpolated expression up to the closing `"`.
+    StringEnd,
     Identifier,
     DoKeyword,
     EndKeyword,
     ForKeyword,
+    InKeyword,
     WhileKeyword,
     IfKeyword,
     ThenKeyword,
     ElseKeyword,
     FnKeyword,
+    PubKeyword,
     ReturnKeyword,
     AndKeyword,
     OrKeyword,
     LetKeyword,
+    TrueKeyword,
+    FalseKeyword,
     EndStatement, // ';' or '\n'
+    /// A `---` comment documenting the function or variable which follows it.
+    DocComment,
+}
+
+impl fmt::Display for TokenType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            TokenType::Assign => "'='",
+            TokenType::Colon => "':'",
+            TokenType::Comma => "','",
+            TokenType::OpenBracket => "'('",
+            TokenType::CloseBracket => "')'",
+            TokenType::OpenSquare => "'['",
+            TokenType::CloseSquare => "']'",
+            TokenType::Plus => "'+'",
+            TokenType::Minus => "'-'",
+            TokenType::Times => "'*'",
+            TokenType::Divide => "'/'",
+            TokenType::PlusAssign => "'+='",
+            TokenType::MinusAssign => "'-='",
+            TokenType::TimesAssign => "'*='",
+            TokenType::DivideAssign => "'/='",
+            TokenType::LessThan => "'<'",
+            TokenType::GreaterThan => "'>'",
+            TokenType::LessThanOrEqual => "'<='",
+            TokenType::GreaterThanOrEqual => "'>='",
+            TokenType::Equivalent => "'=='",
+            TokenType::NotEquivalent => "'!='",
+            TokenType::Arrow => "'->'",
+            TokenType::DotDot => "'..'",
+            TokenType::Not => "'!'",
+            TokenType::IntLiteral => "integer literal",
+            TokenType::FloatLiteral => "floating-point literal",
+            TokenType::CharLiteral => "character literal",
+            TokenType::StringLiteral => "string literal",
+            TokenType::StringStart => "interpolated string",
+            TokenType::StringMiddle | TokenType::StringEnd => "'}'",
+            TokenType::Identifier => "identifier",
+            TokenType::DoKeyword => "'do'",
+            TokenType::EndKeyword => "'end'",
+            TokenType::ForKeyword => "'for'",
+            TokenType::InKeyword => "'in'",
+            TokenType::WhileKeyword => "'while'",
+            TokenType::IfKeyword => "'if'",
+            TokenType::ThenKeyword => "'then'",
+            TokenType::ElseKeyword => "'else'",
+            TokenType::FnKeyword => "'fn'",
+            TokenType::PubKeyword => "'pub'",
+            TokenType::ReturnKeyword => "'return'",
+            TokenType::AndKeyword => "'and'",
+            TokenType::OrKeyword => "'or'",
+            TokenType::LetKeyword => "'let'",
+            TokenType::TrueKeyword => "'true'",
+            TokenType::FalseKeyword => "'false'",
+            TokenType::EndStatement => "newline or ';'",
+            TokenType::DocComment => "doc comment",
+        };
+        write!(f, "{s}")
+    }
 }
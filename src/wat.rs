@@ -0,0 +1,120 @@
+//! Printing of generated modules in the WebAssembly text format, for
+//! debugging the code generator. The code of each Kobe function is annotated
+//! with the source lines it was generated from, e.g.,
+//!
+//! ```wat
+//! (func $main (;0;) (type 0)
+//!   (local $x i64)
+//!   ;; main.kobe:2
+//!   i64.const 1
+//!   local.set $x
+//! ```
+
+use std::collections::HashMap;
+
+use wasmparser::{Parser, Payload};
+
+use crate::codegen::LineTable;
+
+/// Print a module produced by [`crate::codegen::CodeGenerator::generate`] as
+/// text, with a comment giving the source line (in the input with the given
+/// name) before the code generated for each line.
+pub fn print(wasm: &[u8], line_table: &LineTable, input_source_name: &str) -> String {
+    let lines = lines_by_offset(wasm, line_table);
+
+    let mut storage = String::new();
+    let printed = wasmprinter::Config::new()
+        .offsets_and_lines(wasm, &mut storage)
+        .expect("generated modules are valid");
+
+    let mut out = String::new();
+    for (offset, text) in printed {
+        if let Some(line) = offset.and_then(|offset| lines.get(&offset)) {
+            let indent = &text[..text.len() - text.trim_start().len()];
+            out += &format!("{indent};; {input_source_name}:{line}\n");
+        }
+        out += text;
+        if !text.ends_with('\n') {
+            out += "\n";
+        }
+    }
+    out
+}
+
+/// The source line for each instruction in the module which begins the code
+/// generated for a line, by the instruction's offset in the module.
+fn lines_by_offset(wasm: &[u8], line_table: &LineTable) -> HashMap<usize, usize> {
+    let mut lines = HashMap::new();
+    let mut bodies = Parser::new(0)
+        .parse_all(wasm)
+        .filter_map(|payload| match payload {
+            Ok(Payload::CodeSectionEntry(body)) => Some(body),
+            _ => None,
+        });
+
+    // the Kobe functions come first, with the runtime functions (which have
+    // no source lines) after them
+    for (function_lines, body) in line_table.iter().zip(&mut bodies) {
+        let offsets: Vec<usize> = body
+            .get_operators_reader()
+            .expect("generated modules are valid")
+            .into_iter_with_offsets()
+            .map(|result| result.expect("generated modules are valid").1)
+            .collect();
+        for &(instruction, line) in function_lines {
+            lines.insert(offsets[instruction], line);
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{check::TypeChecker, codegen::CodeGenerator, lex::Lexer, parse::Parser};
+
+    fn generate(input: &str) -> (Vec<u8>, LineTable) {
+        let lexer = Lexer::new(Cursor::new(input), "main.kobe".to_string());
+        let mut checker = TypeChecker::new(Parser::new(lexer));
+        let functions = checker.parser_mut().parse_all().unwrap();
+        let (functions, _) = checker.check(functions).unwrap();
+        CodeGenerator::new(functions).generate()
+    }
+
+    const PROGRAM: &str = "\
+fn main() -> int
+  let total = 0
+  for x in [1, 2, 3] do
+    total += x
+  end
+  let s = \"total: {total} {1.5}\"
+  return total
+end";
+
+    #[test]
+    fn names_and_lines() {
+        let (wasm, line_table) = generate(PROGRAM);
+        let text = print(&wasm, &line_table, "main.kobe");
+
+        assert!(text.contains("(func $main (;0;)"));
+        assert!(text.contains("(local $total i64)"));
+        assert!(text.contains(
+            "    ;; main.kobe:2\n    \
+             i64.const 0\n    \
+             local.set $total\n    \
+             ;; main.kobe:3\n"
+        ));
+        assert!(text.contains("call $runtime.concat"));
+        // comments are only added to Kobe functions
+        assert_eq!(text.matches(";; main.kobe:").count(), 5);
+    }
+
+    #[test]
+    fn round_trip() {
+        let (wasm, line_table) = generate(PROGRAM);
+        let text = print(&wasm, &line_table, "main.kobe");
+        assert_eq!(wat::parse_str(&text).unwrap(), wasm);
+    }
+}